@@ -0,0 +1 @@
+DROP TABLE IF EXISTS fittrack.personal_records;
@@ -0,0 +1,16 @@
+-- Personal Records (one row per PR broken, latest row per type is the current record)
+CREATE TABLE fittrack.personal_records (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES fittrack.users(id) ON DELETE CASCADE,
+    variation_id INTEGER NOT NULL REFERENCES fittrack.variations(id) ON DELETE CASCADE,
+    set_id INTEGER REFERENCES fittrack.sets(id) ON DELETE SET NULL,
+    workout_session_id INTEGER REFERENCES fittrack.workout_sessions(id) ON DELETE SET NULL,
+    record_type VARCHAR(20) NOT NULL,
+    value FLOAT NOT NULL,
+    weight FLOAT NOT NULL,
+    reps INTEGER NOT NULL,
+    achieved_on DATE NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX personal_records_user_variation_idx ON fittrack.personal_records (user_id, variation_id, record_type);
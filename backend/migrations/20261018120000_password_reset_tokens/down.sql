@@ -0,0 +1 @@
+DROP TABLE IF EXISTS fittrack.password_reset_tokens;
@@ -0,0 +1,11 @@
+-- Password Reset Tokens (single use, only the hash of the emailed token is stored)
+CREATE TABLE fittrack.password_reset_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES fittrack.users(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    used_at TIMESTAMP
+);
+
+CREATE INDEX password_reset_tokens_user_idx ON fittrack.password_reset_tokens (user_id);
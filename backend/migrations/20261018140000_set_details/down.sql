@@ -0,0 +1,12 @@
+DROP INDEX IF EXISTS fittrack.sets_session_order_idx;
+ALTER TABLE fittrack.sets
+    DROP CONSTRAINT IF EXISTS sets_rest_seconds_check,
+    DROP CONSTRAINT IF EXISTS sets_rir_check,
+    DROP CONSTRAINT IF EXISTS sets_rpe_check,
+    DROP CONSTRAINT IF EXISTS sets_set_type_check,
+    DROP COLUMN IF EXISTS tempo,
+    DROP COLUMN IF EXISTS rest_seconds,
+    DROP COLUMN IF EXISTS rir,
+    DROP COLUMN IF EXISTS rpe,
+    DROP COLUMN IF EXISTS set_type,
+    DROP COLUMN IF EXISTS set_index;
@@ -0,0 +1,24 @@
+-- Per-set details: position within the session, set type and effort
+ALTER TABLE fittrack.sets
+    ADD COLUMN set_index INTEGER,
+    ADD COLUMN set_type VARCHAR(10) NOT NULL DEFAULT 'working',
+    ADD COLUMN rpe FLOAT,
+    ADD COLUMN rir INTEGER,
+    ADD COLUMN rest_seconds INTEGER,
+    ADD COLUMN tempo VARCHAR(16),
+    ADD CONSTRAINT sets_set_type_check CHECK (set_type IN ('warmup', 'working', 'drop', 'failure', 'amrap')),
+    ADD CONSTRAINT sets_rpe_check CHECK (rpe IS NULL OR (rpe >= 1 AND rpe <= 10)),
+    ADD CONSTRAINT sets_rir_check CHECK (rir IS NULL OR rir >= 0),
+    ADD CONSTRAINT sets_rest_seconds_check CHECK (rest_seconds IS NULL OR rest_seconds >= 0);
+
+-- Existing session sets keep the order they were logged in
+UPDATE fittrack.sets s
+SET set_index = numbered.idx
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY workout_session_id ORDER BY id) AS idx
+    FROM fittrack.sets
+    WHERE workout_session_id IS NOT NULL
+) numbered
+WHERE s.id = numbered.id;
+
+CREATE INDEX sets_session_order_idx ON fittrack.sets (workout_session_id, set_index);
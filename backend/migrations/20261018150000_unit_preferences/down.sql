@@ -0,0 +1,5 @@
+ALTER TABLE fittrack.users
+    DROP CONSTRAINT IF EXISTS users_length_unit_check,
+    DROP CONSTRAINT IF EXISTS users_weight_unit_check,
+    DROP COLUMN IF EXISTS length_unit,
+    DROP COLUMN IF EXISTS weight_unit;
@@ -0,0 +1,6 @@
+-- Display units per user, weights and lengths are always stored in kg and cm
+ALTER TABLE fittrack.users
+    ADD COLUMN weight_unit VARCHAR(2) NOT NULL DEFAULT 'kg',
+    ADD COLUMN length_unit VARCHAR(2) NOT NULL DEFAULT 'cm',
+    ADD CONSTRAINT users_weight_unit_check CHECK (weight_unit IN ('kg', 'lb')),
+    ADD CONSTRAINT users_length_unit_check CHECK (length_unit IN ('cm', 'in'));
@@ -0,0 +1,11 @@
+ALTER TABLE fittrack.cardio_logs
+    DROP CONSTRAINT IF EXISTS cardio_logs_calories_check,
+    DROP CONSTRAINT IF EXISTS cardio_logs_heart_rate_check,
+    DROP CONSTRAINT IF EXISTS cardio_logs_elevation_check,
+    DROP CONSTRAINT IF EXISTS cardio_logs_distance_check,
+    DROP COLUMN IF EXISTS started_at,
+    DROP COLUMN IF EXISTS calories,
+    DROP COLUMN IF EXISTS max_heart_rate,
+    DROP COLUMN IF EXISTS avg_heart_rate,
+    DROP COLUMN IF EXISTS elevation_gain_m,
+    DROP COLUMN IF EXISTS distance_m;
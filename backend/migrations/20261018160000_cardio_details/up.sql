@@ -0,0 +1,16 @@
+-- Distance and effort for cardio logs, distances are stored in metres
+ALTER TABLE fittrack.cardio_logs
+    ADD COLUMN distance_m FLOAT,
+    ADD COLUMN elevation_gain_m FLOAT,
+    ADD COLUMN avg_heart_rate INTEGER,
+    ADD COLUMN max_heart_rate INTEGER,
+    ADD COLUMN calories INTEGER,
+    ADD COLUMN started_at TIMESTAMP,
+    ADD CONSTRAINT cardio_logs_distance_check CHECK (distance_m IS NULL OR distance_m >= 0),
+    ADD CONSTRAINT cardio_logs_elevation_check CHECK (elevation_gain_m IS NULL OR elevation_gain_m >= 0),
+    ADD CONSTRAINT cardio_logs_heart_rate_check CHECK (
+        (avg_heart_rate IS NULL OR avg_heart_rate > 0)
+        AND (max_heart_rate IS NULL OR max_heart_rate > 0)
+        AND (avg_heart_rate IS NULL OR max_heart_rate IS NULL OR max_heart_rate >= avg_heart_rate)
+    ),
+    ADD CONSTRAINT cardio_logs_calories_check CHECK (calories IS NULL OR calories >= 0);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS fittrack.cardio_track_points;
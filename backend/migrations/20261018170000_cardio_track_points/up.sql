@@ -0,0 +1,14 @@
+-- Points parsed from imported GPX/TCX/FIT files, kept for drawing routes and HR charts
+CREATE TABLE fittrack.cardio_track_points (
+    id BIGSERIAL PRIMARY KEY,
+    cardio_log_id INTEGER NOT NULL REFERENCES fittrack.cardio_logs(id) ON DELETE CASCADE,
+    seq INTEGER NOT NULL,
+    recorded_at TIMESTAMP,
+    latitude FLOAT,
+    longitude FLOAT,
+    elevation_m FLOAT,
+    heart_rate INTEGER,
+    distance_m FLOAT
+);
+
+CREATE UNIQUE INDEX cardio_track_points_log_seq_idx ON fittrack.cardio_track_points (cardio_log_id, seq);
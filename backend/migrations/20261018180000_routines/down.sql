@@ -0,0 +1,4 @@
+ALTER TABLE fittrack.cardio_logs DROP COLUMN IF EXISTS completed;
+ALTER TABLE fittrack.sets DROP COLUMN IF EXISTS completed;
+DROP TABLE IF EXISTS fittrack.routine_exercises;
+DROP TABLE IF EXISTS fittrack.routines;
@@ -0,0 +1,35 @@
+-- Reusable workout plans, started sessions get one planned row per target set
+CREATE TABLE fittrack.routines (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES fittrack.users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    notes TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX routines_user_idx ON fittrack.routines (user_id);
+
+-- Each entry targets either a strength variation or a cardio exercise
+CREATE TABLE fittrack.routine_exercises (
+    id SERIAL PRIMARY KEY,
+    routine_id INTEGER NOT NULL REFERENCES fittrack.routines(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    variation_id INTEGER REFERENCES fittrack.variations(id) ON DELETE CASCADE,
+    cardio_exercise_id INTEGER REFERENCES fittrack.cardio_exercises(id) ON DELETE CASCADE,
+    target_sets INTEGER NOT NULL DEFAULT 1,
+    reps_min INTEGER,
+    reps_max INTEGER,
+    target_weight FLOAT,
+    rest_seconds INTEGER,
+    target_duration_minutes INTEGER,
+    notes TEXT,
+    CONSTRAINT routine_exercises_target_check CHECK ((variation_id IS NULL) <> (cardio_exercise_id IS NULL)),
+    CONSTRAINT routine_exercises_sets_check CHECK (target_sets >= 1),
+    CONSTRAINT routine_exercises_reps_check CHECK (reps_min IS NULL OR reps_max IS NULL OR reps_max >= reps_min),
+    CONSTRAINT routine_exercises_position_unique UNIQUE (routine_id, position)
+);
+
+-- Planned rows from a routine stay out of records and analytics until they are completed
+ALTER TABLE fittrack.sets ADD COLUMN completed BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE fittrack.cardio_logs ADD COLUMN completed BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,5 @@
+ALTER TABLE fittrack.workout_sessions DROP COLUMN IF EXISTS program_day_id;
+DROP TABLE IF EXISTS fittrack.progression_rules;
+DROP TABLE IF EXISTS fittrack.program_days;
+DROP TABLE IF EXISTS fittrack.program_weeks;
+DROP TABLE IF EXISTS fittrack.programs;
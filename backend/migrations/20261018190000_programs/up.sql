@@ -0,0 +1,53 @@
+-- Periodized blocks: a program is a list of weeks, each week an ordered list of routine days
+CREATE TABLE fittrack.programs (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES fittrack.users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    notes TEXT,
+    deload_percent FLOAT NOT NULL DEFAULT 60,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT programs_deload_percent_check CHECK (deload_percent > 0 AND deload_percent <= 100)
+);
+
+CREATE INDEX programs_user_idx ON fittrack.programs (user_id);
+
+CREATE TABLE fittrack.program_weeks (
+    id SERIAL PRIMARY KEY,
+    program_id INTEGER NOT NULL REFERENCES fittrack.programs(id) ON DELETE CASCADE,
+    week_number INTEGER NOT NULL,
+    deload BOOLEAN NOT NULL DEFAULT FALSE,
+    CONSTRAINT program_weeks_number_unique UNIQUE (program_id, week_number)
+);
+
+-- A routine can't be deleted while a program uses it, which would silently drop the day and the
+-- link of every session started from it. The check is deferred to commit rather than RESTRICT so
+-- that deleting the user, which cascades to both routines and programs, is still allowed.
+CREATE TABLE fittrack.program_days (
+    id SERIAL PRIMARY KEY,
+    program_week_id INTEGER NOT NULL REFERENCES fittrack.program_weeks(id) ON DELETE CASCADE,
+    day_number INTEGER NOT NULL,
+    routine_id INTEGER NOT NULL REFERENCES fittrack.routines(id) DEFERRABLE INITIALLY DEFERRED,
+    CONSTRAINT program_days_number_unique UNIQUE (program_week_id, day_number)
+);
+
+-- A rule without a variation applies to every strength exercise in the program
+CREATE TABLE fittrack.progression_rules (
+    id SERIAL PRIMARY KEY,
+    program_id INTEGER NOT NULL REFERENCES fittrack.programs(id) ON DELETE CASCADE,
+    variation_id INTEGER REFERENCES fittrack.variations(id) ON DELETE CASCADE,
+    rule_type VARCHAR(20) NOT NULL,
+    increment FLOAT,
+    percent FLOAT,
+    CONSTRAINT progression_rules_type_check CHECK (rule_type IN ('linear', 'double', 'e1rm')),
+    CONSTRAINT progression_rules_increment_check CHECK (increment IS NULL OR increment >= 0),
+    CONSTRAINT progression_rules_percent_check CHECK (percent IS NULL OR (percent > 0 AND percent <= 100))
+);
+
+CREATE UNIQUE INDEX progression_rules_variation_unique ON fittrack.progression_rules (program_id, COALESCE(variation_id, 0));
+
+-- Sessions started from a program remember which day they were, so the next one can be worked out
+ALTER TABLE fittrack.workout_sessions
+    ADD COLUMN program_day_id INTEGER REFERENCES fittrack.program_days(id) ON DELETE SET NULL;
+
+CREATE INDEX workout_sessions_program_day_idx ON fittrack.workout_sessions (program_day_id);
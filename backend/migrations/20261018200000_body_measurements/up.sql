@@ -0,0 +1,30 @@
+-- Body weight and girth history, users.weight mirrors the latest weight reading
+CREATE TABLE fittrack.body_measurements (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES fittrack.users(id) ON DELETE CASCADE,
+    measured_on DATE NOT NULL,
+    weight FLOAT,
+    body_fat_percent FLOAT,
+    neck FLOAT,
+    chest FLOAT,
+    waist FLOAT,
+    hips FLOAT,
+    arms FLOAT,
+    thighs FLOAT,
+    notes TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT body_measurements_weight_check CHECK (weight IS NULL OR weight > 0),
+    CONSTRAINT body_measurements_body_fat_check CHECK (body_fat_percent IS NULL OR (body_fat_percent >= 0 AND body_fat_percent <= 100)),
+    CONSTRAINT body_measurements_girth_check CHECK (
+        (neck IS NULL OR neck > 0) AND (chest IS NULL OR chest > 0) AND (waist IS NULL OR waist > 0)
+        AND (hips IS NULL OR hips > 0) AND (arms IS NULL OR arms > 0) AND (thighs IS NULL OR thighs > 0)
+    )
+);
+
+CREATE INDEX body_measurements_user_date_idx ON fittrack.body_measurements (user_id, measured_on);
+
+-- Keep the weight users already have as their first reading. When it was last changed isn't
+-- recorded, so it's dated to sign-up; accounts without a created_at get a synthetic reading
+-- dated the day the migration runs.
+INSERT INTO fittrack.body_measurements (user_id, measured_on, weight)
+SELECT id, COALESCE(created_at::date, CURRENT_DATE), weight FROM fittrack.users WHERE weight IS NOT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE fittrack.users DROP CONSTRAINT IF EXISTS users_sex_check;
+ALTER TABLE fittrack.users DROP COLUMN IF EXISTS sex;
@@ -0,0 +1,4 @@
+-- Needed by the Mifflin-St Jeor BMR equation, left empty until the user sets it
+ALTER TABLE fittrack.users
+    ADD COLUMN sex VARCHAR(6),
+    ADD CONSTRAINT users_sex_check CHECK (sex IS NULL OR sex IN ('male', 'female'));
@@ -0,0 +1 @@
+ALTER TABLE fittrack.cardio_exercises DROP COLUMN IF EXISTS met;
@@ -0,0 +1,16 @@
+-- Metabolic equivalent per cardio exercise, used to estimate calories burned when none are logged
+ALTER TABLE fittrack.cardio_exercises
+    ADD COLUMN met DOUBLE PRECISION NOT NULL DEFAULT 7.0 CHECK (met > 0 AND met <= 25);
+
+UPDATE fittrack.cardio_exercises SET met = CASE
+    WHEN name ILIKE '%walk%' THEN 3.5
+    WHEN name ILIKE '%hik%' THEN 6.0
+    WHEN name ILIKE '%run%' OR name ILIKE '%jog%' THEN 9.8
+    WHEN name ILIKE '%cycl%' OR name ILIKE '%bike%' OR name ILIKE '%spin%' THEN 7.5
+    WHEN name ILIKE '%swim%' THEN 8.0
+    WHEN name ILIKE '%row%' THEN 7.0
+    WHEN name ILIKE '%elliptical%' THEN 5.0
+    WHEN name ILIKE '%stair%' THEN 9.0
+    WHEN name ILIKE '%rope%' THEN 11.0
+    ELSE met
+END;
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS fittrack.account_deletions;
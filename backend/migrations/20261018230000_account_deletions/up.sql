@@ -0,0 +1,13 @@
+-- One row per erased account, so erasure requests can be shown to have been carried out. It holds
+-- nothing that leads back to the user: the row is keyed by a random reference handed to them as a
+-- receipt, not by the former user id, alongside when it happened and how much was removed.
+CREATE TABLE fittrack.account_deletions (
+    id SERIAL PRIMARY KEY,
+    deletion_id TEXT NOT NULL UNIQUE,
+    deleted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    exported BOOLEAN NOT NULL DEFAULT FALSE,
+    sessions INTEGER NOT NULL DEFAULT 0,
+    sets INTEGER NOT NULL DEFAULT 0,
+    cardio_logs INTEGER NOT NULL DEFAULT 0,
+    tokens_revoked INTEGER NOT NULL DEFAULT 0
+);
@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS fittrack.cardio_exercises_search_idx;
+DROP INDEX IF EXISTS fittrack.variations_search_idx;
+DROP INDEX IF EXISTS fittrack.workout_sessions_search_idx;
+ALTER TABLE fittrack.cardio_exercises DROP COLUMN IF EXISTS search_vector;
+ALTER TABLE fittrack.variations DROP COLUMN IF EXISTS search_vector;
+ALTER TABLE fittrack.workout_sessions DROP COLUMN IF EXISTS search_vector;
@@ -0,0 +1,21 @@
+-- Search documents kept up to date by Postgres. Names and titles weigh more than free text.
+-- The columns are only read by the search query, so they're left out of src/schema.rs to keep
+-- the existing models' default selects unchanged; src/schema.patch strips them when print_schema
+-- regenerates the file.
+ALTER TABLE fittrack.workout_sessions ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('english'::regconfig, coalesce(title, '')), 'A') ||
+    setweight(to_tsvector('english'::regconfig, coalesce(notes, '')), 'B')
+) STORED;
+
+ALTER TABLE fittrack.variations ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('english'::regconfig, coalesce(name, '')), 'A') ||
+    setweight(to_tsvector('english'::regconfig, coalesce(description, '')), 'B')
+) STORED;
+
+ALTER TABLE fittrack.cardio_exercises ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('english'::regconfig, coalesce(name, '')), 'A')
+) STORED;
+
+CREATE INDEX workout_sessions_search_idx ON fittrack.workout_sessions USING GIN (search_vector);
+CREATE INDEX variations_search_idx ON fittrack.variations USING GIN (search_vector);
+CREATE INDEX cardio_exercises_search_idx ON fittrack.cardio_exercises USING GIN (search_vector);
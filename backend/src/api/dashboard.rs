@@ -1,7 +1,7 @@
-use actix_web::{HttpResponse, Responder, web};
+use actix_web::{HttpResponse, web};
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use crate::{api::middleware::AuthenticatedUser, services::get_service::{GetService}};
+use crate::{api::{middleware::AuthenticatedUser, validation::{Validate, ValidatedJson, ValidatedQuery, Validator}}, error::{ApiError, ApiResult}, services::get_service::{Bucket, GetService, OneRepMaxFormula}};
 
 #[derive(Debug,Deserialize,Serialize)]
 pub struct MonthlyWorkoutRequest{
@@ -15,6 +15,15 @@ pub struct PerformanceRequest{
     pub end_date: String
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OneRepMaxRequest{
+    pub variation_id: i32,
+    pub start_date: String,
+    pub end_date: String,
+    pub formula: Option<OneRepMaxFormula>,
+    pub group_by: Option<Bucket>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MuscleGroupSummaryRequest{
     pub muscle_group_ids: Vec<i32>,
@@ -22,6 +31,62 @@ pub struct MuscleGroupSummaryRequest{
     pub end_date: String
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CardioSummaryQuery{
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub group_by: Option<Bucket>,
+    pub cardio_exercise_id: Option<i32>,
+}
+
+pub const MAX_RANGE_DAYS: i64 = 5 * 366;
+
+fn validate_date_range(v: &mut Validator, start_date: &str, end_date: &str){
+    let start = v.date("start_date", start_date);
+    let end = v.date("end_date", end_date);
+    if let (Some(start), Some(end)) = (start, end) {
+        v.check(start <= end, "end_date", "must not be before start_date")
+         .check((end - start).num_days() <= MAX_RANGE_DAYS, "end_date", "range can't span more than 5 years");
+    }
+}
+
+impl Validate for MonthlyWorkoutRequest{
+    fn validate(&self, v: &mut Validator){
+        v.range("year", self.year, 1900, 9999)
+         .range("month", self.month, 1, 12);
+    }
+}
+
+impl Validate for PerformanceRequest{
+    fn validate(&self, v: &mut Validator){
+        v.check(self.variation_id > 0, "variation_id", "must reference a variation");
+        validate_date_range(v, &self.start_date, &self.end_date);
+    }
+}
+
+impl Validate for OneRepMaxRequest{
+    fn validate(&self, v: &mut Validator){
+        v.check(self.variation_id > 0, "variation_id", "must reference a variation");
+        validate_date_range(v, &self.start_date, &self.end_date);
+    }
+}
+
+impl Validate for CardioSummaryQuery{
+    fn validate(&self, v: &mut Validator){
+        v.check(self.start_date <= self.end_date, "end_date", "must not be before start_date")
+         .check((self.end_date - self.start_date).num_days() <= MAX_RANGE_DAYS, "end_date", "range can't span more than 5 years")
+         .check(self.cardio_exercise_id.is_none_or(|id| id > 0), "cardio_exercise_id", "must reference a cardio exercise");
+    }
+}
+
+impl Validate for MuscleGroupSummaryRequest{
+    fn validate(&self, v: &mut Validator){
+        v.check(!self.muscle_group_ids.is_empty(), "muscle_group_ids", "must not be empty")
+         .check(self.muscle_group_ids.iter().all(|id| *id > 0), "muscle_group_ids", "must only contain valid ids");
+        validate_date_range(v, &self.start_date, &self.end_date);
+    }
+}
+
 pub struct Dashboard;
 impl Dashboard{
     pub fn new() -> Self{
@@ -31,64 +96,90 @@ impl Dashboard{
     pub async fn monthly_workout_levels_handler(
         get_service:web::Data<GetService>,
         user: AuthenticatedUser,
-        payload: web::Json<MonthlyWorkoutRequest>,
-    ) -> impl Responder{
+        payload: ValidatedJson<MonthlyWorkoutRequest>,
+    ) -> ApiResult<HttpResponse>{
         let user_id = user.id;
         let payload_inner = payload.into_inner();
         let year = payload_inner.year;
         let month = payload_inner.month;
 
-        match get_service.get_workout_levels(user_id, year, month).await{
-            Ok(sessions) => return HttpResponse::Ok().json(sessions),
-            Err(err) => return HttpResponse::InternalServerError().body(err.to_string())
-        };
-        // HttpResponse::Ok().json({})
+        let sessions = get_service.get_workout_levels(user_id, year, month).await?;
+        Ok(HttpResponse::Ok().json(sessions))
     }
 
     pub async fn performance_data_handler(
         get_service: web::Data<GetService>,
         user: AuthenticatedUser,
-        payload: web::Json<PerformanceRequest>
-    ) -> impl Responder{
+        payload: ValidatedJson<PerformanceRequest>
+    ) -> ApiResult<HttpResponse>{
 
         let user_id = user.id;
         let payload_inner = payload.into_inner();
         let variation_id = payload_inner.variation_id;
         let start_date = match NaiveDate::parse_from_str(&payload_inner.start_date, "%Y-%m-%d"){
             Ok(date) => date,
-            Err(err) => return HttpResponse::BadRequest().body(format!("Invalid start_date format: {}", err))
+            Err(err) => return Err(ApiError::BadRequest(format!("Invalid start_date format: {}", err)))
         };
         let end_date = match NaiveDate::parse_from_str(&payload_inner.end_date, "%Y-%m-%d"){
             Ok(date) => date,
-            Err(err) => return HttpResponse::BadRequest().body(format!("Invalid end_date format: {}", err))
+            Err(err) => return Err(ApiError::BadRequest(format!("Invalid end_date format: {}", err)))
         };
-        match get_service.get_performance_details(user_id, variation_id, start_date, end_date).await{
-            Ok(data) => return HttpResponse::Ok().json(data),
-            Err(err) => return HttpResponse::InternalServerError().body(err.to_string())
-        }
+        let data = get_service.get_performance_details(user_id, variation_id, start_date, end_date).await?;
+        Ok(HttpResponse::Ok().json(data))
+    }
+
+    pub async fn one_rep_max_handler(
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        payload: ValidatedJson<OneRepMaxRequest>
+    ) -> ApiResult<HttpResponse>{
+        let user_id = user.id;
+        let payload_inner = payload.into_inner();
+        let start_date = match NaiveDate::parse_from_str(&payload_inner.start_date, "%Y-%m-%d"){
+            Ok(date) => date,
+            Err(err) => return Err(ApiError::BadRequest(format!("Invalid start_date format: {}", err)))
+        };
+        let end_date = match NaiveDate::parse_from_str(&payload_inner.end_date, "%Y-%m-%d"){
+            Ok(date) => date,
+            Err(err) => return Err(ApiError::BadRequest(format!("Invalid end_date format: {}", err)))
+        };
+        let formula = payload_inner.formula.unwrap_or_default();
+        let bucket = payload_inner.group_by.unwrap_or_default();
+
+        let data = get_service.get_one_rep_max_details(user_id, payload_inner.variation_id, start_date, end_date, formula, bucket).await?;
+        Ok(HttpResponse::Ok().json(data))
     }
 
     pub async fn musclegrp_summary_handler(
         get_service: web::Data<GetService>,
         user: AuthenticatedUser,
-        payload: web::Json<MuscleGroupSummaryRequest> 
-    ) -> impl Responder{
+        payload: ValidatedJson<MuscleGroupSummaryRequest> 
+    ) -> ApiResult<HttpResponse>{
         let user_id = user.id;
         let payload_inner = payload.into_inner();
         let muscle_group_ids = payload_inner.muscle_group_ids;
         let start_date = match NaiveDate::parse_from_str(&payload_inner.start_date, "%Y-%m-%d"){
             Ok(date) => date,
-            Err(err) => return HttpResponse::BadRequest().body(format!("Invalid start_date format: {}", err))
+            Err(err) => return Err(ApiError::BadRequest(format!("Invalid start_date format: {}", err)))
         };
         let end_date = match NaiveDate::parse_from_str(&payload_inner.end_date, "%Y-%m-%d"){
             Ok(date) => date,
-            Err(err) => return HttpResponse::BadRequest().body(format!("Invalid end_date format: {}", err))
+            Err(err) => return Err(ApiError::BadRequest(format!("Invalid end_date format: {}", err)))
         };
 
-        match get_service.get_numberof_sets_per_musclegroup(user_id, start_date, end_date, muscle_group_ids).await{
-            Ok(summary) => return HttpResponse::Ok().json(summary),
-            Err(err) => return HttpResponse::InternalServerError().body(err.to_string())
-        }
+        let summary = get_service.get_numberof_sets_per_musclegroup(user_id, start_date, end_date, muscle_group_ids).await?;
+        Ok(HttpResponse::Ok().json(summary))
+    }
+
+    pub async fn cardio_summary_handler(
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        query: ValidatedQuery<CardioSummaryQuery>
+    ) -> ApiResult<HttpResponse>{
+        let query = query.into_inner();
+        let bucket = query.group_by.unwrap_or_default();
+        let summary = get_service.get_cardio_summary(user.id, query.start_date, query.end_date, bucket, query.cardio_exercise_id).await?;
+        Ok(HttpResponse::Ok().json(summary))
     }
 
 
@@ -0,0 +1,31 @@
+use actix_web::{http::header::{ContentDisposition, DispositionParam, DispositionType}, web, HttpResponse};
+use chrono::Utc;
+use crate::{api::middleware::AuthenticatedUser, error::ApiResult, services::export_service::ExportService};
+
+#[derive(Clone, Default)]
+pub struct Export{}
+
+impl Export{
+    pub fn new() -> Self{
+        Export {}
+    }
+
+    /// Streams a zip of the user's sessions, sets, cardio logs and custom catalogue entries as CSV and JSON.
+    pub async fn export_handler(
+        export_service: web::Data<ExportService>,
+        user: AuthenticatedUser,
+    ) -> ApiResult<HttpResponse> {
+        Ok(HttpResponse::Ok()
+            .content_type("application/zip")
+            .insert_header(Self::attachment())
+            .streaming(export_service.export(user.id)))
+    }
+
+    /// Download header for an export archive, named after today's date.
+    pub fn attachment() -> ContentDisposition{
+        ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!("fittrack-export-{}.zip", Utc::now().date_naive()))],
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use crate::{api::{middleware::AuthenticatedUser, validation::{Validate, ValidatedQuery, Validator}}, error::{ApiError, ApiResult},
+            services::{csv_import::{ImportDefaults, ImportSource}, get_service::GetService, import_service::ImportService, units::{DistanceUnit, WeightUnit}}};
+
+/// Options for a CSV import. Without `source` the app is detected from the header row, and the
+/// units default to the user's own for files that don't state them.
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery{
+    pub source: Option<ImportSource>,
+    pub dry_run: Option<bool>,
+    pub unit: Option<WeightUnit>,
+    pub distance_unit: Option<DistanceUnit>,
+}
+
+// Every field is an enum or flag, so deserializing is the whole check
+impl Validate for ImportQuery{
+    fn validate(&self, _v: &mut Validator){}
+}
+
+#[derive(Clone, Default)]
+pub struct Imports{}
+
+impl Imports{
+    pub fn new() -> Self{
+        Imports {}
+    }
+
+    /// Imports a Strong, Hevy or FitNotes CSV export. With `dry_run` the report of what would be
+    /// written is returned and nothing is saved.
+    pub async fn import_handler(
+        import_service: web::Data<ImportService>,
+        get_service: web::Data<GetService>,
+        query: ValidatedQuery<ImportQuery>,
+        body: web::Bytes,
+        user: AuthenticatedUser,
+    ) -> ApiResult<HttpResponse> {
+        if body.is_empty() {
+            return Err(ApiError::BadRequest("Request body must contain the CSV export".to_string()));
+        }
+        let query = query.into_inner();
+        let units = get_service.get_units(user.id).await?;
+        let defaults = ImportDefaults {
+            weight: query.unit.unwrap_or(units.weight),
+            distance: query.distance_unit.unwrap_or(units.distance),
+        };
+        let dry_run = query.dry_run.unwrap_or(false);
+        let report = import_service.import(user.id, query.source, defaults, dry_run, &body).await?;
+        if dry_run {
+            Ok(HttpResponse::Ok().json(report))
+        } else {
+            Ok(HttpResponse::Created().json(report))
+        }
+    }
+}
@@ -1,7 +1,18 @@
-use actix_web::{cookie::Cookie, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{cookie::Cookie, web, HttpRequest, HttpResponse};
+use futures_util::TryStreamExt;
 use serde::{Deserialize, Serialize};
 use ::time::Duration as TimeDuration;
-use crate::{api::middleware::AuthenticatedUser,services::{auth_service::{AuthService}, get_service::GetService, jwt_service::JwtService}};
+use crate::{api::{export::Export, middleware::AuthenticatedUser, validation::{Validate, ValidatedJson, Validator, MAX_BODY_WEIGHT, MAX_NAME_LEN, MIN_PASSWORD_LEN}}, db::model::Sex, error::{ApiError, ApiResult}, services::{auth_service::{AuthService}, export_service::ExportService, get_service::GetService, units::{LengthUnit, WeightUnit}, jwt_service::JwtService, session_service::{SessionService, SessionTokens}}};
+
+const ACCESS_COOKIE: &str = "token";
+const REFRESH_COOKIE: &str = "refresh_token";
+// Refresh cookie is only sent to /api/refresh; logout finds the session through the access token
+const REFRESH_COOKIE_PATH: &str = "/api/refresh";
+pub const DELETION_ID_HEADER: &str = "X-Deletion-Id";
+const MIN_USERNAME_LEN: usize = 3;
+const MAX_USERNAME_LEN: usize = 50;
+const MAX_PASSWORD_LEN: usize = 128;
+const MAX_HEIGHT: f64 = 300.0;
 
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
@@ -20,6 +31,11 @@ pub struct RegisterRequest {
     pub weight: f64,
     pub height: f64,
     pub dob: String,
+    /// Preferred units, also the units `weight` and `height` are given in. Default to kg and cm.
+    pub weight_unit: Option<WeightUnit>,
+    pub length_unit: Option<LengthUnit>,
+    /// Used for BMR estimates, optional
+    pub sex: Option<Sex>,
 }
 
 #[derive(Debug,Deserialize)]
@@ -30,7 +46,12 @@ pub struct LoginRequest {
 
 #[derive(Deserialize)]
 pub struct ForgotPasswordRequest{
-    pub username: String,
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest{
+    pub token: String,
     pub password: String,
     pub confirmpassword: String
 }
@@ -43,6 +64,101 @@ pub struct UpdateUserInfo{
     pub weight: Option<f64>,
     pub height: Option<f64>,
     pub dob: Option<String>,
+    /// Changes the stored preference, `weight` and `height` in the same request are read in it.
+    pub weight_unit: Option<WeightUnit>,
+    pub length_unit: Option<LengthUnit>,
+    pub sex: Option<Sex>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountRequest{
+    pub password: String,
+    /// Return a final export of the account's data as the response, built in full before deletion
+    pub export: Option<bool>,
+}
+
+fn validate_password(v: &mut Validator, password: &str, confirmpassword: &str){
+    v.check(password.chars().count() >= MIN_PASSWORD_LEN, "password", &format!("must be at least {} characters", MIN_PASSWORD_LEN))
+     .check(password.chars().count() <= MAX_PASSWORD_LEN, "password", &format!("must be at most {} characters", MAX_PASSWORD_LEN))
+     .check(password == confirmpassword, "confirmpassword", "must match password");
+}
+
+fn validate_dob(v: &mut Validator, dob: &str){
+    if let Some(date) = v.date("dob", dob) {
+        v.check(date < chrono::Utc::now().date_naive(), "dob", "must be in the past");
+    }
+}
+
+/// Limits are metric, so values are converted with the unit they were sent in before checking.
+fn validate_body(v: &mut Validator, weight: Option<f64>, height: Option<f64>, weight_unit: Option<WeightUnit>, length_unit: Option<LengthUnit>){
+    let weight_unit = weight_unit.unwrap_or_default();
+    let length_unit = length_unit.unwrap_or_default();
+    if let Some(weight) = weight {
+        let kg = weight_unit.to_kg(weight);
+        v.check(kg > 0.0 && kg <= MAX_BODY_WEIGHT, "weight", &format!("must be between 0 and {} {}", weight_unit.kg_to(MAX_BODY_WEIGHT), weight_unit.as_str()));
+    }
+    if let Some(height) = height {
+        let cm = length_unit.to_cm(height);
+        v.check(cm > 0.0 && cm <= MAX_HEIGHT, "height", &format!("must be between 0 and {} {}", length_unit.cm_to(MAX_HEIGHT), length_unit.as_str()));
+    }
+}
+
+impl Validate for RegisterRequest{
+    fn validate(&self, v: &mut Validator){
+        let username_ok = self.username.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        v.length("fullname", &self.fullname, 1, MAX_NAME_LEN)
+         .length("username", &self.username, MIN_USERNAME_LEN, MAX_USERNAME_LEN)
+         .check(username_ok, "username", "may only contain letters, digits, '_', '-' and '.'")
+         .email("email", &self.email);
+        validate_body(v, Some(self.weight), Some(self.height), self.weight_unit, self.length_unit);
+        validate_password(v, &self.password, &self.confirmpassword);
+        validate_dob(v, &self.dob);
+    }
+}
+
+impl Validate for LoginRequest{
+    fn validate(&self, v: &mut Validator){
+        v.length("username", &self.username, 1, MAX_USERNAME_LEN)
+         .check(!self.password.is_empty(), "password", "must not be blank");
+    }
+}
+
+impl Validate for ForgotPasswordRequest{
+    fn validate(&self, v: &mut Validator){
+        v.email("email", &self.email);
+    }
+}
+
+impl Validate for ResetPasswordRequest{
+    fn validate(&self, v: &mut Validator){
+        v.check(!self.token.trim().is_empty(), "token", "must not be blank");
+        validate_password(v, &self.password, &self.confirmpassword);
+    }
+}
+
+impl Validate for DeleteAccountRequest{
+    fn validate(&self, v: &mut Validator){
+        v.check(!self.password.is_empty(), "password", "must not be blank");
+    }
+}
+
+impl Validate for UpdateUserInfo{
+    fn validate(&self, v: &mut Validator){
+        if let Some(fullname) = &self.fullname {
+            v.length("fullname", fullname, 1, MAX_NAME_LEN);
+        }
+        if let Some(email) = &self.email {
+            v.email("email", email);
+        }
+        if let Some(password) = &self.password {
+            v.check(password.chars().count() >= MIN_PASSWORD_LEN, "password", &format!("must be at least {} characters", MIN_PASSWORD_LEN))
+             .check(password.chars().count() <= MAX_PASSWORD_LEN, "password", &format!("must be at most {} characters", MAX_PASSWORD_LEN));
+        }
+        validate_body(v, self.weight, self.height, self.weight_unit, self.length_unit);
+        if let Some(dob) = &self.dob {
+            validate_dob(v, dob);
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -54,96 +170,207 @@ impl Login{
 
     pub async fn register_handler(
         auth_service: web::Data<AuthService>,
-        payload: web::Json<RegisterRequest>,
-    ) -> impl Responder {
-        let result = auth_service.register(payload.into_inner()).await;
-        HttpResponse::Ok().json(result)
+        payload: ValidatedJson<RegisterRequest>,
+    ) -> ApiResult<HttpResponse> {
+        let result = auth_service.register(payload.into_inner()).await?;
+        Ok(HttpResponse::Ok().json(result))
     }
 
     pub async fn login_handler(
         auth_service: web::Data<AuthService>,
-        jwt_service: web::Data<JwtService>,
-        payload: web::Json<LoginRequest>,
-        ) -> impl Responder  {
-        let result = auth_service.login(payload.into_inner()).await;
-        if !result.success{
-            return HttpResponse::Unauthorized().json(serde_json::json!({ "success": false, "message": format!("{}",result.message)}));
+        session_service: web::Data<SessionService>,
+        payload: ValidatedJson<LoginRequest>,
+        ) -> ApiResult<HttpResponse> {
+        let result = auth_service.login(payload.into_inner()).await?;
+        let user_id = match result.user_id{
+            Some(id) => id,
+            None => return Err(ApiError::Internal("Authenticated user has no id".to_string()))
+        };
+        let tokens = session_service.start_session(user_id, &result.username).await?;
+
+        let (access_cookie, refresh_cookie) = Self::session_cookies(&session_service, tokens);
+        Ok(HttpResponse::Ok()
+                    .cookie(access_cookie)
+                    .cookie(refresh_cookie)
+                    .json(serde_json::json!({
+                        "success": true,
+                        "message": "Login successful"
+                    })))
+    }
+
+    pub async fn refresh_handler(
+        session_service: web::Data<SessionService>,
+        req: HttpRequest,
+    ) -> ApiResult<HttpResponse> {
+        let refresh_token = match req.cookie(REFRESH_COOKIE){
+            Some(cookie) => cookie.value().to_string(),
+            None => return Err(ApiError::Unauthorized("No refresh token cookie found".to_string()))
+        };
+
+        match session_service.refresh_session(&refresh_token).await?{
+            Some(tokens) => {
+                let (access_cookie, refresh_cookie) = Self::session_cookies(&session_service, tokens);
+                Ok(HttpResponse::Ok()
+                    .cookie(access_cookie)
+                    .cookie(refresh_cookie)
+                    .json(serde_json::json!({
+                        "success": true,
+                        "message": "Session refreshed"
+                    })))
+            }
+            // Built by hand rather than through ApiError so the stale cookies get cleared
+            None => Ok(HttpResponse::Unauthorized()
+                .cookie(Self::expired_cookie(ACCESS_COOKIE, "/"))
+                .cookie(Self::expired_cookie(REFRESH_COOKIE, REFRESH_COOKIE_PATH))
+                .json(serde_json::json!({
+                    "success": false,
+                    "error": "unauthorized",
+                    "message": "Invalid or expired refresh token"
+                }))),
         }
-        
-        let user_id = result.user_id.unwrap_or_default(); // Should handle error ideally, but success implied user_id present
-        let cookie = Cookie::build("token", jwt_service.generate_token(
-                &result.username, user_id).unwrap())
+    }
+
+    pub async fn logout_handler(
+        session_service: web::Data<SessionService>,
+        req: HttpRequest,
+    ) -> ApiResult<HttpResponse> {
+        if let Some(cookie) = req.cookie(REFRESH_COOKIE) {
+            session_service.end_session(cookie.value()).await?;
+        } else if let Some(cookie) = req.cookie(ACCESS_COOKIE) {
+            session_service.end_session_for_access_token(cookie.value()).await?;
+        }
+
+        Ok(HttpResponse::Ok()
+            .cookie(Self::expired_cookie(ACCESS_COOKIE, "/"))
+            .cookie(Self::expired_cookie(REFRESH_COOKIE, REFRESH_COOKIE_PATH))
+            .json(serde_json::json!({
+                "success": true,
+                "message": "Logged out"
+            })))
+    }
+
+    fn session_cookies(session_service: &SessionService, tokens: SessionTokens) -> (Cookie<'static>, Cookie<'static>){
+        let access_cookie = Cookie::build(ACCESS_COOKIE, tokens.access_token)
                 .path("/")
-                .max_age(TimeDuration::minutes(60))
+                .max_age(TimeDuration::minutes(session_service.access_token_minutes()))
+                .same_site(actix_web::cookie::SameSite::None)
+                .http_only(true)
+                .finish();
+        let refresh_cookie = Cookie::build(REFRESH_COOKIE, tokens.refresh_token)
+                .path(REFRESH_COOKIE_PATH)
+                .max_age(TimeDuration::days(session_service.refresh_token_days()))
                 .same_site(actix_web::cookie::SameSite::None)
                 .http_only(true)
                 .finish();
+        (access_cookie, refresh_cookie)
+    }
 
-        return HttpResponse::Ok()
-                    .cookie(cookie)
-                    .json(serde_json::json!({
-                        "success": true,
-                        "message": "Login successful"
-                    }));
+    fn expired_cookie(name: &'static str, path: &'static str) -> Cookie<'static>{
+        Cookie::build(name, "")
+                .path(path)
+                .max_age(TimeDuration::ZERO)
+                .same_site(actix_web::cookie::SameSite::None)
+                .http_only(true)
+                .finish()
     }
 
     pub async fn forgot_password_handler(
         auth_service: web::Data<AuthService>,
-        payload: web::Json<ForgotPasswordRequest>,
-    ) -> impl Responder {
-        let result = auth_service.forgot_password(payload.into_inner()).await;
-        HttpResponse::Ok().json(result)
+        payload: ValidatedJson<ForgotPasswordRequest>,
+    ) -> ApiResult<HttpResponse> {
+        let result = auth_service.forgot_password(payload.into_inner()).await?;
+        Ok(HttpResponse::Ok().json(result))
+    }
+
+    pub async fn reset_password_handler(
+        auth_service: web::Data<AuthService>,
+        payload: ValidatedJson<ResetPasswordRequest>,
+    ) -> ApiResult<HttpResponse> {
+        let result = auth_service.reset_password(payload.into_inner()).await?;
+        Ok(HttpResponse::Ok().json(result))
     }
 
     pub async fn verify_token_handler(
     jwt_service: web::Data<JwtService>,
+    session_service: web::Data<SessionService>,
     req: HttpRequest,
-    ) -> impl Responder {
+    ) -> ApiResult<HttpResponse> {
         // Try to extract cookie
-        if let Some(cookie) = req.cookie("token") {
-            let token = cookie.value();
+        let cookie = match req.cookie(ACCESS_COOKIE) {
+            Some(c) => c,
+            None => return Err(ApiError::Unauthorized("No token cookie found".to_string()))
+        };
 
-            match jwt_service.validate_token(token) {
-                Ok(claims) => {
-                    HttpResponse::Ok().json(serde_json::json!({
-                        "success": true,
-                        "message": "Token is valid",
-                        "username": claims.claims.sub,
-                        "user_id": claims.claims.id
-                    }))
-                }
-                Err(err) => {
-                    HttpResponse::Unauthorized().json(serde_json::json!({
-                        "success": false,
-                        "message": format!("Invalid or expired token: {}", err)
-                    }))
+        match jwt_service.validate_token(cookie.value()) {
+            Ok(claims) => {
+                if !session_service.user_exists(claims.claims.id).await? {
+                    return Err(ApiError::Unauthorized("Invalid or expired token: account no longer exists".to_string()));
                 }
+                Ok(HttpResponse::Ok().json(serde_json::json!({
+                    "success": true,
+                    "message": "Token is valid",
+                    "username": claims.claims.sub,
+                    "user_id": claims.claims.id
+                })))
             }
-        } else {
-            HttpResponse::Unauthorized().json(serde_json::json!({
-                "success": false,
-                "message": "No token cookie found"
-            }))
+            Err(err) => Err(ApiError::Unauthorized(format!("Invalid or expired token: {}", err)))
         }
     }
 
     pub async fn update_user_handler(auth_service: web::Data<AuthService>,
         user: AuthenticatedUser,
-        payload: web::Json<UpdateUserInfo>,
-    ) -> impl Responder {
+        payload: ValidatedJson<UpdateUserInfo>,
+    ) -> ApiResult<HttpResponse> {
         let id = user.id;
         let username = user.username;
         let update_info = payload.into_inner();
-        let result = auth_service.update_user_details(id, username, update_info).await;
-        HttpResponse::Ok().json(result)
+        let result = auth_service.update_user_details(id, username, update_info).await?;
+        Ok(HttpResponse::Ok().json(result))
+    }
+
+    /// Permanently deletes the account after re-checking the password. API tokens can't be used.
+    /// With `export` the whole archive is built before anything is erased and returned as the body,
+    /// so a failed export deletes nothing and a successful response always means the account is gone.
+    /// The deletion reference comes back in the JSON body, or in a header alongside the archive.
+    pub async fn delete_account_handler(auth_service: web::Data<AuthService>,
+        export_service: web::Data<ExportService>,
+        user: AuthenticatedUser,
+        payload: ValidatedJson<DeleteAccountRequest>,
+    ) -> ApiResult<HttpResponse> {
+        if user.api_token_id.is_some() {
+            return Err(ApiError::Forbidden("Accounts can only be deleted from a signed-in session".to_string()));
+        }
+        let request = payload.into_inner();
+        auth_service.confirm_password(user.id, user.username, request.password).await?;
+
+        let user_id = user.id;
+        if request.export.unwrap_or(false) {
+            let archive: Vec<web::Bytes> = export_service.export(user_id).try_collect().await?;
+            let deletion = auth_service.delete_account(user_id, true).await?;
+            return Ok(HttpResponse::Ok()
+                .content_type("application/zip")
+                .insert_header(Export::attachment())
+                .insert_header((DELETION_ID_HEADER, deletion.deletion_id))
+                .cookie(Self::expired_cookie(ACCESS_COOKIE, "/"))
+                .cookie(Self::expired_cookie(REFRESH_COOKIE, REFRESH_COOKIE_PATH))
+                .body(archive.concat()));
+        }
+
+        let deletion = auth_service.delete_account(user_id, false).await?;
+        Ok(HttpResponse::Ok()
+            .cookie(Self::expired_cookie(ACCESS_COOKIE, "/"))
+            .cookie(Self::expired_cookie(REFRESH_COOKIE, REFRESH_COOKIE_PATH))
+            .json(serde_json::json!({
+                "success": true,
+                "message": "Account deleted",
+                "deletion_id": deletion.deletion_id
+            })))
     }
 
     pub async fn user_info_handler(get_service: web::Data<GetService>,
         user: AuthenticatedUser
-    ) -> impl Responder{
-        match get_service.get_user_info(user.id).await{
-            Ok(user_info) => HttpResponse::Ok().json(user_info),
-            Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-        }
+    ) -> ApiResult<HttpResponse> {
+        let user_info = get_service.get_user_info(user.id).await?;
+        Ok(HttpResponse::Ok().json(user_info))
     }
 }
\ No newline at end of file
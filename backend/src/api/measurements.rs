@@ -0,0 +1,207 @@
+use chrono::{NaiveDate, Utc};
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use crate::{api::{dashboard::MAX_RANGE_DAYS, middleware::AuthenticatedUser, validation::{Validate, ValidatedJson, ValidatedQuery, Validator, MAX_BODY_WEIGHT, MAX_NOTES_LEN}},
+            error::ApiResult,
+            services::{get_service::{BodyMetric, GetService}, post_service::PostService, put_service::PutService, units::{LengthUnit, Units, WeightUnit}}};
+
+const MAX_GIRTH_CM: f64 = 250.0;
+const DEFAULT_TREND_WINDOW: i64 = 7;
+const MAX_TREND_WINDOW: i64 = 90;
+
+/// Readings shared by the create and update payloads, every one of them optional.
+#[derive(Debug, Default, Deserialize)]
+pub struct MeasurementValues{
+    pub weight: Option<f64>,
+    pub body_fat_percent: Option<f64>,
+    pub neck: Option<f64>,
+    pub chest: Option<f64>,
+    pub waist: Option<f64>,
+    pub hips: Option<f64>,
+    pub arms: Option<f64>,
+    pub thighs: Option<f64>,
+    /// Unit `weight` is given in, defaults to the user's preference.
+    pub weight_unit: Option<WeightUnit>,
+    /// Unit the girths are given in, defaults to the user's preference.
+    pub length_unit: Option<LengthUnit>,
+}
+
+impl MeasurementValues{
+    fn girths(&self) -> [(&'static str, Option<f64>); 6]{
+        [("neck", self.neck), ("chest", self.chest), ("waist", self.waist), ("hips", self.hips), ("arms", self.arms), ("thighs", self.thighs)]
+    }
+
+    fn is_empty(&self) -> bool{
+        self.weight.is_none() && self.body_fat_percent.is_none() && self.girths().iter().all(|(_, g)| g.is_none())
+    }
+
+    /// Converts weight to kg and girths to cm in place so services only see storage units.
+    pub fn to_metric(&mut self, units: &Units){
+        let (weight_unit, length_unit) = (self.weight_unit, self.length_unit);
+        self.weight = self.weight.map(|w| units.weight_in(w, weight_unit));
+        for girth in [&mut self.neck, &mut self.chest, &mut self.waist, &mut self.hips, &mut self.arms, &mut self.thighs]{
+            *girth = girth.map(|g| units.length_in(g, length_unit));
+        }
+    }
+}
+
+fn validate_values(v: &mut Validator, values: &MeasurementValues){
+    if let Some(weight) = values.weight {
+        let unit = values.weight_unit.unwrap_or_default();
+        let kg = unit.to_kg(weight);
+        v.check(kg > 0.0 && kg <= MAX_BODY_WEIGHT, "weight", &format!("must be between 0 and {} {}", unit.kg_to(MAX_BODY_WEIGHT), unit.as_str()));
+    }
+    if let Some(body_fat) = values.body_fat_percent {
+        v.range("body_fat_percent", body_fat, 0.0, 100.0);
+    }
+    let unit = values.length_unit.unwrap_or_default();
+    for (field, girth) in values.girths(){
+        if let Some(g) = girth {
+            let cm = unit.to_cm(g);
+            v.check(cm > 0.0 && cm <= MAX_GIRTH_CM, field, &format!("must be between 0 and {} {}", unit.cm_to(MAX_GIRTH_CM), unit.as_str()));
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MeasurementRequest{
+    /// Defaults to today
+    pub measured_on: Option<NaiveDate>,
+    pub notes: Option<String>,
+    #[serde(flatten)]
+    pub values: MeasurementValues,
+}
+
+/// Only the fields sent are changed.
+#[derive(Debug, Deserialize)]
+pub struct UpdateMeasurementRequest{
+    pub measured_on: Option<NaiveDate>,
+    pub notes: Option<String>,
+    #[serde(flatten)]
+    pub values: MeasurementValues,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MeasurementQuery{
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrendQuery{
+    pub metric: BodyMetric,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    /// Moving average window in days, defaults to 7
+    pub window: Option<i64>,
+}
+
+impl Validate for MeasurementRequest{
+    fn validate(&self, v: &mut Validator){
+        v.check(!self.values.is_empty(), "weight", "at least one measurement is required");
+        if let Some(date) = self.measured_on {
+            v.not_future("measured_on", date);
+        }
+        if let Some(notes) = &self.notes {
+            v.length("notes", notes, 0, MAX_NOTES_LEN);
+        }
+        validate_values(v, &self.values);
+    }
+}
+
+impl Validate for UpdateMeasurementRequest{
+    fn validate(&self, v: &mut Validator){
+        if let Some(date) = self.measured_on {
+            v.not_future("measured_on", date);
+        }
+        if let Some(notes) = &self.notes {
+            v.length("notes", notes, 0, MAX_NOTES_LEN);
+        }
+        validate_values(v, &self.values);
+    }
+}
+
+impl Validate for MeasurementQuery{
+    fn validate(&self, v: &mut Validator){
+        v.check(self.start_date <= self.end_date, "end_date", "must not be before start_date")
+         .check((self.end_date - self.start_date).num_days() <= MAX_RANGE_DAYS, "end_date", "range can't span more than 5 years");
+    }
+}
+
+impl Validate for TrendQuery{
+    fn validate(&self, v: &mut Validator){
+        v.check(self.start_date <= self.end_date, "end_date", "must not be before start_date")
+         .check((self.end_date - self.start_date).num_days() <= MAX_RANGE_DAYS, "end_date", "range can't span more than 5 years");
+        if let Some(window) = self.window {
+            v.range("window", window, 1, MAX_TREND_WINDOW);
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Measurements{}
+
+impl Measurements{
+    pub fn new() -> Self{
+        Measurements {}
+    }
+
+    pub async fn list_measurements_handler(
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        query: ValidatedQuery<MeasurementQuery>,
+    ) -> ApiResult<HttpResponse> {
+        let query = query.into_inner();
+        let measurements = get_service.get_measurements(user.id, query.start_date, query.end_date).await?;
+        Ok(HttpResponse::Ok().json(measurements))
+    }
+
+    pub async fn create_measurement_handler(
+        post_service: web::Data<PostService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        payload: ValidatedJson<MeasurementRequest>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let mut req = payload.into_inner();
+        req.values.to_metric(&units);
+        let measured_on = req.measured_on.unwrap_or_else(|| Utc::now().date_naive());
+        let measurement = post_service.add_measurement(user.id, measured_on, req).await?;
+        Ok(HttpResponse::Created().json(units.measurement_out(measurement)))
+    }
+
+    pub async fn update_measurement_handler(
+        put_service: web::Data<PutService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+        payload: ValidatedJson<UpdateMeasurementRequest>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let mut req = payload.into_inner();
+        req.values.to_metric(&units);
+        let measurement = put_service.update_measurement(user.id, path.into_inner(), req).await?;
+        Ok(HttpResponse::Ok().json(units.measurement_out(measurement)))
+    }
+
+    pub async fn delete_measurement_handler(
+        put_service: web::Data<PutService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+    ) -> ApiResult<HttpResponse> {
+        let resp = put_service.delete_measurement(user.id, path.into_inner()).await?;
+        Ok(HttpResponse::Ok().json(resp))
+    }
+
+    /// Daily values and a trailing moving average for one metric, in the user's units.
+    pub async fn measurement_trend_handler(
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        query: ValidatedQuery<TrendQuery>,
+    ) -> ApiResult<HttpResponse> {
+        let query = query.into_inner();
+        let window = query.window.unwrap_or(DEFAULT_TREND_WINDOW);
+        let trend = get_service.get_measurement_trend(user.id, query.metric, query.start_date, query.end_date, window).await?;
+        Ok(HttpResponse::Ok().json(trend))
+    }
+}
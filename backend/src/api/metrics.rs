@@ -0,0 +1,20 @@
+use actix_web::{web, HttpResponse};
+use crate::{api::middleware::AuthenticatedUser, error::ApiResult, services::metrics_service::MetricsService};
+
+#[derive(Clone, Default)]
+pub struct Metrics{}
+
+impl Metrics{
+    pub fn new() -> Self{
+        Metrics {}
+    }
+
+    /// Age, BMI, BMR and TDEE worked out from the profile, latest body fat reading and recent training.
+    pub async fn body_metrics_handler(
+        metrics_service: web::Data<MetricsService>,
+        user: AuthenticatedUser,
+    ) -> ApiResult<HttpResponse> {
+        let metrics = metrics_service.get_metrics(user.id).await?;
+        Ok(HttpResponse::Ok().json(metrics))
+    }
+}
@@ -1,33 +1,70 @@
-use std::future::{ready, Ready};
-use actix_web::{dev::Payload, web, FromRequest, HttpRequest, error::ErrorUnauthorized, Error};
-use crate::services::jwt_service::JwtService;
+use std::{future::Future, pin::Pin};
+use actix_web::{dev::Payload, http::{header, Method}, web, FromRequest, HttpRequest};
+use crate::{error::ApiError, services::{jwt_service::JwtService, session_service::{SessionService, TokenScope, API_TOKEN_PREFIX}}};
 
 #[derive(Debug)]
 pub struct AuthenticatedUser {
     pub id: i32,
     pub username: String,
+    pub scope: TokenScope,
+    /// Set when the request was authenticated with a personal API token rather than a session
+    pub api_token_id: Option<i32>,
 }
 
 impl FromRequest for AuthenticatedUser {
-    type Error = Error;
-    type Future = Ready<Result<Self, Self::Error>>;
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
 
     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
         let jwt_service = req.app_data::<web::Data<JwtService>>()
-            .expect("JwtService not found in app data");
+            .expect("JwtService not found in app data")
+            .clone();
+        let session_service = req.app_data::<web::Data<SessionService>>()
+            .expect("SessionService not found in app data")
+            .clone();
 
-        if let Some(cookie) = req.cookie("token") {
-             match jwt_service.validate_token(cookie.value()) {
-                Ok(token_data) => {
-                    return ready(Ok(AuthenticatedUser {
-                        id: token_data.claims.id,
-                        username: token_data.claims.sub,
-                    }));
+        let bearer = req.headers().get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string());
+        let cookie = req.cookie("token").map(|c| c.value().to_string());
+        let read_only_method = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+
+        Box::pin(async move {
+            let token = match bearer.or(cookie) {
+                Some(t) => t,
+                None => return Err(ApiError::Unauthorized("No auth token found".to_string())),
+            };
+
+            if token.starts_with(API_TOKEN_PREFIX) {
+                let identity = match session_service.authenticate_api_token(&token).await? {
+                    Some(i) => i,
+                    None => return Err(ApiError::Unauthorized("Invalid token".to_string())),
+                };
+                if identity.scope == TokenScope::ReadOnly && !read_only_method {
+                    return Err(ApiError::Forbidden("Token is read-only".to_string()));
                 }
-                Err(_) => return ready(Err(ErrorUnauthorized("Invalid token"))),
+                return Ok(AuthenticatedUser {
+                    id: identity.user_id,
+                    username: identity.username,
+                    scope: identity.scope,
+                    api_token_id: Some(identity.token_id),
+                });
             }
-        }
 
-        ready(Err(ErrorUnauthorized("No auth token found")))
+            let token_data = match jwt_service.validate_token(&token) {
+                Ok(t) => t,
+                Err(_) => return Err(ApiError::Unauthorized("Invalid token".to_string())),
+            };
+            if !session_service.user_exists(token_data.claims.id).await? {
+                return Err(ApiError::Unauthorized("Invalid token".to_string()));
+            }
+            Ok(AuthenticatedUser {
+                id: token_data.claims.id,
+                username: token_data.claims.sub,
+                scope: TokenScope::ReadWrite,
+                api_token_id: None,
+            })
+        })
     }
 }
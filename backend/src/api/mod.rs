@@ -2,31 +2,69 @@ pub mod login;
 pub mod workouts;
 pub mod dashboard;
 pub mod middleware;
+pub mod records;
+pub mod routines;
+pub mod programs;
+pub mod measurements;
+pub mod metrics;
+pub mod export;
+pub mod imports;
+pub mod search;
+pub mod tokens;
+pub mod validation;
 use std::sync::Arc;
 use log::error;
 use actix_web::web;
-use crate::{api::{login::Login, workouts::Workouts}, services::{auth_service::AuthService, get_service::GetService, jwt_service::JwtService, post_service::PostService, put_service::PutService}};
+use crate::{api::{export::Export, imports::Imports, login::Login, measurements::Measurements, metrics::Metrics, programs::Programs, records::Records, routines::Routines, search::Search, tokens::Tokens, workouts::Workouts}, services::{auth_service::AuthService, export_service::ExportService, get_service::GetService, import_service::ImportService, jwt_service::JwtService, metrics_service::MetricsService, post_service::PostService, program_service::ProgramService, put_service::PutService, records_service::RecordsService, routine_service::RoutineService, search_service::SearchService, session_service::SessionService}};
+
+/// The services handlers get through `web::Data`, built once in `main`.
+#[derive(Clone)]
+pub struct ApiServices{
+    pub auth_service: Arc<AuthService>,
+    pub jwt_service: Arc<JwtService>,
+    pub post_service: Arc<PostService>,
+    pub get_service: Arc<GetService>,
+    pub put_service: Arc<PutService>,
+    pub records_service: Arc<RecordsService>,
+    pub session_service: Arc<SessionService>,
+    pub routine_service: Arc<RoutineService>,
+    pub program_service: Arc<ProgramService>,
+    pub metrics_service: Arc<MetricsService>,
+    pub export_service: Arc<ExportService>,
+    pub import_service: Arc<ImportService>,
+    pub search_service: Arc<SearchService>
+}
 
 #[derive(Clone)]
 pub struct API{
-    auth_service: Arc<AuthService>,
-    jwt_service: Arc<JwtService>,
-    post_service: Arc<PostService>,
-    get_service: Arc<GetService>,
-    put_service: Arc<PutService>,
+    services: ApiServices,
     login_api : Option<Login>,
-    workouts_api: Option<Workouts>
+    workouts_api: Option<Workouts>,
+    records_api: Option<Records>,
+    tokens_api: Option<Tokens>,
+    routines_api: Option<Routines>,
+    programs_api: Option<Programs>,
+    measurements_api: Option<Measurements>,
+    metrics_api: Option<Metrics>,
+    export_api: Option<Export>,
+    imports_api: Option<Imports>,
+    search_api: Option<Search>
 }
 impl API{
-    pub fn new(auth_service: Arc<AuthService>, jwt_service: Arc<JwtService>, post_service: Arc<PostService>, get_service: Arc<GetService>,put_service:Arc<PutService>) -> Self{
+    pub fn new(services: ApiServices) -> Self{
         API{
-            auth_service,
-            jwt_service,
-            post_service,
-            get_service,
-            put_service,
+            services,
             login_api: None,
-            workouts_api: None
+            workouts_api: None,
+            records_api: None,
+            tokens_api: None,
+            routines_api: None,
+            programs_api: None,
+            measurements_api: None,
+            metrics_api: None,
+            export_api: None,
+            imports_api: None,
+            search_api: None
         }
     }
 
@@ -39,38 +77,112 @@ impl API{
 
         let workouts_api = Workouts::new();
         self.workouts_api = Some(workouts_api);
+
+        let records_api = Records::new();
+        self.records_api = Some(records_api);
+
+        let tokens_api = Tokens::new();
+        self.tokens_api = Some(tokens_api);
+
+        let routines_api = Routines::new();
+        self.routines_api = Some(routines_api);
+
+        let programs_api = Programs::new();
+        self.programs_api = Some(programs_api);
+
+        let measurements_api = Measurements::new();
+        self.measurements_api = Some(measurements_api);
+
+        let metrics_api = Metrics::new();
+        self.metrics_api = Some(metrics_api);
+
+        let export_api = Export::new();
+        self.export_api = Some(export_api);
+
+        let imports_api = Imports::new();
+        self.imports_api = Some(imports_api);
+
+        let search_api = Search::new();
+        self.search_api = Some(search_api);
     }
 
     pub fn configure(&self, cfg: &mut web::ServiceConfig){
-        cfg.app_data(web::Data::from(self.auth_service.clone()))
-           .app_data(web::Data::from(self.jwt_service.clone()))
-           .app_data(web::Data::from(self.post_service.clone()))
-           .app_data(web::Data::from(self.get_service.clone()))
-           .app_data(web::Data::from(self.put_service.clone()));
+        cfg.app_data(web::Data::from(self.services.auth_service.clone()))
+           .app_data(web::Data::from(self.services.jwt_service.clone()))
+           .app_data(web::Data::from(self.services.post_service.clone()))
+           .app_data(web::Data::from(self.services.get_service.clone()))
+           .app_data(web::Data::from(self.services.put_service.clone()))
+           .app_data(web::Data::from(self.services.records_service.clone()))
+           .app_data(web::Data::from(self.services.session_service.clone()))
+           .app_data(web::Data::from(self.services.routine_service.clone()))
+           .app_data(web::Data::from(self.services.program_service.clone()))
+           .app_data(web::Data::from(self.services.metrics_service.clone()))
+           .app_data(web::Data::from(self.services.export_service.clone()))
+           .app_data(web::Data::from(self.services.import_service.clone()))
+           .app_data(web::Data::from(self.services.search_service.clone()));
 
         // configure routes
         cfg.service(
             web::scope("/api")
                 .route("/login", web::post().to(crate::api::login::Login::login_handler))
+                .route("/refresh", web::post().to(crate::api::login::Login::refresh_handler))
+                .route("/logout", web::post().to(crate::api::login::Login::logout_handler))
                 .route("/register", web::post().to(crate::api::login::Login::register_handler))
                 .route("/forgot-password", web::post().to(crate::api::login::Login::forgot_password_handler))
+                .route("/reset-password", web::post().to(crate::api::login::Login::reset_password_handler))
                 .route("/verify-token", web::get().to(crate::api::login::Login::verify_token_handler))
+                .route("/tokens", web::get().to(crate::api::tokens::Tokens::list_tokens_handler))
+                .route("/tokens", web::post().to(crate::api::tokens::Tokens::create_token_handler))
+                .route("/tokens/{id}", web::delete().to(crate::api::tokens::Tokens::revoke_token_handler))
                 .route("/workouts/addsession", web::post().to(crate::api::workouts::Workouts::workout_session_handler))
+                .route("/workouts/sessions/full", web::post().to(crate::api::workouts::Workouts::full_session_handler))
                 .route("/workouts/session/{id}", web::put().to(crate::api::workouts::Workouts::update_session_handler))
                 .route("/workouts/session/{id}", web::delete().to(crate::api::workouts::Workouts::delete_session_handler))
                 .route("/workouts/addset", web::post().to(crate::api::workouts::Workouts::workout_set_handler))
                 .route("/workouts/set/{id}", web::put().to(crate::api::workouts::Workouts::update_set_handler))
                 .route("/workouts/set/{id}", web::delete().to(crate::api::workouts::Workouts::delete_set_handler))
                 .route("/workouts/addcardio", web::post().to(crate::api::workouts::Workouts::cardio_log_handler))
+                .service(web::resource("/workouts/cardio/import")
+                    .app_data(web::PayloadConfig::new(crate::api::workouts::MAX_IMPORT_BYTES))
+                    .route(web::post().to(crate::api::workouts::Workouts::import_cardio_handler)))
+                .service(web::resource("/workouts/import")
+                    .app_data(web::PayloadConfig::new(crate::api::workouts::MAX_IMPORT_BYTES))
+                    .route(web::post().to(crate::api::imports::Imports::import_handler)))
+                .route("/workouts/cardio/{id}/track", web::get().to(crate::api::workouts::Workouts::track_points_handler))
                 .route("/workouts/cardio/{id}", web::put().to(crate::api::workouts::Workouts::update_cardio_handler))
                 .route("/workouts/cardio/{id}", web::delete().to(crate::api::workouts::Workouts::delete_cardio_handler))
                 .route("/workouts/history", web::get().to(crate::api::workouts::Workouts::history_handler))
                 .route("/workouts/session/{id}", web::get().to(crate::api::workouts::Workouts::session_details_handler))
                 .route("/updateuser", web::put().to(crate::api::login::Login::update_user_handler))
+                .route("/account", web::delete().to(crate::api::login::Login::delete_account_handler))
                 .route("/userinfo", web::get().to(crate::api::login::Login::user_info_handler))
+                .route("/metrics", web::get().to(crate::api::metrics::Metrics::body_metrics_handler))
+                .route("/search", web::get().to(crate::api::search::Search::search_handler))
+                .route("/export", web::get().to(crate::api::export::Export::export_handler))
                 .route("/monthlylevels", web::post().to(crate::api::dashboard::Dashboard::monthly_workout_levels_handler))
                 .route("/performancemetrics", web::post().to(crate::api::dashboard::Dashboard::performance_data_handler))
+                .route("/onerepmax", web::post().to(crate::api::dashboard::Dashboard::one_rep_max_handler))
+                .route("/records", web::get().to(crate::api::records::Records::records_handler))
                 .route("/mslegrpsumm", web::post().to(crate::api::dashboard::Dashboard::musclegrp_summary_handler))
+                .route("/cardio/summary", web::get().to(crate::api::dashboard::Dashboard::cardio_summary_handler))
+                .route("/routines", web::get().to(crate::api::routines::Routines::list_routines_handler))
+                .route("/routines", web::post().to(crate::api::routines::Routines::create_routine_handler))
+                .route("/routines/{id}", web::get().to(crate::api::routines::Routines::get_routine_handler))
+                .route("/routines/{id}", web::put().to(crate::api::routines::Routines::update_routine_handler))
+                .route("/routines/{id}", web::delete().to(crate::api::routines::Routines::delete_routine_handler))
+                .route("/routines/{id}/start", web::post().to(crate::api::routines::Routines::start_routine_handler))
+                .route("/programs", web::get().to(crate::api::programs::Programs::list_programs_handler))
+                .route("/programs", web::post().to(crate::api::programs::Programs::create_program_handler))
+                .route("/programs/{id}", web::get().to(crate::api::programs::Programs::get_program_handler))
+                .route("/programs/{id}", web::put().to(crate::api::programs::Programs::update_program_handler))
+                .route("/programs/{id}", web::delete().to(crate::api::programs::Programs::delete_program_handler))
+                .route("/programs/{id}/next", web::get().to(crate::api::programs::Programs::next_session_handler))
+                .route("/programs/{id}/next/start", web::post().to(crate::api::programs::Programs::start_next_session_handler))
+                .route("/measurements", web::get().to(crate::api::measurements::Measurements::list_measurements_handler))
+                .route("/measurements", web::post().to(crate::api::measurements::Measurements::create_measurement_handler))
+                .route("/measurements/trend", web::get().to(crate::api::measurements::Measurements::measurement_trend_handler))
+                .route("/measurements/{id}", web::put().to(crate::api::measurements::Measurements::update_measurement_handler))
+                .route("/measurements/{id}", web::delete().to(crate::api::measurements::Measurements::delete_measurement_handler))
                 .route("/workouts/muscle_groups", web::get().to(crate::api::workouts::Workouts::get_muscle_groups_handler))
                 .route("/workouts/variations", web::get().to(crate::api::workouts::Workouts::get_variations_handler))
                 .route("/workouts/cardio_exercises", web::get().to(crate::api::workouts::Workouts::get_cardio_exercises_handler))
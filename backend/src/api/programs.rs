@@ -0,0 +1,208 @@
+use std::collections::HashSet;
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use crate::{api::{middleware::AuthenticatedUser, routines::StartRoutineQuery, validation::{Validate, ValidatedJson, ValidatedQuery, Validator, MAX_NAME_LEN, MAX_NOTES_LEN},
+                  workouts::{validate_weight, CardioLogDetails, SessionDetailsResponse}},
+            db::{model::{Program, ProgramDay, ProgramWeek, ProgressionRule, ProgressionType}, programs::ProgramPlan},
+            error::ApiResult,
+            services::{get_service::GetService, program_service::ProgramService, units::{Units, WeightUnit}}};
+
+const MAX_PROGRAM_WEEKS: usize = 52;
+const MAX_DAYS_PER_WEEK: usize = 7;
+const MAX_PROGRESSION_RULES: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct ProgramRequest {
+    pub name: String,
+    pub notes: Option<String>,
+    /// Share of the prescribed weight used in deload weeks, defaults to 60
+    pub deload_percent: Option<f64>,
+    /// Unit every rule `increment` is given in, defaults to the user's preference.
+    pub unit: Option<WeightUnit>,
+    /// Stored in the order given, week 1 first
+    pub weeks: Vec<ProgramWeekRequest>,
+    #[serde(default)]
+    pub rules: Vec<ProgressionRuleRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProgramWeekRequest {
+    pub deload: Option<bool>,
+    /// One routine per training day, in order
+    pub routine_ids: Vec<i32>,
+}
+
+/// A rule without `variation_id` is the default for every strength exercise in the program.
+#[derive(Debug, Deserialize)]
+pub struct ProgressionRuleRequest {
+    pub variation_id: Option<i32>,
+    pub rule: ProgressionType,
+    /// Weight added on progression, defaults to 2.5 kg
+    pub increment: Option<f64>,
+    /// Share of the best estimated 1RM, required by `e1rm` rules
+    pub percent: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProgramResponse {
+    #[serde(flatten)]
+    pub program: Program,
+    pub weeks: Vec<ProgramWeekResponse>,
+    pub rules: Vec<ProgressionRule>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProgramWeekResponse {
+    #[serde(flatten)]
+    pub week: ProgramWeek,
+    pub days: Vec<ProgramDay>,
+}
+
+impl Validate for ProgramRequest{
+    fn validate(&self, v: &mut Validator){
+        v.length("name", self.name.trim(), 1, MAX_NAME_LEN)
+         .check(!self.weeks.is_empty(), "weeks", "program must contain at least one week")
+         .range("weeks", self.weeks.len(), 0, MAX_PROGRAM_WEEKS)
+         .range("rules", self.rules.len(), 0, MAX_PROGRESSION_RULES);
+        if let Some(notes) = &self.notes {
+            v.length("notes", notes, 0, MAX_NOTES_LEN);
+        }
+        if let Some(percent) = self.deload_percent {
+            v.range("deload_percent", percent, 1.0, 100.0);
+        }
+        for (i, week) in self.weeks.iter().enumerate(){
+            v.check(!week.routine_ids.is_empty(), &format!("weeks[{}].routine_ids", i), "week must contain at least one day")
+             .range(&format!("weeks[{}].routine_ids", i), week.routine_ids.len(), 0, MAX_DAYS_PER_WEEK)
+             .check(week.routine_ids.iter().all(|id| *id > 0), &format!("weeks[{}].routine_ids", i), "must reference routines");
+        }
+        let mut seen = HashSet::new();
+        for (i, rule) in self.rules.iter().enumerate(){
+            let field = |name: &str| format!("rules[{}].{}", i, name);
+            v.check(seen.insert(rule.variation_id), &field("variation_id"), "only one rule per variation, and one default rule");
+            if let Some(increment) = rule.increment {
+                validate_weight(v, &field("increment"), increment, self.unit);
+            }
+            match rule.percent {
+                Some(percent) => { v.range(&field("percent"), percent, 1.0, 100.0); }
+                None => { v.check(rule.rule != ProgressionType::E1rm, &field("percent"), "is required for e1rm rules"); }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Programs{}
+
+impl Programs{
+    pub fn new() -> Self{
+        Programs {}
+    }
+
+    fn program_out(units: &Units, plan: ProgramPlan) -> ProgramResponse{
+        ProgramResponse {
+            program: plan.program,
+            weeks: plan.weeks.into_iter().map(|(week, days)| ProgramWeekResponse { week, days }).collect(),
+            rules: plan.rules.into_iter()
+                .map(|mut r| {
+                    r.increment = r.increment.map(|w| units.weight_out(w));
+                    r
+                })
+                .collect(),
+        }
+    }
+
+    fn increments_in(units: &Units, req: &mut ProgramRequest){
+        let unit = req.unit;
+        for rule in req.rules.iter_mut(){
+            rule.increment = rule.increment.map(|w| units.weight_in(w, unit));
+        }
+    }
+
+    pub async fn list_programs_handler(
+        program_service: web::Data<ProgramService>,
+        user: AuthenticatedUser,
+    ) -> ApiResult<HttpResponse> {
+        let programs = program_service.get_programs(user.id).await?;
+        Ok(HttpResponse::Ok().json(programs))
+    }
+
+    pub async fn get_program_handler(
+        program_service: web::Data<ProgramService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let plan = program_service.get_program(user.id, path.into_inner()).await?;
+        Ok(HttpResponse::Ok().json(Self::program_out(&units, plan)))
+    }
+
+    pub async fn create_program_handler(
+        program_service: web::Data<ProgramService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        payload: ValidatedJson<ProgramRequest>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let mut req = payload.into_inner();
+        Self::increments_in(&units, &mut req);
+        let plan = program_service.create_program(user.id, req).await?;
+        Ok(HttpResponse::Created().json(Self::program_out(&units, plan)))
+    }
+
+    pub async fn update_program_handler(
+        program_service: web::Data<ProgramService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+        payload: ValidatedJson<ProgramRequest>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let mut req = payload.into_inner();
+        Self::increments_in(&units, &mut req);
+        let plan = program_service.update_program(user.id, path.into_inner(), req).await?;
+        Ok(HttpResponse::Ok().json(Self::program_out(&units, plan)))
+    }
+
+    pub async fn delete_program_handler(
+        program_service: web::Data<ProgramService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+    ) -> ApiResult<HttpResponse> {
+        program_service.delete_program(user.id, path.into_inner()).await?;
+        Ok(HttpResponse::NoContent().finish())
+    }
+
+    /// Prescribed weights and reps for the program's next day, worked out from the completed sets.
+    pub async fn next_session_handler(
+        program_service: web::Data<ProgramService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let mut next = program_service.get_next_session(user.id, path.into_inner()).await?;
+        for exercise in next.exercises.iter_mut(){
+            exercise.weight = exercise.weight.map(|w| units.weight_out(w));
+        }
+        Ok(HttpResponse::Ok().json(next))
+    }
+
+    pub async fn start_next_session_handler(
+        program_service: web::Data<ProgramService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+        query: ValidatedQuery<StartRoutineQuery>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let (session, sets, cardio) = program_service.start_next_session(user.id, path.into_inner(), query.into_inner()).await?;
+        let resp = SessionDetailsResponse {
+            session,
+            sets: sets.into_iter().map(|s| units.set_out(s)).collect(),
+            cardio_logs: cardio.into_iter().map(|c| CardioLogDetails::new(c, &units)).collect(),
+            estimated_calories: None,
+        };
+        Ok(HttpResponse::Created().json(resp))
+    }
+}
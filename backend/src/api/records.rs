@@ -0,0 +1,28 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use crate::{api::{middleware::AuthenticatedUser, workouts::Workouts}, error::ApiResult, services::{get_service::GetService, records_service::RecordsService}};
+
+#[derive(Debug, Deserialize)]
+pub struct RecordsQuery {
+    pub variation_id: Option<i32>,
+}
+
+#[derive(Clone, Default)]
+pub struct Records{}
+
+impl Records{
+    pub fn new() -> Self{
+        Records {}
+    }
+
+    pub async fn records_handler(
+        records_service: web::Data<RecordsService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        query: web::Query<RecordsQuery>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let records = records_service.get_records(user.id, query.variation_id).await?;
+        Ok(HttpResponse::Ok().json(Workouts::records_out(&units, records)))
+    }
+}
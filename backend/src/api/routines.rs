@@ -0,0 +1,200 @@
+use chrono::NaiveDateTime;
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use crate::{api::{middleware::AuthenticatedUser, validation::{Validate, ValidatedJson, ValidatedQuery, Validator, MAX_MINUTES_PER_DAY, MAX_NAME_LEN, MAX_NOTES_LEN, MAX_REPS},
+                  workouts::{validate_weight, CardioLogDetails, SessionDetailsResponse, MAX_REST_SECONDS}},
+            db::model::{Routine, RoutineExercise},
+            error::ApiResult,
+            services::{get_service::GetService, routine_service::RoutineService, units::{Units, WeightUnit}}};
+
+const MAX_ROUTINE_EXERCISES: usize = 100;
+const MAX_TARGET_SETS: i32 = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct RoutineRequest {
+    pub name: String,
+    pub notes: Option<String>,
+    /// Unit every `target_weight` is given in, defaults to the user's preference.
+    pub unit: Option<WeightUnit>,
+    /// Stored in the order given
+    pub exercises: Vec<RoutineExerciseRequest>,
+}
+
+/// One entry of a routine, either a strength variation or a cardio exercise.
+#[derive(Debug, Deserialize)]
+pub struct RoutineExerciseRequest {
+    pub variation_id: Option<i32>,
+    pub cardio_exercise_id: Option<i32>,
+    /// Defaults to 1
+    pub target_sets: Option<i32>,
+    pub reps_min: Option<i32>,
+    pub reps_max: Option<i32>,
+    pub target_weight: Option<f64>,
+    pub rest_seconds: Option<i32>,
+    pub target_duration_minutes: Option<i32>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartRoutineQuery {
+    /// Defaults to now
+    pub start_time: Option<NaiveDateTime>,
+    /// Defaults to the routine name
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoutineResponse {
+    #[serde(flatten)]
+    pub routine: Routine,
+    pub exercises: Vec<RoutineExercise>,
+}
+
+impl Validate for RoutineRequest{
+    fn validate(&self, v: &mut Validator){
+        v.length("name", self.name.trim(), 1, MAX_NAME_LEN)
+         .check(!self.exercises.is_empty(), "exercises", "routine must contain at least one exercise")
+         .range("exercises", self.exercises.len(), 0, MAX_ROUTINE_EXERCISES);
+        if let Some(notes) = &self.notes {
+            v.length("notes", notes, 0, MAX_NOTES_LEN);
+        }
+        for (i, e) in self.exercises.iter().enumerate(){
+            let field = |name: &str| format!("exercises[{}].{}", i, name);
+            v.check(e.variation_id.is_some() != e.cardio_exercise_id.is_some(), &field("variation_id"), "set exactly one of variation_id or cardio_exercise_id");
+            if let Some(target_sets) = e.target_sets {
+                v.range(&field("target_sets"), target_sets, 1, MAX_TARGET_SETS);
+            }
+            if let Some(reps_min) = e.reps_min {
+                v.range(&field("reps_min"), reps_min, 1, MAX_REPS);
+            }
+            if let Some(reps_max) = e.reps_max {
+                v.range(&field("reps_max"), reps_max, 1, MAX_REPS)
+                 .check(e.reps_min.is_none_or(|min| min <= reps_max), &field("reps_max"), "must not be less than reps_min");
+            }
+            if let Some(weight) = e.target_weight {
+                validate_weight(v, &field("target_weight"), weight, self.unit);
+            }
+            if let Some(rest_seconds) = e.rest_seconds {
+                v.range(&field("rest_seconds"), rest_seconds, 0, MAX_REST_SECONDS);
+            }
+            if let Some(duration) = e.target_duration_minutes {
+                v.range(&field("target_duration_minutes"), duration, 1, MAX_MINUTES_PER_DAY);
+            }
+            if let Some(notes) = &e.notes {
+                v.length(&field("notes"), notes, 0, MAX_NOTES_LEN);
+            }
+        }
+    }
+}
+
+impl Validate for StartRoutineQuery{
+    fn validate(&self, v: &mut Validator){
+        if let Some(title) = &self.title {
+            v.length("title", title, 0, MAX_NAME_LEN);
+        }
+        if let Some(start_time) = self.start_time {
+            v.not_future("start_time", start_time.date());
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Routines{}
+
+impl Routines{
+    pub fn new() -> Self{
+        Routines {}
+    }
+
+    fn routine_out(units: &Units, (routine, exercises): (Routine, Vec<RoutineExercise>)) -> RoutineResponse{
+        RoutineResponse {
+            routine,
+            exercises: exercises.into_iter().map(|e| units.routine_exercise_out(e)).collect(),
+        }
+    }
+
+    fn weights_in(units: &Units, req: &mut RoutineRequest){
+        let unit = req.unit;
+        for e in req.exercises.iter_mut(){
+            e.target_weight = e.target_weight.map(|w| units.weight_in(w, unit));
+        }
+    }
+
+    pub async fn list_routines_handler(
+        routine_service: web::Data<RoutineService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let routines = routine_service.get_routines(user.id).await?;
+        let resp: Vec<RoutineResponse> = routines.into_iter().map(|r| Self::routine_out(&units, r)).collect();
+        Ok(HttpResponse::Ok().json(resp))
+    }
+
+    pub async fn get_routine_handler(
+        routine_service: web::Data<RoutineService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let routine = routine_service.get_routine(user.id, path.into_inner()).await?;
+        Ok(HttpResponse::Ok().json(Self::routine_out(&units, routine)))
+    }
+
+    pub async fn create_routine_handler(
+        routine_service: web::Data<RoutineService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        payload: ValidatedJson<RoutineRequest>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let mut req = payload.into_inner();
+        Self::weights_in(&units, &mut req);
+        let routine = routine_service.create_routine(user.id, req).await?;
+        Ok(HttpResponse::Created().json(Self::routine_out(&units, routine)))
+    }
+
+    pub async fn update_routine_handler(
+        routine_service: web::Data<RoutineService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+        payload: ValidatedJson<RoutineRequest>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let mut req = payload.into_inner();
+        Self::weights_in(&units, &mut req);
+        let routine = routine_service.update_routine(user.id, path.into_inner(), req).await?;
+        Ok(HttpResponse::Ok().json(Self::routine_out(&units, routine)))
+    }
+
+    pub async fn delete_routine_handler(
+        routine_service: web::Data<RoutineService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+    ) -> ApiResult<HttpResponse> {
+        routine_service.delete_routine(user.id, path.into_inner()).await?;
+        Ok(HttpResponse::NoContent().finish())
+    }
+
+    /// Starts a session from the routine. The planned sets come back with `completed: false` and
+    /// are ticked off through `PUT /workouts/set/{id}` as the user works through them.
+    pub async fn start_routine_handler(
+        routine_service: web::Data<RoutineService>,
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+        query: ValidatedQuery<StartRoutineQuery>,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let (session, sets, cardio) = routine_service.start_routine(user.id, path.into_inner(), query.into_inner()).await?;
+        let resp = SessionDetailsResponse {
+            session,
+            sets: sets.into_iter().map(|s| units.set_out(s)).collect(),
+            cardio_logs: cardio.into_iter().map(|c| CardioLogDetails::new(c, &units)).collect(),
+            estimated_calories: None,
+        };
+        Ok(HttpResponse::Created().json(resp))
+    }
+}
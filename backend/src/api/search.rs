@@ -0,0 +1,41 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use crate::{api::{middleware::AuthenticatedUser, validation::{Validate, ValidatedQuery, Validator, MAX_NAME_LEN}}, error::ApiResult, services::search_service::SearchService};
+
+const DEFAULT_SEARCH_LIMIT: i64 = 20;
+const MAX_SEARCH_LIMIT: i64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery{
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+impl Validate for SearchQuery{
+    fn validate(&self, v: &mut Validator){
+        v.length("q", self.q.trim(), 1, MAX_NAME_LEN);
+        if let Some(limit) = self.limit {
+            v.range("limit", limit, 1, MAX_SEARCH_LIMIT);
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Search{}
+
+impl Search{
+    pub fn new() -> Self{
+        Search {}
+    }
+
+    /// Ranked matches across session titles and notes, exercise names and descriptions.
+    pub async fn search_handler(
+        search_service: web::Data<SearchService>,
+        user: AuthenticatedUser,
+        query: ValidatedQuery<SearchQuery>,
+    ) -> ApiResult<HttpResponse> {
+        let query = query.into_inner();
+        let hits = search_service.search(user.id, &query.q, query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT)).await?;
+        Ok(HttpResponse::Ok().json(hits))
+    }
+}
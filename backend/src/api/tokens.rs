@@ -0,0 +1,61 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use crate::{api::{middleware::AuthenticatedUser, validation::{Validate, ValidatedJson, Validator, MAX_NAME_LEN}}, error::{ApiError, ApiResult}, services::session_service::{SessionService, TokenScope}};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scope: Option<TokenScope>,
+}
+
+impl Validate for CreateTokenRequest{
+    fn validate(&self, v: &mut Validator){
+        v.length("name", &self.name, 1, MAX_NAME_LEN);
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Tokens{}
+
+impl Tokens{
+    pub fn new() -> Self{
+        Tokens {}
+    }
+
+    pub async fn list_tokens_handler(
+        session_service: web::Data<SessionService>,
+        user: AuthenticatedUser,
+    ) -> ApiResult<HttpResponse> {
+        if user.api_token_id.is_some() {
+            return Err(ApiError::Forbidden("API tokens can't manage API tokens".to_string()));
+        }
+        let tokens = session_service.get_api_tokens(user.id).await?;
+        Ok(HttpResponse::Ok().json(tokens))
+    }
+
+    pub async fn create_token_handler(
+        session_service: web::Data<SessionService>,
+        user: AuthenticatedUser,
+        req: ValidatedJson<CreateTokenRequest>,
+    ) -> ApiResult<HttpResponse> {
+        if user.api_token_id.is_some() {
+            return Err(ApiError::Forbidden("API tokens can't manage API tokens".to_string()));
+        }
+        let req = req.into_inner();
+        let scope = req.scope.unwrap_or(TokenScope::ReadOnly);
+        let created = session_service.create_api_token(user.id, req.name.trim(), scope).await?;
+        Ok(HttpResponse::Created().json(created))
+    }
+
+    pub async fn revoke_token_handler(
+        session_service: web::Data<SessionService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+    ) -> ApiResult<HttpResponse> {
+        if user.api_token_id.is_some() {
+            return Err(ApiError::Forbidden("API tokens can't manage API tokens".to_string()));
+        }
+        session_service.revoke_api_token(user.id, path.into_inner()).await?;
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
@@ -0,0 +1,152 @@
+use std::{fmt::Display, future::Future, pin::Pin};
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use chrono::{NaiveDate, Utc};
+use serde::de::DeserializeOwned;
+use crate::error::{ApiError, ApiResult, FieldError};
+
+pub const MAX_WEIGHT: f64 = 1000.0;
+pub const MAX_REPS: i32 = 1000;
+pub const MAX_MINUTES_PER_DAY: i32 = 24 * 60;
+pub const MAX_BODY_WEIGHT: f64 = 500.0;
+pub const MAX_NAME_LEN: usize = 100;
+pub const MAX_NOTES_LEN: usize = 2000;
+pub const MIN_PASSWORD_LEN: usize = 8;
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Implemented by request payloads, rules push one error per offending field into the validator.
+pub trait Validate{
+    fn validate(&self, v: &mut Validator);
+}
+
+#[derive(Debug, Default)]
+pub struct Validator{
+    errors: Vec<FieldError>,
+}
+
+impl Validator{
+    pub fn new() -> Self{
+        Validator { errors: Vec::new() }
+    }
+
+    pub fn check(&mut self, ok: bool, field: &str, message: &str) -> &mut Self{
+        if !ok {
+            self.errors.push(FieldError{
+                field: field.to_string(),
+                message: message.to_string(),
+            });
+        }
+        self
+    }
+
+    pub fn range<N: PartialOrd + Display + Copy>(&mut self, field: &str, value: N, min: N, max: N) -> &mut Self{
+        let ok = value >= min && value <= max;
+        self.check(ok, field, &format!("must be between {} and {}", min, max))
+    }
+
+    pub fn length(&mut self, field: &str, value: &str, min: usize, max: usize) -> &mut Self{
+        let len = value.trim().chars().count();
+        if len < min {
+            let message = if min <= 1 { "must not be blank".to_string() } else { format!("must be at least {} characters", min) };
+            return self.check(false, field, &message);
+        }
+        self.check(len <= max, field, &format!("must be at most {} characters", max))
+    }
+
+    pub fn email(&mut self, field: &str, value: &str) -> &mut Self{
+        let ok = match value.split_once('@') {
+            Some((local, domain)) => !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !value.chars().any(char::is_whitespace)
+                && value.len() <= MAX_NAME_LEN,
+            None => false,
+        };
+        self.check(ok, field, "must be a valid email address")
+    }
+
+    /// Parses a `YYYY-MM-DD` string, recording an error and returning `None` when it isn't one.
+    pub fn date(&mut self, field: &str, value: &str) -> Option<NaiveDate>{
+        match NaiveDate::parse_from_str(value, DATE_FORMAT) {
+            Ok(d) => Some(d),
+            Err(_) => {
+                self.check(false, field, "must be a date formatted as YYYY-MM-DD");
+                None
+            }
+        }
+    }
+
+    /// Allows a day of slack so clients ahead of UTC can still log today's work.
+    pub fn not_future(&mut self, field: &str, date: NaiveDate) -> &mut Self{
+        let limit = Utc::now().date_naive() + chrono::Duration::days(1);
+        self.check(date <= limit, field, "must not be in the future")
+    }
+
+    pub fn finish(self) -> ApiResult<()>{
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        Err(ApiError::InvalidFields(self.errors))
+    }
+}
+
+pub fn validate<T: Validate>(value: &T) -> ApiResult<()>{
+    let mut v = Validator::new();
+    value.validate(&mut v);
+    v.finish()
+}
+
+/// `web::Json` that rejects payloads failing their `Validate` rules with a 422.
+#[derive(Debug)]
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T>{
+    pub fn into_inner(self) -> T{
+        self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T>{
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future{
+        let json = web::Json::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let value = match json.await {
+                Ok(j) => j.into_inner(),
+                Err(err) => return Err(ApiError::BadRequest(err.to_string())),
+            };
+            validate(&value)?;
+            Ok(ValidatedJson(value))
+        })
+    }
+}
+
+/// `web::Query` counterpart of `ValidatedJson`.
+#[derive(Debug)]
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T> ValidatedQuery<T>{
+    pub fn into_inner(self) -> T{
+        self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedQuery<T>{
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future{
+        let query = web::Query::<T>::from_query(req.query_string());
+        Box::pin(async move {
+            let value = match query {
+                Ok(q) => q.into_inner(),
+                Err(err) => return Err(ApiError::BadRequest(err.to_string())),
+            };
+            validate(&value)?;
+            Ok(ValidatedQuery(value))
+        })
+    }
+}
@@ -1,8 +1,21 @@
 use chrono::{NaiveDate, NaiveDateTime};
 use serde::Deserialize;
-use crate::{services::{get_service::GetService, post_service::PostService, put_service::PutService}};
-use actix_web::{web, HttpResponse, Responder};
-use crate::api::middleware::AuthenticatedUser;
+use crate::{db::{model::{CardioLog, PersonalRecord, SetType}, workouts::HistoryFilter}, error::{ApiError, ApiResult}, services::{get_service::{GetService, HistoryCursor}, post_service::PostService, put_service::PutService, track_import::TrackFormat, units::{DistanceUnit, Units, WeightUnit}}};
+use actix_web::{web, HttpResponse};
+use crate::api::{middleware::AuthenticatedUser, validation::{Validate, ValidatedJson, ValidatedQuery, Validator, MAX_MINUTES_PER_DAY, MAX_NAME_LEN, MAX_NOTES_LEN, MAX_REPS, MAX_WEIGHT}};
+
+const MAX_HISTORY_LIMIT: i64 = 100;
+const MAX_SETS_PER_SESSION: usize = 500;
+const MAX_RIR: i32 = 10;
+pub const MAX_REST_SECONDS: i32 = 60 * 60;
+const MAX_TEMPO_LEN: usize = 16;
+const MAX_DISTANCE_M: f64 = 1_000_000.0;
+const MAX_ELEVATION_M: f64 = 20_000.0;
+const MIN_HEART_RATE: i32 = 20;
+const MAX_HEART_RATE: i32 = 250;
+const MAX_CALORIES: i32 = 20_000;
+const MAX_MET: f64 = 25.0;
+pub const MAX_IMPORT_BYTES: usize = 25 * 1024 * 1024;
 
 #[derive(Debug,Deserialize)]
 pub struct StrengthSet{
@@ -12,7 +25,22 @@ pub struct StrengthSet{
     pub variation_id: i32,
     pub weight: f64,
     pub reps: i32,
-    pub performed_on: NaiveDate
+    pub performed_on: NaiveDate,
+    #[serde(flatten)]
+    pub details: SetDetails,
+}
+
+/// Optional detail shared by every payload that creates or edits a strength set.
+#[derive(Debug, Default, Deserialize)]
+pub struct SetDetails{
+    pub set_index: Option<i32>,
+    pub set_type: Option<SetType>,
+    pub rpe: Option<f64>,
+    pub rir: Option<i32>,
+    pub rest_seconds: Option<i32>,
+    pub tempo: Option<String>,
+    /// Unit `weight` is given in, defaults to the user's preference.
+    pub unit: Option<WeightUnit>,
 }
 
 #[derive(Debug,Deserialize)]
@@ -20,7 +48,32 @@ pub struct CardioSet{
     pub user_id: i32,
     pub workout_session_id: Option<i32>,
     pub cardio_exercise_id: i32,
-    pub duration: i32
+    pub duration: i32,
+    /// Defaults to the day of `started_at`, or today
+    pub performed_on: Option<NaiveDate>,
+    #[serde(flatten)]
+    pub details: CardioDetails,
+}
+
+/// Optional measurements shared by every payload that creates or edits a cardio log.
+#[derive(Debug, Default, Deserialize)]
+pub struct CardioDetails{
+    pub distance: Option<f64>,
+    pub elevation_gain: Option<f64>,
+    pub avg_heart_rate: Option<i32>,
+    pub max_heart_rate: Option<i32>,
+    pub calories: Option<i32>,
+    pub started_at: Option<NaiveDateTime>,
+    /// Unit `distance` is given in (elevation in metres or feet to match), defaults to the user's preference.
+    pub distance_unit: Option<DistanceUnit>,
+}
+
+impl CardioDetails{
+    /// Converts distance and elevation to metres in place so services only see storage units.
+    pub fn to_metric(&mut self, units: &Units){
+        self.distance = self.distance.map(|d| units.distance_in(d, self.distance_unit));
+        self.elevation_gain = self.elevation_gain.map(|e| units.elevation_in(e, self.distance_unit));
+    }
 }
 
 #[derive(Debug,Deserialize)]
@@ -33,6 +86,38 @@ pub struct WorkoutSession{
     pub end_time: NaiveDateTime,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SessionSetEntry {
+    pub variation_id: i32,
+    pub weight: f64,
+    pub reps: i32,
+    #[serde(flatten)]
+    pub details: SetDetails,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionCardioEntry {
+    pub cardio_exercise_id: i32,
+    pub duration: i32,
+    #[serde(flatten)]
+    pub details: CardioDetails,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FullSessionRequest {
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub date: NaiveDate,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    /// Applies to every set that doesn't name its own unit.
+    pub unit: Option<WeightUnit>,
+    #[serde(default)]
+    pub sets: Vec<SessionSetEntry>,
+    #[serde(default)]
+    pub cardio_logs: Vec<SessionCardioEntry>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateSessionRequest {
     pub title: Option<String>,
@@ -46,18 +131,83 @@ pub struct UpdateSessionRequest {
 pub struct UpdateSetRequest {
     pub weight: Option<f64>,
     pub reps: Option<i32>,
+    /// Marks a planned set from a routine as done (or back to planned)
+    pub completed: Option<bool>,
+    #[serde(flatten)]
+    pub details: SetDetails,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateCardioRequest {
     pub duration: Option<i32>,
+    pub completed: Option<bool>,
+    #[serde(flatten)]
+    pub details: CardioDetails,
 }
 
 #[derive(Debug, serde::Serialize)]
 pub struct SessionDetailsResponse {
     pub session: crate::db::model::WorkoutSession,
     pub sets: Vec<crate::db::model::WorkoutSet>,
-    pub cardio_logs: Vec<crate::db::model::CardioLog>,
+    pub cardio_logs: Vec<CardioLogDetails>,
+    /// Estimated kcal burned, only filled in by the session details endpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_calories: Option<f64>,
+}
+
+/// A cardio log with pace and speed derived from distance and duration. The `_m` fields stay in
+/// metres; `distance` and `elevation_gain` repeat them in the user's units.
+#[derive(Debug, serde::Serialize)]
+pub struct CardioLogDetails {
+    #[serde(flatten)]
+    pub log: CardioLog,
+    pub distance: Option<f64>,
+    pub distance_unit: DistanceUnit,
+    pub elevation_gain: Option<f64>,
+    /// `m` or `ft`
+    pub elevation_unit: &'static str,
+    /// Seconds per km or mile
+    pub pace_seconds: Option<f64>,
+    /// Km or miles per hour
+    pub speed: Option<f64>,
+}
+
+impl CardioLogDetails {
+    pub fn new(log: CardioLog, units: &Units) -> Self{
+        let (pace_seconds, speed) = match log.distance_m {
+            Some(m) if m > 0.0 && log.duration_minutes > 0 => {
+                let distance = m / units.distance.metres_per_unit();
+                let minutes = log.duration_minutes as f64;
+                (Some((minutes * 60.0 / distance).round()), Some(((distance * 60.0 / minutes) * 100.0).round() / 100.0))
+            }
+            _ => (None, None),
+        };
+        CardioLogDetails {
+            distance: log.distance_m.map(|m| units.distance_out(m)),
+            distance_unit: units.distance,
+            elevation_gain: log.elevation_gain_m.map(|m| units.elevation_out(m)),
+            elevation_unit: units.distance.elevation_unit(),
+            log,
+            pace_seconds,
+            speed,
+        }
+    }
+}
+
+/// Query string of a track upload, the file itself is the raw request body.
+#[derive(Debug, Deserialize)]
+pub struct ImportTrackQuery {
+    pub cardio_exercise_id: i32,
+    pub workout_session_id: Option<i32>,
+    /// Detected from the file contents when not given
+    pub format: Option<TrackFormat>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ImportTrackResponse {
+    pub session: Option<crate::db::model::WorkoutSession>,
+    pub cardio_log: CardioLogDetails,
+    pub track_points: usize,
 }
 
 #[derive(Debug, Deserialize)]
@@ -65,6 +215,13 @@ pub struct HistoryQuery {
     pub limit: Option<i64>,
     pub start_date: Option<NaiveDate>,
     pub end_date: Option<NaiveDate>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+    /// Case-insensitive search within the session title
+    pub q: Option<String>,
+    pub variation_id: Option<i32>,
+    pub muscle_group_id: Option<i32>,
+    pub has_cardio: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -81,6 +238,223 @@ pub struct CreateVariationRequest {
 #[derive(Debug, Deserialize)]
 pub struct CreateCardioExerciseRequest {
     pub name: String,
+    /// Metabolic equivalent used for calorie estimates, defaults to 7
+    pub met: Option<f64>,
+}
+
+impl Validate for StrengthSet{
+    fn validate(&self, v: &mut Validator){
+        v.check(self.variation_id > 0, "variation_id", "must reference a variation")
+         .check(self.workout_session_id.is_none_or(|id| id > 0), "workout_session_id", "must reference a session")
+         .range("reps", self.reps, 1, MAX_REPS)
+         .not_future("performed_on", self.performed_on);
+        validate_weight(v, "weight", self.weight, self.details.unit);
+        validate_set_details(v, "", &self.details);
+    }
+}
+
+/// The limit is in kg, so weights sent in another unit are converted before checking.
+pub fn validate_weight(v: &mut Validator, field: &str, weight: f64, unit: Option<WeightUnit>){
+    let unit = unit.unwrap_or_default();
+    let kg = unit.to_kg(weight);
+    v.check((0.0..=MAX_WEIGHT).contains(&kg), field, &format!("must be between 0 and {} {}", unit.kg_to(MAX_WEIGHT), unit.as_str()));
+}
+
+/// Tempo is written as four phases (eccentric, pause, concentric, pause), e.g. `3-1-X-0` or `31X0`.
+fn valid_tempo(tempo: &str) -> bool{
+    let phases: Vec<char> = tempo.chars().filter(|c| *c != '-').collect();
+    tempo.len() <= MAX_TEMPO_LEN
+        && phases.len() == 4
+        && phases.iter().all(|c| c.is_ascii_digit() || c.eq_ignore_ascii_case(&'x'))
+}
+
+fn validate_set_details(v: &mut Validator, prefix: &str, details: &SetDetails){
+    if let Some(set_index) = details.set_index {
+        v.check(set_index >= 1, &format!("{}set_index", prefix), "must be at least 1");
+    }
+    if let Some(rpe) = details.rpe {
+        v.range(&format!("{}rpe", prefix), rpe, 1.0, 10.0);
+    }
+    if let Some(rir) = details.rir {
+        v.range(&format!("{}rir", prefix), rir, 0, MAX_RIR);
+    }
+    if let Some(rest_seconds) = details.rest_seconds {
+        v.range(&format!("{}rest_seconds", prefix), rest_seconds, 0, MAX_REST_SECONDS);
+    }
+    if let Some(tempo) = &details.tempo {
+        v.check(valid_tempo(tempo), &format!("{}tempo", prefix), "must be four phases of digits or X, e.g. 3-1-X-0");
+    }
+}
+
+impl Validate for CardioSet{
+    fn validate(&self, v: &mut Validator){
+        v.check(self.cardio_exercise_id > 0, "cardio_exercise_id", "must reference a cardio exercise")
+         .check(self.workout_session_id.is_none_or(|id| id > 0), "workout_session_id", "must reference a session")
+         .range("duration", self.duration, 1, MAX_MINUTES_PER_DAY);
+        if let Some(performed_on) = self.performed_on {
+            v.not_future("performed_on", performed_on);
+        }
+        validate_cardio_details(v, "", &self.details);
+    }
+}
+
+/// Distance limits are in metres, values sent in miles are converted before checking.
+fn validate_cardio_details(v: &mut Validator, prefix: &str, details: &CardioDetails){
+    let unit = details.distance_unit.unwrap_or_default();
+    if let Some(distance) = details.distance {
+        let m = unit.to_m(distance);
+        v.check((0.0..=MAX_DISTANCE_M).contains(&m), &format!("{}distance", prefix), &format!("must be between 0 and {} {}", unit.m_to(MAX_DISTANCE_M), unit.as_str()));
+    }
+    if let Some(elevation) = details.elevation_gain {
+        let m = unit.elevation_to_m(elevation);
+        v.check((0.0..=MAX_ELEVATION_M).contains(&m), &format!("{}elevation_gain", prefix), &format!("must be between 0 and {}", unit.m_to_elevation(MAX_ELEVATION_M)));
+    }
+    if let Some(avg) = details.avg_heart_rate {
+        v.range(&format!("{}avg_heart_rate", prefix), avg, MIN_HEART_RATE, MAX_HEART_RATE);
+    }
+    if let Some(max) = details.max_heart_rate {
+        v.range(&format!("{}max_heart_rate", prefix), max, MIN_HEART_RATE, MAX_HEART_RATE);
+    }
+    if let (Some(avg), Some(max)) = (details.avg_heart_rate, details.max_heart_rate) {
+        v.check(max >= avg, &format!("{}max_heart_rate", prefix), "must not be below avg_heart_rate");
+    }
+    if let Some(calories) = details.calories {
+        v.range(&format!("{}calories", prefix), calories, 0, MAX_CALORIES);
+    }
+    if let Some(started_at) = details.started_at {
+        v.not_future(&format!("{}started_at", prefix), started_at.date());
+    }
+}
+
+fn validate_session_times(v: &mut Validator, start_time: NaiveDateTime, end_time: NaiveDateTime){
+    let minutes = (end_time - start_time).num_minutes();
+    v.check(end_time > start_time, "end_time", "must be after start_time")
+     .check(minutes <= MAX_MINUTES_PER_DAY as i64, "end_time", "session can't last longer than 24 hours");
+}
+
+impl Validate for WorkoutSession{
+    fn validate(&self, v: &mut Validator){
+        if let Some(title) = &self.title {
+            v.length("title", title, 0, MAX_NAME_LEN);
+        }
+        if let Some(notes) = &self.notes {
+            v.length("notes", notes, 0, MAX_NOTES_LEN);
+        }
+        v.not_future("date", self.date);
+        validate_session_times(v, self.start_time, self.end_time);
+    }
+}
+
+impl Validate for FullSessionRequest{
+    fn validate(&self, v: &mut Validator){
+        if let Some(title) = &self.title {
+            v.length("title", title, 0, MAX_NAME_LEN);
+        }
+        if let Some(notes) = &self.notes {
+            v.length("notes", notes, 0, MAX_NOTES_LEN);
+        }
+        v.not_future("date", self.date)
+         .check(!self.sets.is_empty() || !self.cardio_logs.is_empty(), "sets", "session must contain at least one set or cardio log")
+         .range("sets", self.sets.len(), 0, MAX_SETS_PER_SESSION);
+        validate_session_times(v, self.start_time, self.end_time);
+        for (i, set) in self.sets.iter().enumerate(){
+            v.check(set.variation_id > 0, &format!("sets[{}].variation_id", i), "must reference a variation")
+             .range(&format!("sets[{}].reps", i), set.reps, 1, MAX_REPS);
+            validate_weight(v, &format!("sets[{}].weight", i), set.weight, set.details.unit.or(self.unit));
+            validate_set_details(v, &format!("sets[{}].", i), &set.details);
+        }
+        for (i, log) in self.cardio_logs.iter().enumerate(){
+            v.check(log.cardio_exercise_id > 0, &format!("cardio_logs[{}].cardio_exercise_id", i), "must reference a cardio exercise")
+             .range(&format!("cardio_logs[{}].duration", i), log.duration, 1, MAX_MINUTES_PER_DAY);
+            validate_cardio_details(v, &format!("cardio_logs[{}].", i), &log.details);
+        }
+    }
+}
+
+impl Validate for UpdateSessionRequest{
+    fn validate(&self, v: &mut Validator){
+        if let Some(title) = &self.title {
+            v.length("title", title, 0, MAX_NAME_LEN);
+        }
+        if let Some(notes) = &self.notes {
+            v.length("notes", notes, 0, MAX_NOTES_LEN);
+        }
+        if let Some(date) = self.date {
+            v.not_future("date", date);
+        }
+        if let (Some(start_time), Some(end_time)) = (self.start_time, self.end_time) {
+            validate_session_times(v, start_time, end_time);
+        }
+    }
+}
+
+impl Validate for UpdateSetRequest{
+    fn validate(&self, v: &mut Validator){
+        if let Some(weight) = self.weight {
+            validate_weight(v, "weight", weight, self.details.unit);
+        }
+        if let Some(reps) = self.reps {
+            v.range("reps", reps, 1, MAX_REPS);
+        }
+        validate_set_details(v, "", &self.details);
+    }
+}
+
+impl Validate for UpdateCardioRequest{
+    fn validate(&self, v: &mut Validator){
+        if let Some(duration) = self.duration {
+            v.range("duration", duration, 1, MAX_MINUTES_PER_DAY);
+        }
+        validate_cardio_details(v, "", &self.details);
+    }
+}
+
+impl Validate for ImportTrackQuery{
+    fn validate(&self, v: &mut Validator){
+        v.check(self.cardio_exercise_id > 0, "cardio_exercise_id", "must reference a cardio exercise")
+         .check(self.workout_session_id.is_none_or(|id| id > 0), "workout_session_id", "must reference a session");
+    }
+}
+
+impl Validate for HistoryQuery{
+    fn validate(&self, v: &mut Validator){
+        if let Some(limit) = self.limit {
+            v.range("limit", limit, 1, MAX_HISTORY_LIMIT);
+        }
+        if let (Some(start_date), Some(end_date)) = (self.start_date, self.end_date) {
+            v.check(start_date <= end_date, "end_date", "must not be before start_date");
+        }
+        if let Some(cursor) = &self.cursor {
+            v.check(HistoryCursor::parse(cursor).is_some(), "cursor", "is not a valid history cursor");
+        }
+        if let Some(q) = &self.q {
+            v.length("q", q, 1, MAX_NAME_LEN);
+        }
+        v.check(self.variation_id.is_none_or(|id| id > 0), "variation_id", "must reference a variation")
+         .check(self.muscle_group_id.is_none_or(|id| id > 0), "muscle_group_id", "must reference a muscle group");
+    }
+}
+
+impl Validate for CreateMuscleGroupRequest{
+    fn validate(&self, v: &mut Validator){
+        v.length("name", &self.name, 1, MAX_NAME_LEN);
+    }
+}
+
+impl Validate for CreateVariationRequest{
+    fn validate(&self, v: &mut Validator){
+        v.check(self.muscle_group_id > 0, "muscle_group_id", "must reference a muscle group")
+         .length("name", &self.name, 1, MAX_NAME_LEN);
+    }
+}
+
+impl Validate for CreateCardioExerciseRequest{
+    fn validate(&self, v: &mut Validator){
+        v.length("name", &self.name, 1, MAX_NAME_LEN);
+        if let Some(met) = self.met {
+            v.check(met > 0.0 && met <= MAX_MET, "met", &format!("must be between 0 and {}", MAX_MET));
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -91,189 +465,259 @@ impl Workouts{
         Workouts {}
     }
 
+    pub fn records_out(units: &Units, records: Vec<PersonalRecord>) -> Vec<PersonalRecord>{
+        records.into_iter().map(|r| units.record_out(r)).collect()
+    }
+
     pub async fn workout_session_handler(post_service: web::Data<PostService>,
-        payload: web::Json<WorkoutSession>,
+        payload: ValidatedJson<WorkoutSession>,
         user: AuthenticatedUser,
-    ) -> impl Responder {
+    ) -> ApiResult<HttpResponse> {
         let mut session = payload.into_inner();
         session.user_id = user.id;
-        let resp = post_service.add_workout_session(session).await;
-        HttpResponse::Ok().json(resp)
+        let resp = post_service.add_workout_session(session).await?;
+        Ok(HttpResponse::Ok().json(resp))
+    }
+
+    pub async fn full_session_handler(post_service: web::Data<PostService>,
+        get_service: web::Data<GetService>,
+        payload: ValidatedJson<FullSessionRequest>,
+        user: AuthenticatedUser,
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
+        let mut req = payload.into_inner();
+        for set in req.sets.iter_mut(){
+            set.weight = units.weight_in(set.weight, set.details.unit.or(req.unit));
+        }
+        for log in req.cardio_logs.iter_mut(){
+            log.details.to_metric(&units);
+        }
+        let (session, sets, cardio) = post_service.add_full_session(user.id, req).await?;
+        let resp = SessionDetailsResponse {
+            session,
+            sets: sets.into_iter().map(|s| units.set_out(s)).collect(),
+            cardio_logs: cardio.into_iter().map(|c| CardioLogDetails::new(c, &units)).collect(),
+            estimated_calories: None,
+        };
+        Ok(HttpResponse::Created().json(resp))
     }
 
     pub async fn workout_set_handler(post_service: web::Data<PostService>,
-        payload: web::Json<StrengthSet>,
+        get_service: web::Data<GetService>,
+        payload: ValidatedJson<StrengthSet>,
         user: AuthenticatedUser,
-    ) -> impl Responder {
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
         let mut set = payload.into_inner();
         set.user_id = user.id;
-        let resp = post_service.add_workout_set(set).await;
-        HttpResponse::Ok().json(resp)
+        set.weight = units.weight_in(set.weight, set.details.unit);
+        let mut resp = post_service.add_workout_set(set).await?;
+        resp.new_records = Self::records_out(&units, resp.new_records);
+        Ok(HttpResponse::Ok().json(resp))
     }
 
     pub async fn cardio_log_handler(post_service: web::Data<PostService>,
-        payload: web::Json<CardioSet>,
+        get_service: web::Data<GetService>,
+        payload: ValidatedJson<CardioSet>,
         user: AuthenticatedUser,
-    ) -> impl Responder {
+    ) -> ApiResult<HttpResponse> {
+        let units = get_service.get_units(user.id).await?;
         let mut log = payload.into_inner();
         log.user_id = user.id;
-        let resp = post_service.add_cardio_set(log).await;
-        HttpResponse::Ok().json(resp)
+        log.details.to_metric(&units);
+        let resp = post_service.add_cardio_set(log).await?;
+        Ok(HttpResponse::Ok().json(resp))
+    }
+
+    pub async fn import_cardio_handler(post_service: web::Data<PostService>,
+        get_service: web::Data<GetService>,
+        query: ValidatedQuery<ImportTrackQuery>,
+        body: web::Bytes,
+        user: AuthenticatedUser,
+    ) -> ApiResult<HttpResponse> {
+        if body.is_empty() {
+            return Err(ApiError::BadRequest("Request body must contain the activity file".to_string()));
+        }
+        let units = get_service.get_units(user.id).await?;
+        let (session, log, track_points) = post_service.import_cardio_track(user.id, query.into_inner(), &body).await?;
+        Ok(HttpResponse::Created().json(ImportTrackResponse {
+            session,
+            cardio_log: CardioLogDetails::new(log, &units),
+            track_points,
+        }))
+    }
+
+    pub async fn track_points_handler(
+        get_service: web::Data<GetService>,
+        user: AuthenticatedUser,
+        path: web::Path<i32>,
+    ) -> ApiResult<HttpResponse> {
+        let points = get_service.get_track_points(user.id, path.into_inner()).await?;
+        Ok(HttpResponse::Ok().json(points))
     }
 
     pub async fn update_session_handler(
         put_service: web::Data<PutService>,
         user: AuthenticatedUser,
         path: web::Path<i32>,
-        payload: web::Json<UpdateSessionRequest>,
-    ) -> impl Responder {
+        payload: ValidatedJson<UpdateSessionRequest>,
+    ) -> ApiResult<HttpResponse> {
         let session_id = path.into_inner();
         let req = payload.into_inner();
-        let resp = put_service.update_workout_session(user.id, session_id, req.title, req.date, req.start_time, req.end_time, req.notes).await;
-        HttpResponse::Ok().json(resp)
+        let resp = put_service.update_workout_session(user.id, session_id, req.title, req.date, req.start_time, req.end_time, req.notes).await?;
+        Ok(HttpResponse::Ok().json(resp))
     }
 
     pub async fn delete_session_handler(
         put_service: web::Data<PutService>,
         user: AuthenticatedUser,
         path: web::Path<i32>,
-    ) -> impl Responder {
+    ) -> ApiResult<HttpResponse> {
         let session_id = path.into_inner();
-        let resp = put_service.delete_workout_session(user.id, session_id).await;
-        HttpResponse::Ok().json(resp)
+        let resp = put_service.delete_workout_session(user.id, session_id).await?;
+        Ok(HttpResponse::Ok().json(resp))
     }
 
     pub async fn update_set_handler(
         put_service: web::Data<PutService>,
+        get_service: web::Data<GetService>,
         user: AuthenticatedUser,
         path: web::Path<i32>,
-        payload: web::Json<UpdateSetRequest>,
-    ) -> impl Responder {
+        payload: ValidatedJson<UpdateSetRequest>,
+    ) -> ApiResult<HttpResponse> {
         let set_id = path.into_inner();
-        let req = payload.into_inner();
-        let resp = put_service.update_workout_set(user.id, set_id, req.weight, req.reps).await;
-        HttpResponse::Ok().json(resp)
+        let mut req = payload.into_inner();
+        if let Some(weight) = req.weight {
+            let units = get_service.get_units(user.id).await?;
+            req.weight = Some(units.weight_in(weight, req.details.unit));
+        }
+        let resp = put_service.update_workout_set(user.id, set_id, req).await?;
+        Ok(HttpResponse::Ok().json(resp))
     }
 
     pub async fn delete_set_handler(
         put_service: web::Data<PutService>,
         user: AuthenticatedUser,
         path: web::Path<i32>,
-    ) -> impl Responder {
+    ) -> ApiResult<HttpResponse> {
         let set_id = path.into_inner();
-        let resp = put_service.delete_workout_set(user.id, set_id).await;
-        HttpResponse::Ok().json(resp)
+        let resp = put_service.delete_workout_set(user.id, set_id).await?;
+        Ok(HttpResponse::Ok().json(resp))
     }
 
     pub async fn update_cardio_handler(
         put_service: web::Data<PutService>,
+        get_service: web::Data<GetService>,
         user: AuthenticatedUser,
         path: web::Path<i32>,
-        payload: web::Json<UpdateCardioRequest>,
-    ) -> impl Responder {
+        payload: ValidatedJson<UpdateCardioRequest>,
+    ) -> ApiResult<HttpResponse> {
         let log_id = path.into_inner();
-        let req = payload.into_inner();
-        let resp = put_service.update_cardio_log(user.id, log_id, req.duration).await;
-        HttpResponse::Ok().json(resp)
+        let mut req = payload.into_inner();
+        if req.details.distance.is_some() || req.details.elevation_gain.is_some() {
+            let units = get_service.get_units(user.id).await?;
+            req.details.to_metric(&units);
+        }
+        let resp = put_service.update_cardio_log(user.id, log_id, req).await?;
+        Ok(HttpResponse::Ok().json(resp))
     }
 
     pub async fn delete_cardio_handler(
         put_service: web::Data<PutService>,
         user: AuthenticatedUser,
         path: web::Path<i32>,
-    ) -> impl Responder {
+    ) -> ApiResult<HttpResponse> {
         let log_id = path.into_inner();
-        let resp = put_service.delete_cardio_log(user.id, log_id).await;
-        HttpResponse::Ok().json(resp)
+        let resp = put_service.delete_cardio_log(user.id, log_id).await?;
+        Ok(HttpResponse::Ok().json(resp))
     }
     pub async fn history_handler(
         get_service: web::Data<GetService>,
         user: AuthenticatedUser,
-        query: web::Query<HistoryQuery>,
-    ) -> impl Responder {
+        query: ValidatedQuery<HistoryQuery>,
+    ) -> ApiResult<HttpResponse> {
+        let query = query.into_inner();
         let limit = query.limit.unwrap_or(20);
-        match get_service.get_history(user.id, limit, query.start_date, query.end_date).await {
-            Ok(sessions) => HttpResponse::Ok().json(sessions),
-            Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-        }
+        let filter = HistoryFilter {
+            start_date: query.start_date,
+            end_date: query.end_date,
+            after: query.cursor.as_deref().and_then(HistoryCursor::parse).map(|c| (c.start_time, c.id)),
+            title: query.q,
+            variation_id: query.variation_id,
+            muscle_group_id: query.muscle_group_id,
+            has_cardio: query.has_cardio,
+        };
+        let page = get_service.get_history(user.id, limit, filter).await?;
+        Ok(HttpResponse::Ok().json(page))
     }
 
     pub async fn session_details_handler(
         get_service: web::Data<GetService>,
         user: AuthenticatedUser,
         path: web::Path<i32>,
-    ) -> impl Responder {
+    ) -> ApiResult<HttpResponse> {
         let session_id = path.into_inner();
-        match get_service.get_session_details(user.id, session_id).await {
-            Ok((session, sets, cardio)) => {
-                let resp = SessionDetailsResponse {
-                    session,
-                    sets,
-                    cardio_logs: cardio,
-                };
-                HttpResponse::Ok().json(resp)
-            },
-            Err(e) => HttpResponse::NotFound().body(format!("Error: {}", e)),
-        }
+        let (session, sets, cardio, estimated_calories) = get_service.get_session_details(user.id, session_id).await?;
+        let resp = SessionDetailsResponse {
+            session,
+            sets,
+            cardio_logs: cardio,
+            estimated_calories,
+        };
+        Ok(HttpResponse::Ok().json(resp))
     }
 
     pub async fn get_muscle_groups_handler(
         get_service: web::Data<GetService>,
         user: AuthenticatedUser,
-    ) -> impl Responder {
-        match get_service.get_muscle_groups(user.id).await {
-            Ok(data) => HttpResponse::Ok().json(data),
-            Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-        }
+    ) -> ApiResult<HttpResponse> {
+        let data = get_service.get_muscle_groups(user.id).await?;
+        Ok(HttpResponse::Ok().json(data))
     }
 
     pub async fn get_variations_handler(
         get_service: web::Data<GetService>,
         user: AuthenticatedUser,
-    ) -> impl Responder {
-        match get_service.get_variations(user.id).await {
-            Ok(data) => HttpResponse::Ok().json(data),
-            Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-        }
+    ) -> ApiResult<HttpResponse> {
+        let data = get_service.get_variations(user.id).await?;
+        Ok(HttpResponse::Ok().json(data))
     }
 
     pub async fn get_cardio_exercises_handler(
         get_service: web::Data<GetService>,
         user: AuthenticatedUser,
-    ) -> impl Responder {
-        match get_service.get_cardio_exercises(user.id).await {
-            Ok(data) => HttpResponse::Ok().json(data),
-            Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-        }
+    ) -> ApiResult<HttpResponse> {
+        let data = get_service.get_cardio_exercises(user.id).await?;
+        Ok(HttpResponse::Ok().json(data))
     }
 
     pub async fn create_muscle_group_handler(
         post_service: web::Data<PostService>,
         user: AuthenticatedUser,
-        payload: web::Json<CreateMuscleGroupRequest>,
-    ) -> impl Responder {
+        payload: ValidatedJson<CreateMuscleGroupRequest>,
+    ) -> ApiResult<HttpResponse> {
         let req = payload.into_inner();
-        let resp = post_service.add_muscle_group(user.id, req).await;
-        HttpResponse::Ok().json(resp)
+        let resp = post_service.add_muscle_group(user.id, req).await?;
+        Ok(HttpResponse::Ok().json(resp))
     }
 
     pub async fn create_variation_handler(
         post_service: web::Data<PostService>,
         user: AuthenticatedUser,
-        payload: web::Json<CreateVariationRequest>,
-    ) -> impl Responder {
+        payload: ValidatedJson<CreateVariationRequest>,
+    ) -> ApiResult<HttpResponse> {
         let req = payload.into_inner();
-        let resp = post_service.add_variation(user.id, req).await;
-        HttpResponse::Ok().json(resp)
+        let resp = post_service.add_variation(user.id, req).await?;
+        Ok(HttpResponse::Ok().json(resp))
     }
 
     pub async fn create_cardio_exercise_handler(
         post_service: web::Data<PostService>,
         user: AuthenticatedUser,
-        payload: web::Json<CreateCardioExerciseRequest>,
-    ) -> impl Responder {
+        payload: ValidatedJson<CreateCardioExerciseRequest>,
+    ) -> ApiResult<HttpResponse> {
         let req = payload.into_inner();
-        let resp = post_service.add_cardio_exercise(user.id, req).await;
-        HttpResponse::Ok().json(resp)
+        let resp = post_service.add_cardio_exercise(user.id, req).await?;
+        Ok(HttpResponse::Ok().json(resp))
     }
 }
\ No newline at end of file
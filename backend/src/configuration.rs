@@ -6,13 +6,19 @@ const FILE_PATH: &str = "src/config.toml";
 #[derive(Deserialize,Serialize,Default,Clone,Debug)]
 pub struct Config{
     server: Server,pub 
-    database: Database
+    database: Database,
+    #[serde(default)]
+    mail: Mail,
+    #[serde(default)]
+    jwt: Jwt,
 }
 
 #[derive(Deserialize,Serialize,Default,Clone,Debug)]
 pub struct Server{
     pub ip : String,
     pub port: String,
+    #[serde(default)]
+    pub profile: String,
 }
 
 #[derive(Deserialize,Serialize,Default,Clone,Debug)]
@@ -25,6 +31,36 @@ pub struct Database{
     pub schema : String,
 }
 
+#[derive(Deserialize,Serialize,Default,Clone,Debug)]
+pub struct Mail{
+    pub from: String,
+    pub outbox: String,
+    pub reset_url: String,
+}
+
+/// Active signing key. `secret` is used by the HS* algorithms, the PEM paths by RS*/ES*/EdDSA.
+#[derive(Deserialize,Serialize,Default,Clone,Debug)]
+#[serde(default)]
+pub struct Jwt{
+    pub algorithm: String,
+    pub kid: String,
+    pub secret: String,
+    pub private_key: String,
+    pub public_key: String,
+    pub expiration_minutes: i64,
+    pub previous_keys: Vec<JwtKey>,
+}
+
+/// Retired key kept only to verify tokens issued before a rotation.
+#[derive(Deserialize,Serialize,Default,Clone,Debug)]
+#[serde(default)]
+pub struct JwtKey{
+    pub algorithm: String,
+    pub kid: String,
+    pub secret: String,
+    pub public_key: String,
+}
+
 impl Config{
     pub fn load() -> Result<Self,>{
         let mut contents = String::new();
@@ -55,6 +91,18 @@ impl Config{
         if let Ok(name) = std::env::var("DATABASE_NAME") { config.database.dbname = name; }
         if let Ok(ip) = std::env::var("SERVER_IP") { config.server.ip = ip; }
         if let Ok(port) = std::env::var("SERVER_PORT") { config.server.port = port; }
+        if let Ok(profile) = std::env::var("APP_PROFILE") { config.server.profile = profile; }
+        if let Ok(alg) = std::env::var("JWT_ALGORITHM") { config.jwt.algorithm = alg; }
+        if let Ok(kid) = std::env::var("JWT_KID") { config.jwt.kid = kid; }
+        if let Ok(secret) = std::env::var("JWT_SECRET") { config.jwt.secret = secret; }
+        if let Ok(path) = std::env::var("JWT_PRIVATE_KEY") { config.jwt.private_key = path; }
+        if let Ok(path) = std::env::var("JWT_PUBLIC_KEY") { config.jwt.public_key = path; }
+        if let Ok(minutes) = std::env::var("JWT_EXPIRATION_MINUTES") {
+            if let Ok(m) = minutes.parse::<i64>() { config.jwt.expiration_minutes = m; }
+        }
+        if let Ok(from) = std::env::var("MAIL_FROM") { config.mail.from = from; }
+        if let Ok(outbox) = std::env::var("MAIL_OUTBOX") { config.mail.outbox = outbox; }
+        if let Ok(url) = std::env::var("PASSWORD_RESET_URL") { config.mail.reset_url = url; }
         
         Ok(config)
     }
@@ -68,11 +116,22 @@ impl Config{
             schema: self.database.schema.clone(),
         }
     }
+    pub fn get_mail_properties(&self) -> Mail{
+        Mail { 
+            from: self.mail.from.clone(), 
+            outbox: self.mail.outbox.clone(), 
+            reset_url: self.mail.reset_url.clone(), 
+        }
+    }
     pub fn get_server_properties(&self) -> Server{
         Server { 
             ip: self.server.ip.clone(), 
             port: self.server.port.clone(), 
+            profile: self.server.profile.clone(), 
         }
     }
+    pub fn get_jwt_properties(&self) -> Jwt{
+        self.jwt.clone()
+    }
     
 }
\ No newline at end of file
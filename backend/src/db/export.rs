@@ -0,0 +1,202 @@
+use std::{io::Write, sync::Arc};
+use serde::Serialize;
+use crate::db::database::DBOperations;
+use crate::error::{ApiError, ApiResult};
+use diesel_async::{pooled_connection::deadpool::Pool, AsyncPgConnection};
+use crate::{db::model::{CardioExercise, CardioLog, MuscleGroup, Variation, WorkoutSession, WorkoutSet}, schema::fittrack::{cardio_exercises, cardio_logs, muscle_groups, sets, variations, workout_sessions}};
+use diesel_async::RunQueryDsl;
+use diesel::{ExpressionMethods, QueryDsl};
+
+/// Tables included in a data export, in the order they're written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportTable{
+    Sessions,
+    Sets,
+    CardioLogs,
+    MuscleGroups,
+    Variations,
+    CardioExercises,
+}
+
+impl ExportTable{
+    pub const ALL: [ExportTable; 6] = [
+        ExportTable::Sessions,
+        ExportTable::Sets,
+        ExportTable::CardioLogs,
+        ExportTable::MuscleGroups,
+        ExportTable::Variations,
+        ExportTable::CardioExercises,
+    ];
+
+    pub fn name(&self) -> &'static str{
+        match self{
+            ExportTable::Sessions => "sessions",
+            ExportTable::Sets => "sets",
+            ExportTable::CardioLogs => "cardio_logs",
+            ExportTable::MuscleGroups => "muscle_groups",
+            ExportTable::Variations => "variations",
+            ExportTable::CardioExercises => "cardio_exercises",
+        }
+    }
+}
+
+/// One page of rows from an export table, ordered by id.
+pub enum ExportRows{
+    Sessions(Vec<WorkoutSession>),
+    Sets(Vec<WorkoutSet>),
+    CardioLogs(Vec<CardioLog>),
+    MuscleGroups(Vec<MuscleGroup>),
+    Variations(Vec<Variation>),
+    CardioExercises(Vec<CardioExercise>),
+}
+
+fn rows_to_csv<W: Write, T: Serialize>(writer: &mut csv::Writer<W>, rows: &[T]) -> csv::Result<()>{
+    for row in rows.iter(){
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes rows as array elements, `first` is whether nothing has been written into the array yet.
+fn rows_to_json<W: Write, T: Serialize>(writer: &mut W, rows: &[T], mut first: bool) -> std::io::Result<()>{
+    for row in rows.iter(){
+        if !first {
+            writer.write_all(b",\n")?;
+        }
+        serde_json::to_writer(&mut *writer, row)?;
+        first = false;
+    }
+    Ok(())
+}
+
+impl ExportRows{
+    pub fn len(&self) -> usize{
+        match self{
+            ExportRows::Sessions(r) => r.len(),
+            ExportRows::Sets(r) => r.len(),
+            ExportRows::CardioLogs(r) => r.len(),
+            ExportRows::MuscleGroups(r) => r.len(),
+            ExportRows::Variations(r) => r.len(),
+            ExportRows::CardioExercises(r) => r.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.len() == 0
+    }
+
+    /// Id of the last row, where the next page starts after.
+    pub fn last_id(&self) -> Option<i32>{
+        match self{
+            ExportRows::Sessions(r) => r.last().map(|x| x.id),
+            ExportRows::Sets(r) => r.last().map(|x| x.id),
+            ExportRows::CardioLogs(r) => r.last().map(|x| x.id),
+            ExportRows::MuscleGroups(r) => r.last().map(|x| x.id),
+            ExportRows::Variations(r) => r.last().map(|x| x.id),
+            ExportRows::CardioExercises(r) => r.last().map(|x| x.id),
+        }
+    }
+
+    pub fn write_csv<W: Write>(&self, writer: &mut csv::Writer<W>) -> csv::Result<()>{
+        match self{
+            ExportRows::Sessions(r) => rows_to_csv(writer, r),
+            ExportRows::Sets(r) => rows_to_csv(writer, r),
+            ExportRows::CardioLogs(r) => rows_to_csv(writer, r),
+            ExportRows::MuscleGroups(r) => rows_to_csv(writer, r),
+            ExportRows::Variations(r) => rows_to_csv(writer, r),
+            ExportRows::CardioExercises(r) => rows_to_csv(writer, r),
+        }
+    }
+
+    pub fn write_json<W: Write>(&self, writer: &mut W, first: bool) -> std::io::Result<()>{
+        match self{
+            ExportRows::Sessions(r) => rows_to_json(writer, r, first),
+            ExportRows::Sets(r) => rows_to_json(writer, r, first),
+            ExportRows::CardioLogs(r) => rows_to_json(writer, r, first),
+            ExportRows::MuscleGroups(r) => rows_to_json(writer, r, first),
+            ExportRows::Variations(r) => rows_to_json(writer, r, first),
+            ExportRows::CardioExercises(r) => rows_to_json(writer, r, first),
+        }
+    }
+}
+
+pub struct ExportDB{
+    database: Arc<DBOperations>,
+    pool: Option<Pool<AsyncPgConnection>>
+}
+
+impl ExportDB{
+    pub fn new(database: Arc<DBOperations>) -> Self{
+        ExportDB { database, pool: None }
+    }
+
+    pub async fn init(&mut self) -> ApiResult<()>{
+        let pool = match self.database.get_pool().await{
+            Ok(p) => p,
+            Err(err) => return Err(err.into())
+        };
+
+        self.pool = Some(pool);
+        Ok(())
+    }
+
+    /// Up to `limit` of the user's rows with an id above `after_id`. Only the user's own muscle
+    /// groups, variations and cardio exercises are exported, not the shared catalogue.
+    pub async fn get_page(&self, user_id: i32, table: ExportTable, after_id: i32, limit: i64) -> ApiResult<ExportRows>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let rows = match table{
+            ExportTable::Sessions => ExportRows::Sessions(workout_sessions::table
+                .filter(workout_sessions::user_id.eq(user_id))
+                .filter(workout_sessions::id.gt(after_id))
+                .order(workout_sessions::id.asc())
+                .limit(limit)
+                .get_results(&mut conn)
+                .await?),
+            ExportTable::Sets => ExportRows::Sets(sets::table
+                .filter(sets::user_id.eq(user_id))
+                .filter(sets::id.gt(after_id))
+                .order(sets::id.asc())
+                .limit(limit)
+                .get_results(&mut conn)
+                .await?),
+            ExportTable::CardioLogs => ExportRows::CardioLogs(cardio_logs::table
+                .filter(cardio_logs::user_id.eq(user_id))
+                .filter(cardio_logs::id.gt(after_id))
+                .order(cardio_logs::id.asc())
+                .limit(limit)
+                .get_results(&mut conn)
+                .await?),
+            ExportTable::MuscleGroups => ExportRows::MuscleGroups(muscle_groups::table
+                .filter(muscle_groups::user_id.eq(user_id))
+                .filter(muscle_groups::id.gt(after_id))
+                .order(muscle_groups::id.asc())
+                .limit(limit)
+                .get_results(&mut conn)
+                .await?),
+            ExportTable::Variations => ExportRows::Variations(variations::table
+                .filter(variations::user_id.eq(user_id))
+                .filter(variations::id.gt(after_id))
+                .order(variations::id.asc())
+                .limit(limit)
+                .get_results(&mut conn)
+                .await?),
+            ExportTable::CardioExercises => ExportRows::CardioExercises(cardio_exercises::table
+                .filter(cardio_exercises::user_id.eq(user_id))
+                .filter(cardio_exercises::id.gt(after_id))
+                .order(cardio_exercises::id.asc())
+                .limit(limit)
+                .get_results(&mut conn)
+                .await?),
+        };
+        Ok(rows)
+    }
+}
@@ -1,10 +1,44 @@
 use std::sync::Arc;
 use crate::db::database::DBOperations;
-use anyhow::{Result,bail};
-use diesel_async::{pooled_connection::deadpool::Pool, AsyncPgConnection};
-use crate::{db::{model::{CardioLog, NewCardioLog, NewWorkoutSession, NewWorkoutSet, UpdateCardioLog, UpdateWorkoutSession, UpdateWorkoutSet, WorkoutSession, WorkoutSet, MuscleGroup, Variation, CardioExercise, NewMuscleGroup, NewVariation, NewCardioExercise}}, schema::fittrack::{cardio_logs, sets, workout_sessions, variations, muscle_groups, cardio_exercises}};
+use crate::error::{ApiError, ApiResult};
+use diesel_async::{pooled_connection::deadpool::Pool, scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection};
+use crate::{db::{model::{CardioLog, NewCardioLog, NewTrackPoint, NewWorkoutSession, NewWorkoutSet, UpdateCardioLog, UpdateWorkoutSession, UpdateWorkoutSet, WorkoutSession, WorkoutSet, MuscleGroup, Variation, CardioExercise, NewMuscleGroup, NewVariation, NewCardioExercise}}, schema::fittrack::{cardio_logs, cardio_track_points, sets, workout_sessions, variations, muscle_groups, cardio_exercises}};
 use diesel_async::RunQueryDsl;
-use diesel::ExpressionMethods;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+
+const TRACK_POINT_CHUNK: usize = 5000;
+
+/// Exercise of an imported row: an existing id, or an index into the exercises the import creates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportRef{
+    Existing(i32),
+    New(usize),
+}
+
+#[derive(Debug)]
+pub struct ImportedSession{
+    pub session: NewWorkoutSession,
+    pub sets: Vec<(ImportRef, NewWorkoutSet)>,
+    pub logs: Vec<(ImportRef, NewCardioLog)>,
+}
+
+/// Everything an import writes. New variations without a muscle group go into a user-owned
+/// group named `fallback_group`, created if the user doesn't have one yet.
+#[derive(Debug)]
+pub struct ImportBatch{
+    pub user_id: i32,
+    pub fallback_group: String,
+    pub new_variations: Vec<(String, Option<i32>)>,
+    pub new_cardio_exercises: Vec<String>,
+    pub sessions: Vec<ImportedSession>,
+}
+
+fn resolve(reference: ImportRef, created: &[i32]) -> Result<i32, diesel::result::Error>{
+    match reference{
+        ImportRef::Existing(id) => Ok(id),
+        ImportRef::New(i) => created.get(i).copied().ok_or(diesel::result::Error::NotFound),
+    }
+}
 
 pub struct LoggerDB{
     database: Arc<DBOperations>,
@@ -16,25 +50,25 @@ impl LoggerDB{
         LoggerDB { database, pool: None }
     }
 
-    pub async fn init(&mut self) -> Result<(),>{
+    pub async fn init(&mut self) -> ApiResult<()>{
         let pool = match self.database.get_pool().await{
             Ok(p) => p,
-            Err(err) => bail!("{}",err)
+            Err(err) => return Err(err.into())
         };
 
         self.pool = Some(pool);
         Ok(())
     }
 
-    pub async fn add_workout_session(&self, mut session: NewWorkoutSession) -> Result<WorkoutSession>{
+    pub async fn add_workout_session(&self, mut session: NewWorkoutSession) -> ApiResult<WorkoutSession>{
         let pool = match &self.pool{
             Some(pok) => pok,
-            None => bail!("Pool is not intialiased"),
+            None => return Err(ApiError::Internal("Pool is not intialiased".to_string())),
         };
         let mut conn = match pool.get().await{
             Ok(cok) => cok,
             Err(err) => {
-                anyhow::bail!("{}",err);
+                return Err(err.into());
             }
         };
         if session.title.is_none(){
@@ -47,23 +81,36 @@ impl LoggerDB{
             .await
             {
                 Ok(iok) => iok,
-                Err(err) => bail!("{}",err)
+                Err(err) => return Err(err.into())
             };
         Ok(inserted_session)
     }
 
-    pub async fn add_workout_set(&self, set:NewWorkoutSet) -> Result<(),>{
+    pub async fn add_workout_set(&self, mut set:NewWorkoutSet) -> ApiResult<WorkoutSet,>{
         println!("Adding workout set: {:?}", set);
         let pool = match &self.pool{
             Some(pok ) => pok,
-            None => bail!("Pool is not intialised"),
+            None => return Err(ApiError::Internal("Pool is not intialised".to_string())),
         };
 
         let mut conn = match pool.get().await{
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
 
+        // Sets logged into a session without an explicit position go after the last one
+        if let (None, Some(session_id)) = (set.set_index, set.workout_session_id) {
+            let last_index = match sets::table
+                .filter(sets::workout_session_id.eq(session_id))
+                .select(diesel::dsl::max(sets::set_index))
+                .first::<Option<i32>>(&mut conn)
+                .await{
+                    Ok(i) => i,
+                    Err(err) => return Err(err.into())
+                };
+            set.set_index = Some(last_index.unwrap_or(0) + 1);
+        }
+
         let inserted_set:WorkoutSet = match diesel::insert_into(sets::table)
             .values(&set)
             // .returning(WorkoutSet::as_select()) 
@@ -71,73 +118,265 @@ impl LoggerDB{
             .await
             {
                 Ok(sok) => sok,
-                Err(err) => bail!("{}",err)
+                Err(err) => return Err(err.into())
             };
 
         println!("Inserted set: {:?}", inserted_set);
-        Ok(())
+        Ok(inserted_set)
     }
 
-    pub async fn add_workout_cardio(&self, logs:NewCardioLog) -> Result<(),>{
+    pub async fn add_workout_cardio(&self, logs:NewCardioLog) -> ApiResult<CardioLog>{
         let pool = match &self.pool{
             Some(pok ) => pok,
-            None => bail!("Pool is not intialised"),
+            None => return Err(ApiError::Internal("Pool is not intialised".to_string())),
         };
 
         let mut conn = match pool.get().await{
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
 
-        let _inserted_log:CardioLog = match diesel::insert_into(cardio_logs::table)
+        match diesel::insert_into(cardio_logs::table)
             .values(&logs)
             .get_result(&mut conn)
             .await
             {
-                Ok(sok) => sok,
-                Err(err) => bail!("{}",err)
+                Ok(inserted_log) => Ok(inserted_log),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    /// Writes a session with all of its sets and cardio logs in one transaction, so a failure
+    /// part way through leaves nothing behind. The session id is filled in on every child row.
+    pub async fn add_full_session(&self, mut session: NewWorkoutSession, mut sets: Vec<NewWorkoutSet>, mut logs: Vec<NewCardioLog>) -> ApiResult<(WorkoutSession, Vec<WorkoutSet>, Vec<CardioLog>)>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string())),
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into()),
+        };
+        if session.title.is_none(){
+            session.title = Some(format!("Session-{}", session.date));
+        }
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let inserted_session = diesel::insert_into(workout_sessions::table)
+                .values(&session)
+                .get_result::<WorkoutSession>(conn)
+                .await?;
+
+            for set in sets.iter_mut(){
+                set.workout_session_id = Some(inserted_session.id);
+            }
+            for log in logs.iter_mut(){
+                log.workout_session_id = Some(inserted_session.id);
+            }
+
+            let inserted_sets = if sets.is_empty() { Vec::new() } else {
+                diesel::insert_into(sets::table)
+                    .values(&sets)
+                    .get_results::<WorkoutSet>(conn)
+                    .await?
             };
-        Ok(())
+            let inserted_logs = if logs.is_empty() { Vec::new() } else {
+                diesel::insert_into(cardio_logs::table)
+                    .values(&logs)
+                    .get_results::<CardioLog>(conn)
+                    .await?
+            };
+            Ok((inserted_session, inserted_sets, inserted_logs))
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(r) => Ok(r),
+            Err(err) => Err(err.into())
+        }
     }
 
-    pub async fn add_muscle_group(&self, data: NewMuscleGroup<'_>) -> Result<MuscleGroup> {
-        let pool = match &self.pool { Some(p) => p, None => bail!("Pool not initialized") };
+    /// Stores an imported activity: the session (when the caller didn't pick one), the cardio log
+    /// and its track points, all or nothing.
+    pub async fn add_imported_cardio(&self, session: Option<NewWorkoutSession>, mut log: NewCardioLog, mut points: Vec<NewTrackPoint>) -> ApiResult<(Option<WorkoutSession>, CardioLog)>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string())),
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into()),
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let inserted_session = match session{
+                Some(s) => {
+                    let inserted = diesel::insert_into(workout_sessions::table)
+                        .values(&s)
+                        .get_result::<WorkoutSession>(conn)
+                        .await?;
+                    log.workout_session_id = Some(inserted.id);
+                    Some(inserted)
+                }
+                None => None,
+            };
+            let inserted_log = diesel::insert_into(cardio_logs::table)
+                .values(&log)
+                .get_result::<CardioLog>(conn)
+                .await?;
+
+            for point in points.iter_mut(){
+                point.cardio_log_id = inserted_log.id;
+            }
+            // Stay well under Postgres' bind parameter limit
+            for chunk in points.chunks(TRACK_POINT_CHUNK){
+                diesel::insert_into(cardio_track_points::table)
+                    .values(chunk)
+                    .execute(conn)
+                    .await?;
+            }
+            Ok((inserted_session, inserted_log))
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(r) => Ok(r),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Writes a whole import, new exercises included, in one transaction. Returns the number of
+    /// sessions, sets and cardio logs stored and the ids of every variation that received sets.
+    pub async fn add_import(&self, batch: ImportBatch) -> ApiResult<(usize, usize, usize, Vec<i32>)>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string())),
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into()),
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let user_id = batch.user_id;
+            let mut fallback: Option<i32> = None;
+            let mut variation_ids = Vec::new();
+            for (name, group) in batch.new_variations.iter(){
+                let muscle_group_id = match (*group, fallback){
+                    (Some(id), _) | (None, Some(id)) => id,
+                    (None, None) => {
+                        let existing: Option<i32> = muscle_groups::table
+                            .filter(muscle_groups::user_id.eq(user_id))
+                            .filter(muscle_groups::name.eq(&batch.fallback_group))
+                            .select(muscle_groups::id)
+                            .first(conn)
+                            .await
+                            .optional()?;
+                        let id = match existing{
+                            Some(id) => id,
+                            None => diesel::insert_into(muscle_groups::table)
+                                .values(&NewMuscleGroup { name: &batch.fallback_group, user_id })
+                                .returning(muscle_groups::id)
+                                .get_result(conn)
+                                .await?,
+                        };
+                        fallback = Some(id);
+                        id
+                    }
+                };
+                let id = diesel::insert_into(variations::table)
+                    .values(&NewVariation { muscle_group_id, name, user_id, description: None })
+                    .returning(variations::id)
+                    .get_result(conn)
+                    .await?;
+                variation_ids.push(id);
+            }
+            let mut cardio_ids = Vec::new();
+            for name in batch.new_cardio_exercises.iter(){
+                let id = diesel::insert_into(cardio_exercises::table)
+                    .values(&NewCardioExercise { name, user_id, met: None })
+                    .returning(cardio_exercises::id)
+                    .get_result(conn)
+                    .await?;
+                cardio_ids.push(id);
+            }
+
+            let session_count = batch.sessions.len();
+            let (mut set_count, mut log_count) = (0, 0);
+            let mut touched = Vec::new();
+            for imported in batch.sessions.into_iter(){
+                let session_id: i32 = diesel::insert_into(workout_sessions::table)
+                    .values(&imported.session)
+                    .returning(workout_sessions::id)
+                    .get_result(conn)
+                    .await?;
+                let mut sets = Vec::with_capacity(imported.sets.len());
+                for (reference, mut set) in imported.sets.into_iter(){
+                    set.workout_session_id = Some(session_id);
+                    set.variation_id = resolve(reference, &variation_ids)?;
+                    if !touched.contains(&set.variation_id) {
+                        touched.push(set.variation_id);
+                    }
+                    sets.push(set);
+                }
+                let mut logs = Vec::with_capacity(imported.logs.len());
+                for (reference, mut log) in imported.logs.into_iter(){
+                    log.workout_session_id = Some(session_id);
+                    log.cardio_exercise_id = resolve(reference, &cardio_ids)?;
+                    logs.push(log);
+                }
+                if !sets.is_empty() {
+                    set_count += diesel::insert_into(sets::table).values(&sets).execute(conn).await?;
+                }
+                if !logs.is_empty() {
+                    log_count += diesel::insert_into(cardio_logs::table).values(&logs).execute(conn).await?;
+                }
+            }
+            Ok((session_count, set_count, log_count, touched))
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(r) => Ok(r),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    pub async fn add_muscle_group(&self, data: NewMuscleGroup<'_>) -> ApiResult<MuscleGroup> {
+        let pool = match &self.pool { Some(p) => p, None => return Err(ApiError::Internal("Pool not initialized".to_string())) };
         let mut conn = match pool.get().await {
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
         let res = diesel::insert_into(muscle_groups::table).values(&data).get_result(&mut conn).await?;
         Ok(res)
     }
 
-    pub async fn add_variation(&self, data: NewVariation<'_>) -> Result<Variation> {
-        let pool = match &self.pool { Some(p) => p, None => bail!("Pool not initialized") };
+    pub async fn add_variation(&self, data: NewVariation<'_>) -> ApiResult<Variation> {
+        let pool = match &self.pool { Some(p) => p, None => return Err(ApiError::Internal("Pool not initialized".to_string())) };
         let mut conn = match pool.get().await {
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
         let res = diesel::insert_into(variations::table).values(&data).get_result(&mut conn).await?;
         Ok(res)
     }
 
-    pub async fn add_cardio_exercise(&self, data: NewCardioExercise<'_>) -> Result<CardioExercise> {
-        let pool = match &self.pool { Some(p) => p, None => bail!("Pool not initialized") };
+    pub async fn add_cardio_exercise(&self, data: NewCardioExercise<'_>) -> ApiResult<CardioExercise> {
+        let pool = match &self.pool { Some(p) => p, None => return Err(ApiError::Internal("Pool not initialized".to_string())) };
         let mut conn = match pool.get().await {
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
         let res = diesel::insert_into(cardio_exercises::table).values(&data).get_result(&mut conn).await?;
         Ok(res)
     }
 
-    pub async fn update_workout_session(&self, user_id: i32, session_id: i32, data: UpdateWorkoutSession) -> Result<WorkoutSession> {
+    pub async fn update_workout_session(&self, user_id: i32, session_id: i32, data: UpdateWorkoutSession) -> ApiResult<WorkoutSession> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => bail!("Pool not initialized"),
+            None => return Err(ApiError::Internal("Pool not initialized".to_string())),
         };
         let mut conn = match pool.get().await {
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
 
         let updated_session = diesel::update(workout_sessions::table)
@@ -149,14 +388,14 @@ impl LoggerDB{
         Ok(updated_session)
     }
 
-    pub async fn delete_workout_session(&self, user_id: i32, session_id: i32) -> Result<()> {
+    pub async fn delete_workout_session(&self, user_id: i32, session_id: i32) -> ApiResult<()> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => bail!("Pool not initialized"),
+            None => return Err(ApiError::Internal("Pool not initialized".to_string())),
         };
         let mut conn = match pool.get().await {
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
 
         let count = diesel::delete(workout_sessions::table)
@@ -166,19 +405,19 @@ impl LoggerDB{
             .await?;
         
         if count == 0 {
-            bail!("Session not found or access denied");
+            return Err(ApiError::NotFound("Session not found".to_string()));
         }
         Ok(())
     }
 
-    pub async fn update_workout_set(&self, user_id: i32, set_id: i32, data: UpdateWorkoutSet) -> Result<WorkoutSet> {
+    pub async fn update_workout_set(&self, user_id: i32, set_id: i32, data: UpdateWorkoutSet) -> ApiResult<WorkoutSet> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => bail!("Pool not initialized"),
+            None => return Err(ApiError::Internal("Pool not initialized".to_string())),
         };
         let mut conn = match pool.get().await {
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
 
         let updated_set = diesel::update(sets::table)
@@ -190,36 +429,37 @@ impl LoggerDB{
         Ok(updated_set)
     }
 
-    pub async fn delete_workout_set(&self, user_id: i32, set_id: i32) -> Result<()> {
+    pub async fn delete_workout_set(&self, user_id: i32, set_id: i32) -> ApiResult<WorkoutSet> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => bail!("Pool not initialized"),
+            None => return Err(ApiError::Internal("Pool not initialized".to_string())),
         };
         let mut conn = match pool.get().await {
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
         
-        let count = diesel::delete(sets::table)
+        let deleted_set = diesel::delete(sets::table)
             .filter(sets::id.eq(set_id))
             .filter(sets::user_id.eq(user_id))
-            .execute(&mut conn)
-            .await?;
+            .get_result::<WorkoutSet>(&mut conn)
+            .await
+            .optional()?;
         
-        if count == 0 {
-            bail!("Set not found or access denied");
+        match deleted_set {
+            Some(set) => Ok(set),
+            None => Err(ApiError::NotFound("Set not found".to_string())),
         }
-        Ok(())
     }
 
-    pub async fn update_cardio_log(&self, user_id: i32, log_id: i32, data: UpdateCardioLog) -> Result<CardioLog> {
+    pub async fn update_cardio_log(&self, user_id: i32, log_id: i32, data: UpdateCardioLog) -> ApiResult<CardioLog> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => bail!("Pool not initialized"),
+            None => return Err(ApiError::Internal("Pool not initialized".to_string())),
         };
         let mut conn = match pool.get().await {
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
 
         let updated_log = diesel::update(cardio_logs::table)
@@ -231,14 +471,14 @@ impl LoggerDB{
         Ok(updated_log)
     }
 
-    pub async fn delete_cardio_log(&self, user_id: i32, log_id: i32) -> Result<()> {
+    pub async fn delete_cardio_log(&self, user_id: i32, log_id: i32) -> ApiResult<()> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => bail!("Pool not initialized"),
+            None => return Err(ApiError::Internal("Pool not initialized".to_string())),
         };
         let mut conn = match pool.get().await {
             Ok(c) => c,
-            Err(err) => bail!(err),
+            Err(err) => return Err(err.into()),
         };
 
         let count = diesel::delete(cardio_logs::table)
@@ -248,7 +488,7 @@ impl LoggerDB{
             .await?;
         
         if count == 0 {
-            bail!("Cardio log not found or access denied");
+            return Err(ApiError::NotFound("Cardio log not found".to_string()));
         }
         Ok(())
     }
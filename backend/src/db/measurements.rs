@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use chrono::NaiveDate;
+use crate::db::database::DBOperations;
+use crate::error::{ApiError, ApiResult};
+use diesel_async::{pooled_connection::deadpool::Pool, scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection};
+use crate::{db::model::{BodyMeasurement, NewBodyMeasurement, UpdateBodyMeasurement}, schema::fittrack::{body_measurements, users}};
+use diesel_async::RunQueryDsl;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+
+pub struct MeasurementDB{
+    database: Arc<DBOperations>,
+    pool: Option<Pool<AsyncPgConnection>>
+}
+
+/// Copies the most recent weight reading onto `users.weight`, or clears it when none are left.
+async fn sync_latest_weight(conn: &mut AsyncPgConnection, user_id: i32) -> Result<(), diesel::result::Error>{
+    let latest: Option<Option<f64>> = body_measurements::table
+        .filter(body_measurements::user_id.eq(user_id))
+        .filter(body_measurements::weight.is_not_null())
+        .order((body_measurements::measured_on.desc(), body_measurements::id.desc()))
+        .select(body_measurements::weight)
+        .first(conn)
+        .await
+        .optional()?;
+    diesel::update(users::table)
+        .filter(users::id.eq(user_id))
+        .set(users::weight.eq(latest.flatten()))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+impl MeasurementDB{
+    pub fn new(database: Arc<DBOperations>) -> Self{
+        MeasurementDB { database, pool: None }
+    }
+
+    pub async fn init(&mut self) -> ApiResult<()>{
+        let pool = match self.database.get_pool().await{
+            Ok(p) => p,
+            Err(err) => return Err(err.into())
+        };
+
+        self.pool = Some(pool);
+        Ok(())
+    }
+
+    /// Readings between the two dates, newest first.
+    pub async fn get_measurements(&self, user_id: i32, start_date: NaiveDate, end_date: NaiveDate) -> ApiResult<Vec<BodyMeasurement>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match body_measurements::table
+            .filter(body_measurements::user_id.eq(user_id))
+            .filter(body_measurements::measured_on.ge(start_date))
+            .filter(body_measurements::measured_on.le(end_date))
+            .order((body_measurements::measured_on.desc(), body_measurements::id.desc()))
+            .get_results(&mut conn)
+            .await{
+                Ok(m) => Ok(m),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    /// The most recent body fat reading, if the user ever logged one.
+    pub async fn get_latest_body_fat(&self, user_id: i32) -> ApiResult<Option<f64>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match body_measurements::table
+            .filter(body_measurements::user_id.eq(user_id))
+            .filter(body_measurements::body_fat_percent.is_not_null())
+            .order((body_measurements::measured_on.desc(), body_measurements::id.desc()))
+            .select(body_measurements::body_fat_percent)
+            .first::<Option<f64>>(&mut conn)
+            .await
+            .optional(){
+                Ok(value) => Ok(value.flatten()),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    pub async fn add_measurement(&self, data: NewBodyMeasurement) -> ApiResult<BodyMeasurement>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let inserted = diesel::insert_into(body_measurements::table)
+                .values(&data)
+                .get_result::<BodyMeasurement>(conn)
+                .await?;
+            sync_latest_weight(conn, inserted.user_id).await?;
+            Ok(inserted)
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(m) => Ok(m),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    pub async fn update_measurement(&self, user_id: i32, measurement_id: i32, data: UpdateBodyMeasurement) -> ApiResult<BodyMeasurement>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let updated = diesel::update(body_measurements::table)
+                .filter(body_measurements::id.eq(measurement_id))
+                .filter(body_measurements::user_id.eq(user_id))
+                .set(&data)
+                .get_result::<BodyMeasurement>(conn)
+                .await?;
+            sync_latest_weight(conn, user_id).await?;
+            Ok(updated)
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(m) => Ok(m),
+            Err(diesel::result::Error::NotFound) => Err(ApiError::NotFound("Measurement not found".to_string())),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    pub async fn delete_measurement(&self, user_id: i32, measurement_id: i32) -> ApiResult<()>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let count = diesel::delete(body_measurements::table)
+                .filter(body_measurements::id.eq(measurement_id))
+                .filter(body_measurements::user_id.eq(user_id))
+                .execute(conn)
+                .await?;
+            if count == 0 {
+                return Err(diesel::result::Error::NotFound);
+            }
+            sync_latest_weight(conn, user_id).await
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(diesel::result::Error::NotFound) => Err(ApiError::NotFound("Measurement not found".to_string())),
+            Err(err) => Err(err.into())
+        }
+    }
+}
@@ -7,6 +7,13 @@ pub mod model;
 pub mod user;
 pub mod workouts;
 pub mod logger;
+pub mod records;
+pub mod tokens;
+pub mod routines;
+pub mod programs;
+pub mod measurements;
+pub mod export;
+pub mod search;
 
 pub struct Database{
     pub database: Option<Arc<DBOperations>>,
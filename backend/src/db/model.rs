@@ -1,7 +1,7 @@
 use chrono::NaiveDate;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
-use crate::schema::fittrack::{users, muscle_groups, variations, sets, cardio_exercises, cardio_logs, workout_sessions};
+use crate::schema::fittrack::{users, muscle_groups, variations, sets, cardio_exercises, cardio_logs, cardio_track_points, workout_sessions, personal_records, routines, routine_exercises, programs, program_weeks, program_days, progression_rules, body_measurements, refresh_tokens, password_reset_tokens, api_tokens, account_deletions};
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Selectable)]
 #[diesel(table_name = users)]
@@ -16,6 +16,9 @@ pub struct User {
     pub weight: Option<f64>,
     pub height: Option<f64>,
     pub dob: Option<chrono::NaiveDate>,
+    pub weight_unit: String,
+    pub length_unit: String,
+    pub sex: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable)]
@@ -28,6 +31,9 @@ pub struct NewUser<'a> {
     pub weight: Option<f64>,
     pub height: Option<f64>,
     pub dob: Option<chrono::NaiveDate>,
+    pub weight_unit: &'a str,
+    pub length_unit: &'a str,
+    pub sex: Option<&'a str>,
 }
 
 #[derive(Debug, Serialize, Deserialize, AsChangeset)]
@@ -39,6 +45,9 @@ pub struct UpdateUser<'a>{
     pub weight: Option<f64>,
     pub height: Option<f64>,
     pub dob: Option<chrono::NaiveDate>,
+    pub weight_unit: Option<&'a str>,
+    pub length_unit: Option<&'a str>,
+    pub sex: Option<&'a str>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Identifiable)]
@@ -87,6 +96,7 @@ pub struct WorkoutSession {
     pub start_time: chrono::NaiveDateTime,
     pub end_time: chrono::NaiveDateTime,
     pub notes: Option<String>,
+    pub program_day_id: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable)]
@@ -98,6 +108,7 @@ pub struct NewWorkoutSession {
     pub date: chrono::NaiveDate,
     pub start_time: chrono::NaiveDateTime,
     pub end_time: chrono::NaiveDateTime,
+    pub program_day_id: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, AsChangeset)]
@@ -123,6 +134,13 @@ pub struct WorkoutSet {
     pub weight: f64,
     pub reps: i32,
     pub performed_on: chrono::NaiveDate,
+    pub set_index: Option<i32>,
+    pub set_type: String,
+    pub rpe: Option<f64>,
+    pub rir: Option<i32>,
+    pub rest_seconds: Option<i32>,
+    pub tempo: Option<String>,
+    pub completed: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable)]
@@ -134,6 +152,13 @@ pub struct NewWorkoutSet {
     pub weight: f64,
     pub reps: i32,
     pub performed_on: chrono::NaiveDate,
+    pub set_index: Option<i32>,
+    pub set_type: String,
+    pub rpe: Option<f64>,
+    pub rir: Option<i32>,
+    pub rest_seconds: Option<i32>,
+    pub tempo: Option<String>,
+    pub completed: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, AsChangeset)]
@@ -141,6 +166,60 @@ pub struct NewWorkoutSet {
 pub struct UpdateWorkoutSet {
     pub weight: Option<f64>,
     pub reps: Option<i32>,
+    pub set_index: Option<i32>,
+    pub set_type: Option<String>,
+    pub rpe: Option<f64>,
+    pub rir: Option<i32>,
+    pub rest_seconds: Option<i32>,
+    pub tempo: Option<String>,
+    pub completed: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SetType{
+    Warmup,
+    #[default]
+    Working,
+    Drop,
+    Failure,
+    Amrap,
+}
+
+impl SetType{
+    pub fn as_str(&self) -> &'static str{
+        match self{
+            SetType::Warmup => "warmup",
+            SetType::Working => "working",
+            SetType::Drop => "drop",
+            SetType::Failure => "failure",
+            SetType::Amrap => "amrap",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sex{
+    Male,
+    Female,
+}
+
+impl Sex{
+    pub fn as_str(&self) -> &'static str{
+        match self{
+            Sex::Male => "male",
+            Sex::Female => "female",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self>{
+        match value{
+            "male" => Some(Sex::Male),
+            "female" => Some(Sex::Female),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Identifiable)]
@@ -149,6 +228,7 @@ pub struct CardioExercise {
     pub id: i32,
     pub name: String,
     pub user_id: Option<i32>,
+    pub met: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable)]
@@ -156,6 +236,8 @@ pub struct CardioExercise {
 pub struct NewCardioExercise<'a> {
     pub name: &'a str,
     pub user_id: i32,
+    /// Left to the column default when not given
+    pub met: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations)]
@@ -170,6 +252,13 @@ pub struct CardioLog {
     pub cardio_exercise_id: i32,
     pub duration_minutes: i32,
     pub performed_on: chrono::NaiveDate,
+    pub distance_m: Option<f64>,
+    pub elevation_gain_m: Option<f64>,
+    pub avg_heart_rate: Option<i32>,
+    pub max_heart_rate: Option<i32>,
+    pub calories: Option<i32>,
+    pub started_at: Option<chrono::NaiveDateTime>,
+    pub completed: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable)]
@@ -179,10 +268,422 @@ pub struct NewCardioLog {
     pub user_id: i32,
     pub cardio_exercise_id: i32,
     pub duration_minutes: i32,
+    /// Falls back to the column default (today) when not given
+    pub performed_on: Option<chrono::NaiveDate>,
+    pub distance_m: Option<f64>,
+    pub elevation_gain_m: Option<f64>,
+    pub avg_heart_rate: Option<i32>,
+    pub max_heart_rate: Option<i32>,
+    pub calories: Option<i32>,
+    pub started_at: Option<chrono::NaiveDateTime>,
+    pub completed: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, AsChangeset)]
 #[diesel(table_name = cardio_logs)]
 pub struct UpdateCardioLog {
     pub duration_minutes: Option<i32>,
-}
\ No newline at end of file
+    pub distance_m: Option<f64>,
+    pub elevation_gain_m: Option<f64>,
+    pub avg_heart_rate: Option<i32>,
+    pub max_heart_rate: Option<i32>,
+    pub calories: Option<i32>,
+    pub started_at: Option<chrono::NaiveDateTime>,
+    pub completed: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(CardioLog))]
+#[diesel(table_name = cardio_track_points)]
+pub struct TrackPoint {
+    pub id: i64,
+    pub cardio_log_id: i32,
+    pub seq: i32,
+    pub recorded_at: Option<chrono::NaiveDateTime>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub elevation_m: Option<f64>,
+    pub heart_rate: Option<i32>,
+    pub distance_m: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = cardio_track_points)]
+pub struct NewTrackPoint {
+    pub cardio_log_id: i32,
+    pub seq: i32,
+    pub recorded_at: Option<chrono::NaiveDateTime>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub elevation_m: Option<f64>,
+    pub heart_rate: Option<i32>,
+    pub distance_m: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations, Selectable)]
+#[diesel(belongs_to(User))]
+#[diesel(belongs_to(Variation))]
+#[diesel(table_name = personal_records)]
+pub struct PersonalRecord {
+    pub id: i32,
+    pub user_id: i32,
+    pub variation_id: i32,
+    pub set_id: Option<i32>,
+    pub workout_session_id: Option<i32>,
+    pub record_type: String,
+    pub value: f64,
+    pub weight: f64,
+    pub reps: i32,
+    pub achieved_on: chrono::NaiveDate,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = personal_records)]
+pub struct NewPersonalRecord {
+    pub user_id: i32,
+    pub variation_id: i32,
+    pub set_id: Option<i32>,
+    pub workout_session_id: Option<i32>,
+    pub record_type: String,
+    pub value: f64,
+    pub weight: f64,
+    pub reps: i32,
+    pub achieved_on: chrono::NaiveDate,
+}
+
+#[derive(Debug, Serialize, Deserialize, AsChangeset)]
+#[diesel(table_name = personal_records)]
+pub struct UpdatePersonalRecord {
+    pub set_id: Option<i32>,
+    pub value: f64,
+    pub weight: f64,
+    pub reps: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations, Selectable)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = refresh_tokens)]
+pub struct RefreshToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub family_id: String,
+    pub token_hash: String,
+    pub expires_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+    pub used_at: Option<chrono::NaiveDateTime>,
+    pub revoked_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = refresh_tokens)]
+pub struct NewRefreshToken {
+    pub user_id: i32,
+    pub family_id: String,
+    pub token_hash: String,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations, Selectable)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = password_reset_tokens)]
+pub struct PasswordResetToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expires_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+    pub used_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = password_reset_tokens)]
+pub struct NewPasswordResetToken {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations, Selectable)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = api_tokens)]
+pub struct ApiToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub scope: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub revoked_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = api_tokens)]
+pub struct NewApiToken<'a> {
+    pub user_id: i32,
+    pub name: &'a str,
+    pub token_hash: String,
+    pub scope: &'a str,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = routines)]
+pub struct Routine {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub notes: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = routines)]
+pub struct NewRoutine {
+    pub user_id: i32,
+    pub name: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, AsChangeset)]
+#[diesel(table_name = routines)]
+#[diesel(treat_none_as_null = true)]
+pub struct UpdateRoutine {
+    pub name: String,
+    pub notes: Option<String>,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(Routine))]
+#[diesel(table_name = routine_exercises)]
+pub struct RoutineExercise {
+    pub id: i32,
+    pub routine_id: i32,
+    pub position: i32,
+    pub variation_id: Option<i32>,
+    pub cardio_exercise_id: Option<i32>,
+    pub target_sets: i32,
+    pub reps_min: Option<i32>,
+    pub reps_max: Option<i32>,
+    pub target_weight: Option<f64>,
+    pub rest_seconds: Option<i32>,
+    pub target_duration_minutes: Option<i32>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = routine_exercises)]
+pub struct NewRoutineExercise {
+    pub routine_id: i32,
+    pub position: i32,
+    pub variation_id: Option<i32>,
+    pub cardio_exercise_id: Option<i32>,
+    pub target_sets: i32,
+    pub reps_min: Option<i32>,
+    pub reps_max: Option<i32>,
+    pub target_weight: Option<f64>,
+    pub rest_seconds: Option<i32>,
+    pub target_duration_minutes: Option<i32>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = programs)]
+pub struct Program {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub notes: Option<String>,
+    pub deload_percent: f64,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = programs)]
+pub struct NewProgram {
+    pub user_id: i32,
+    pub name: String,
+    pub notes: Option<String>,
+    pub deload_percent: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, AsChangeset)]
+#[diesel(table_name = programs)]
+#[diesel(treat_none_as_null = true)]
+pub struct UpdateProgram {
+    pub name: String,
+    pub notes: Option<String>,
+    pub deload_percent: f64,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(Program))]
+#[diesel(table_name = program_weeks)]
+pub struct ProgramWeek {
+    pub id: i32,
+    pub program_id: i32,
+    pub week_number: i32,
+    pub deload: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = program_weeks)]
+pub struct NewProgramWeek {
+    pub program_id: i32,
+    pub week_number: i32,
+    pub deload: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(ProgramWeek))]
+#[diesel(table_name = program_days)]
+pub struct ProgramDay {
+    pub id: i32,
+    pub program_week_id: i32,
+    pub day_number: i32,
+    pub routine_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = program_days)]
+pub struct NewProgramDay {
+    pub program_week_id: i32,
+    pub day_number: i32,
+    pub routine_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(Program))]
+#[diesel(table_name = progression_rules)]
+pub struct ProgressionRule {
+    pub id: i32,
+    pub program_id: i32,
+    pub variation_id: Option<i32>,
+    pub rule_type: String,
+    pub increment: Option<f64>,
+    pub percent: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = progression_rules)]
+pub struct NewProgressionRule {
+    pub program_id: i32,
+    pub variation_id: Option<i32>,
+    pub rule_type: String,
+    pub increment: Option<f64>,
+    pub percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressionType{
+    /// Add `increment` once every working set hit the target reps
+    Linear,
+    /// Climb the rep range at a fixed weight, add `increment` and drop to the bottom once the top is reached
+    Double,
+    /// Prescribe `percent` of the best estimated 1RM
+    E1rm,
+}
+
+impl ProgressionType{
+    pub fn as_str(&self) -> &'static str{
+        match self{
+            ProgressionType::Linear => "linear",
+            ProgressionType::Double => "double",
+            ProgressionType::E1rm => "e1rm",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self>{
+        match value{
+            "linear" => Some(ProgressionType::Linear),
+            "double" => Some(ProgressionType::Double),
+            "e1rm" => Some(ProgressionType::E1rm),
+            _ => None,
+        }
+    }
+}
+
+/// Weight is stored in kg and girths in cm, like everything else.
+#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = body_measurements)]
+pub struct BodyMeasurement {
+    pub id: i32,
+    pub user_id: i32,
+    pub measured_on: NaiveDate,
+    pub weight: Option<f64>,
+    pub body_fat_percent: Option<f64>,
+    pub neck: Option<f64>,
+    pub chest: Option<f64>,
+    pub waist: Option<f64>,
+    pub hips: Option<f64>,
+    pub arms: Option<f64>,
+    pub thighs: Option<f64>,
+    pub notes: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = body_measurements)]
+pub struct NewBodyMeasurement {
+    pub user_id: i32,
+    pub measured_on: NaiveDate,
+    pub weight: Option<f64>,
+    pub body_fat_percent: Option<f64>,
+    pub neck: Option<f64>,
+    pub chest: Option<f64>,
+    pub waist: Option<f64>,
+    pub hips: Option<f64>,
+    pub arms: Option<f64>,
+    pub thighs: Option<f64>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, AsChangeset)]
+#[diesel(table_name = body_measurements)]
+pub struct UpdateBodyMeasurement {
+    pub measured_on: Option<NaiveDate>,
+    pub weight: Option<f64>,
+    pub body_fat_percent: Option<f64>,
+    pub neck: Option<f64>,
+    pub chest: Option<f64>,
+    pub waist: Option<f64>,
+    pub hips: Option<f64>,
+    pub arms: Option<f64>,
+    pub thighs: Option<f64>,
+    pub notes: Option<String>,
+}
+
+/// Audit record of an erased account, kept after the user row and everything it owned is gone.
+#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = account_deletions)]
+pub struct AccountDeletion {
+    pub id: i32,
+    /// Random reference given to the user as a receipt, unrelated to their former id
+    pub deletion_id: String,
+    pub deleted_at: chrono::NaiveDateTime,
+    pub exported: bool,
+    pub sessions: i32,
+    pub sets: i32,
+    pub cardio_logs: i32,
+    pub tokens_revoked: i32,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = account_deletions)]
+pub struct NewAccountDeletion {
+    pub deletion_id: String,
+    pub exported: bool,
+    pub sessions: i32,
+    pub sets: i32,
+    pub cardio_logs: i32,
+    pub tokens_revoked: i32,
+}
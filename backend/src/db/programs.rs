@@ -0,0 +1,358 @@
+use std::sync::Arc;
+use crate::db::database::DBOperations;
+use crate::error::{ApiError, ApiResult};
+use diesel_async::{pooled_connection::deadpool::Pool, scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection};
+use crate::{db::model::{NewProgram, NewProgramDay, NewProgramWeek, NewProgressionRule, Program, ProgramDay, ProgramWeek, ProgressionRule, UpdateProgram}, schema::fittrack::{programs, program_weeks, program_days, progression_rules, workout_sessions}};
+use diesel_async::RunQueryDsl;
+use diesel::{BelongingToDsl, ExpressionMethods, GroupedBy, OptionalExtension, QueryDsl};
+
+/// A program with its weeks (each holding its days, in order) and progression rules.
+#[derive(Debug)]
+pub struct ProgramPlan{
+    pub program: Program,
+    pub weeks: Vec<(ProgramWeek, Vec<ProgramDay>)>,
+    pub rules: Vec<ProgressionRule>,
+}
+
+type PlanRows = (Vec<(ProgramWeek, Vec<ProgramDay>)>, Vec<ProgressionRule>);
+
+pub struct ProgramDB{
+    database: Arc<DBOperations>,
+    pool: Option<Pool<AsyncPgConnection>>
+}
+
+/// Inserts the weeks, days and rules of a program whose row already exists. Days are matched
+/// to their week by week number, so callers leave `program_week_id` at 0.
+async fn insert_plan(conn: &mut AsyncPgConnection, program_id: i32, weeks: Vec<(NewProgramWeek, Vec<NewProgramDay>)>, rules: Vec<NewProgressionRule>) -> Result<PlanRows, diesel::result::Error>{
+    let (mut new_weeks, day_lists): (Vec<NewProgramWeek>, Vec<Vec<NewProgramDay>>) = weeks.into_iter().unzip();
+    for week in new_weeks.iter_mut(){
+        week.program_id = program_id;
+    }
+    let inserted_weeks = diesel::insert_into(program_weeks::table)
+        .values(&new_weeks)
+        .get_results::<ProgramWeek>(conn)
+        .await?;
+
+    let mut new_days = Vec::new();
+    for (week, days) in new_weeks.iter().zip(day_lists){
+        let week_id = match inserted_weeks.iter().find(|w| w.week_number == week.week_number){
+            Some(w) => w.id,
+            None => return Err(diesel::result::Error::NotFound),
+        };
+        for mut day in days{
+            day.program_week_id = week_id;
+            new_days.push(day);
+        }
+    }
+    let mut inserted_days = diesel::insert_into(program_days::table)
+        .values(&new_days)
+        .get_results::<ProgramDay>(conn)
+        .await?;
+    inserted_days.sort_by_key(|d| d.day_number);
+    let inserted_rules = insert_rules(conn, program_id, rules).await?;
+
+    let grouped = inserted_days.grouped_by(&inserted_weeks);
+    Ok((inserted_weeks.into_iter().zip(grouped).collect(), inserted_rules))
+}
+
+async fn insert_rules(conn: &mut AsyncPgConnection, program_id: i32, mut rules: Vec<NewProgressionRule>) -> Result<Vec<ProgressionRule>, diesel::result::Error>{
+    for rule in rules.iter_mut(){
+        rule.program_id = program_id;
+    }
+    if rules.is_empty() {
+        return Ok(Vec::new());
+    }
+    diesel::insert_into(progression_rules::table)
+        .values(&rules)
+        .get_results::<ProgressionRule>(conn)
+        .await
+}
+
+/// Brings the weeks and days of an existing program in line with `weeks` without recreating them.
+/// Weeks are matched by week number and days by day number within their week, so sessions started
+/// from a day keep their link; only weeks and days dropped from the plan are deleted. Rules aren't
+/// referenced by anything and are simply replaced.
+async fn sync_plan(conn: &mut AsyncPgConnection, program_id: i32, weeks: Vec<(NewProgramWeek, Vec<NewProgramDay>)>, rules: Vec<NewProgressionRule>) -> Result<PlanRows, diesel::result::Error>{
+    let existing_weeks: Vec<ProgramWeek> = program_weeks::table
+        .filter(program_weeks::program_id.eq(program_id))
+        .get_results(conn)
+        .await?;
+    let removed_weeks: Vec<i32> = existing_weeks.iter()
+        .filter(|w| !weeks.iter().any(|(n, _)| n.week_number == w.week_number))
+        .map(|w| w.id)
+        .collect();
+    if !removed_weeks.is_empty() {
+        diesel::delete(program_weeks::table.filter(program_weeks::id.eq_any(removed_weeks)))
+            .execute(conn)
+            .await?;
+    }
+
+    let mut synced = Vec::with_capacity(weeks.len());
+    for (mut week, days) in weeks.into_iter(){
+        let saved_week: ProgramWeek = match existing_weeks.iter().find(|w| w.week_number == week.week_number){
+            Some(w) => diesel::update(program_weeks::table.find(w.id))
+                .set(program_weeks::deload.eq(week.deload))
+                .get_result(conn)
+                .await?,
+            None => {
+                week.program_id = program_id;
+                diesel::insert_into(program_weeks::table)
+                    .values(&week)
+                    .get_result(conn)
+                    .await?
+            }
+        };
+
+        let existing_days: Vec<ProgramDay> = program_days::table
+            .filter(program_days::program_week_id.eq(saved_week.id))
+            .get_results(conn)
+            .await?;
+        let removed_days: Vec<i32> = existing_days.iter()
+            .filter(|d| !days.iter().any(|n| n.day_number == d.day_number))
+            .map(|d| d.id)
+            .collect();
+        if !removed_days.is_empty() {
+            diesel::delete(program_days::table.filter(program_days::id.eq_any(removed_days)))
+                .execute(conn)
+                .await?;
+        }
+
+        let mut saved_days = Vec::with_capacity(days.len());
+        for mut day in days.into_iter(){
+            let saved_day: ProgramDay = match existing_days.iter().find(|d| d.day_number == day.day_number){
+                Some(d) => diesel::update(program_days::table.find(d.id))
+                    .set(program_days::routine_id.eq(day.routine_id))
+                    .get_result(conn)
+                    .await?,
+                None => {
+                    day.program_week_id = saved_week.id;
+                    diesel::insert_into(program_days::table)
+                        .values(&day)
+                        .get_result(conn)
+                        .await?
+                }
+            };
+            saved_days.push(saved_day);
+        }
+        saved_days.sort_by_key(|d| d.day_number);
+        synced.push((saved_week, saved_days));
+    }
+    synced.sort_by_key(|(w, _)| w.week_number);
+
+    diesel::delete(progression_rules::table)
+        .filter(progression_rules::program_id.eq(program_id))
+        .execute(conn)
+        .await?;
+    let inserted_rules = insert_rules(conn, program_id, rules).await?;
+    Ok((synced, inserted_rules))
+}
+
+impl ProgramDB{
+    pub fn new(database: Arc<DBOperations>) -> Self{
+        ProgramDB { database, pool: None }
+    }
+
+    pub async fn init(&mut self) -> ApiResult<()>{
+        let pool = match self.database.get_pool().await{
+            Ok(p) => p,
+            Err(err) => return Err(err.into())
+        };
+
+        self.pool = Some(pool);
+        Ok(())
+    }
+
+    pub async fn get_programs(&self, user_id: i32) -> ApiResult<Vec<Program>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match programs::table
+            .filter(programs::user_id.eq(user_id))
+            .order(programs::name.asc())
+            .get_results(&mut conn)
+            .await{
+                Ok(p) => Ok(p),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    pub async fn get_program(&self, user_id: i32, program_id: i32) -> ApiResult<Option<ProgramPlan>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let program: Program = match programs::table
+            .filter(programs::id.eq(program_id))
+            .filter(programs::user_id.eq(user_id))
+            .first(&mut conn)
+            .await
+            .optional(){
+                Ok(Some(p)) => p,
+                Ok(None) => return Ok(None),
+                Err(err) => return Err(err.into())
+            };
+        let weeks: Vec<ProgramWeek> = match ProgramWeek::belonging_to(&program)
+            .order(program_weeks::week_number.asc())
+            .get_results(&mut conn)
+            .await{
+                Ok(w) => w,
+                Err(err) => return Err(err.into())
+            };
+        let days: Vec<ProgramDay> = match ProgramDay::belonging_to(&weeks)
+            .order(program_days::day_number.asc())
+            .get_results(&mut conn)
+            .await{
+                Ok(d) => d,
+                Err(err) => return Err(err.into())
+            };
+        let rules: Vec<ProgressionRule> = match ProgressionRule::belonging_to(&program)
+            .order(progression_rules::id.asc())
+            .get_results(&mut conn)
+            .await{
+                Ok(r) => r,
+                Err(err) => return Err(err.into())
+            };
+
+        let grouped = days.grouped_by(&weeks);
+        Ok(Some(ProgramPlan{
+            program,
+            weeks: weeks.into_iter().zip(grouped).collect(),
+            rules,
+        }))
+    }
+
+    pub async fn add_program(&self, program: NewProgram, weeks: Vec<(NewProgramWeek, Vec<NewProgramDay>)>, rules: Vec<NewProgressionRule>) -> ApiResult<ProgramPlan>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let inserted = diesel::insert_into(programs::table)
+                .values(&program)
+                .get_result::<Program>(conn)
+                .await?;
+            let (weeks, rules) = insert_plan(conn, inserted.id, weeks, rules).await?;
+            Ok(ProgramPlan{ program: inserted, weeks, rules })
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(r) => Ok(r),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Updates the program and its schedule in place. Sessions keep their link to days that are
+    /// still in the plan, so the program carries on where it was and deload weeks stay known.
+    pub async fn replace_program(&self, user_id: i32, program_id: i32, data: UpdateProgram, weeks: Vec<(NewProgramWeek, Vec<NewProgramDay>)>, rules: Vec<NewProgressionRule>) -> ApiResult<ProgramPlan>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let updated = diesel::update(programs::table)
+                .filter(programs::id.eq(program_id))
+                .filter(programs::user_id.eq(user_id))
+                .set(&data)
+                .get_result::<Program>(conn)
+                .await?;
+            let (weeks, rules) = sync_plan(conn, updated.id, weeks, rules).await?;
+            Ok(ProgramPlan{ program: updated, weeks, rules })
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(r) => Ok(r),
+            Err(diesel::result::Error::NotFound) => Err(ApiError::NotFound("Program not found".to_string())),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    pub async fn delete_program(&self, user_id: i32, program_id: i32) -> ApiResult<()>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let count = diesel::delete(programs::table)
+            .filter(programs::id.eq(program_id))
+            .filter(programs::user_id.eq(user_id))
+            .execute(&mut conn)
+            .await?;
+
+        if count == 0 {
+            return Err(ApiError::NotFound("Program not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// The program day of the user's most recent session started from one of `day_ids`.
+    pub async fn get_last_program_day(&self, user_id: i32, day_ids: Vec<i32>) -> ApiResult<Option<i32>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match workout_sessions::table
+            .filter(workout_sessions::user_id.eq(user_id))
+            .filter(workout_sessions::program_day_id.eq_any(day_ids))
+            .order((workout_sessions::start_time.desc(), workout_sessions::id.desc()))
+            .select(workout_sessions::program_day_id)
+            .first::<Option<i32>>(&mut conn)
+            .await
+            .optional(){
+                Ok(day) => Ok(day.flatten()),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    /// Sessions the user started from a deload week of any of their programs.
+    pub async fn get_deload_session_ids(&self, user_id: i32) -> ApiResult<Vec<i32>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match workout_sessions::table
+            .inner_join(program_days::table.inner_join(program_weeks::table))
+            .filter(workout_sessions::user_id.eq(user_id))
+            .filter(program_weeks::deload.eq(true))
+            .select(workout_sessions::id)
+            .load::<i32>(&mut conn)
+            .await{
+                Ok(ids) => Ok(ids),
+                Err(err) => Err(err.into())
+            }
+    }
+}
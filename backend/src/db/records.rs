@@ -0,0 +1,129 @@
+use std::sync::Arc;
+use crate::error::{ApiError, ApiResult};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::{pooled_connection::deadpool::Pool, scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use crate::{db::{database::DBOperations, model::{NewPersonalRecord, PersonalRecord, UpdatePersonalRecord}}, schema::fittrack::personal_records};
+
+pub struct RecordsDB{
+    database: Arc<DBOperations>,
+    pool: Option<Pool<AsyncPgConnection>>,
+}
+
+impl RecordsDB{
+    pub fn new(database: Arc<DBOperations>) -> Self{
+        RecordsDB {
+            database,
+            pool: None
+        }
+    }
+
+    pub async fn init(&mut self) -> ApiResult<()>{
+        let pool = match self.database.get_pool().await{
+            Ok(p) => p,
+            Err(err) => return Err(err.into())
+        };
+        self.pool = Some(pool);
+        Ok(())
+    }
+
+    pub async fn get_records(&self, user_id: i32, variation_id: Option<i32>) -> ApiResult<Vec<PersonalRecord>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let mut query = personal_records::table.into_boxed();
+        query = query.filter(personal_records::user_id.eq(user_id));
+        if let Some(var_id) = variation_id {
+            query = query.filter(personal_records::variation_id.eq(var_id));
+        }
+
+        let records = match query
+            .order((personal_records::achieved_on.desc(), personal_records::id.desc()))
+            .get_results(&mut conn)
+            .await{
+                Ok(r) => r,
+                Err(err) => return Err(err.into())
+            };
+        Ok(records)
+    }
+
+    pub async fn add_records(&self, records: Vec<NewPersonalRecord>) -> ApiResult<Vec<PersonalRecord>>{
+        if records.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let inserted = match diesel::insert_into(personal_records::table)
+            .values(&records)
+            .get_results(&mut conn)
+            .await{
+                Ok(r) => r,
+                Err(err) => return Err(err.into())
+            };
+        Ok(inserted)
+    }
+
+    pub async fn update_record(&self, record_id: i32, data: UpdatePersonalRecord) -> ApiResult<PersonalRecord>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let updated = match diesel::update(personal_records::table)
+            .filter(personal_records::id.eq(record_id))
+            .set(data)
+            .get_result(&mut conn)
+            .await{
+                Ok(r) => r,
+                Err(err) => return Err(err.into())
+            };
+        Ok(updated)
+    }
+
+    pub async fn replace_records(&self, user_id: i32, variation_id: i32, records: Vec<NewPersonalRecord>) -> ApiResult<()>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            diesel::delete(personal_records::table)
+                .filter(personal_records::user_id.eq(user_id))
+                .filter(personal_records::variation_id.eq(variation_id))
+                .execute(conn)
+                .await?;
+            if !records.is_empty() {
+                diesel::insert_into(personal_records::table)
+                    .values(&records)
+                    .execute(conn)
+                    .await?;
+            }
+            Ok(())
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => Err(err.into())
+        }
+    }
+}
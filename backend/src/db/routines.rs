@@ -0,0 +1,186 @@
+use std::sync::Arc;
+use crate::db::database::DBOperations;
+use crate::error::{ApiError, ApiResult};
+use diesel_async::{pooled_connection::deadpool::Pool, scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection};
+use crate::{db::model::{NewRoutine, NewRoutineExercise, Routine, RoutineExercise, UpdateRoutine}, schema::fittrack::{routines, routine_exercises}};
+use diesel_async::RunQueryDsl;
+use diesel::{BelongingToDsl, ExpressionMethods, GroupedBy, OptionalExtension, QueryDsl};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+
+pub struct RoutineDB{
+    database: Arc<DBOperations>,
+    pool: Option<Pool<AsyncPgConnection>>
+}
+
+impl RoutineDB{
+    pub fn new(database: Arc<DBOperations>) -> Self{
+        RoutineDB { database, pool: None }
+    }
+
+    pub async fn init(&mut self) -> ApiResult<()>{
+        let pool = match self.database.get_pool().await{
+            Ok(p) => p,
+            Err(err) => return Err(err.into())
+        };
+
+        self.pool = Some(pool);
+        Ok(())
+    }
+
+    pub async fn get_routines(&self, user_id: i32) -> ApiResult<Vec<(Routine, Vec<RoutineExercise>)>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let user_routines: Vec<Routine> = match routines::table
+            .filter(routines::user_id.eq(user_id))
+            .order(routines::name.asc())
+            .get_results(&mut conn)
+            .await{
+                Ok(r) => r,
+                Err(err) => return Err(err.into())
+            };
+        let exercises: Vec<RoutineExercise> = match RoutineExercise::belonging_to(&user_routines)
+            .order(routine_exercises::position.asc())
+            .get_results(&mut conn)
+            .await{
+                Ok(e) => e,
+                Err(err) => return Err(err.into())
+            };
+
+        let grouped = exercises.grouped_by(&user_routines);
+        Ok(user_routines.into_iter().zip(grouped).collect())
+    }
+
+    pub async fn get_routine(&self, user_id: i32, routine_id: i32) -> ApiResult<Option<(Routine, Vec<RoutineExercise>)>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let routine: Routine = match routines::table
+            .filter(routines::id.eq(routine_id))
+            .filter(routines::user_id.eq(user_id))
+            .first(&mut conn)
+            .await
+            .optional(){
+                Ok(Some(r)) => r,
+                Ok(None) => return Ok(None),
+                Err(err) => return Err(err.into())
+            };
+        match routine_exercises::table
+            .filter(routine_exercises::routine_id.eq(routine.id))
+            .order(routine_exercises::position.asc())
+            .get_results(&mut conn)
+            .await{
+                Ok(exercises) => Ok(Some((routine, exercises))),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    pub async fn add_routine(&self, routine: NewRoutine, mut exercises: Vec<NewRoutineExercise>) -> ApiResult<(Routine, Vec<RoutineExercise>)>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let inserted = diesel::insert_into(routines::table)
+                .values(&routine)
+                .get_result::<Routine>(conn)
+                .await?;
+            for exercise in exercises.iter_mut(){
+                exercise.routine_id = inserted.id;
+            }
+            let inserted_exercises = diesel::insert_into(routine_exercises::table)
+                .values(&exercises)
+                .get_results::<RoutineExercise>(conn)
+                .await?;
+            Ok((inserted, inserted_exercises))
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(r) => Ok(r),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Renames the routine and swaps its exercise list for the new one in a single transaction.
+    pub async fn replace_routine(&self, user_id: i32, routine_id: i32, data: UpdateRoutine, mut exercises: Vec<NewRoutineExercise>) -> ApiResult<(Routine, Vec<RoutineExercise>)>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let updated = diesel::update(routines::table)
+                .filter(routines::id.eq(routine_id))
+                .filter(routines::user_id.eq(user_id))
+                .set(&data)
+                .get_result::<Routine>(conn)
+                .await?;
+            diesel::delete(routine_exercises::table)
+                .filter(routine_exercises::routine_id.eq(updated.id))
+                .execute(conn)
+                .await?;
+            for exercise in exercises.iter_mut(){
+                exercise.routine_id = updated.id;
+            }
+            let inserted_exercises = diesel::insert_into(routine_exercises::table)
+                .values(&exercises)
+                .get_results::<RoutineExercise>(conn)
+                .await?;
+            Ok((updated, inserted_exercises))
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(r) => Ok(r),
+            Err(diesel::result::Error::NotFound) => Err(ApiError::NotFound("Routine not found".to_string())),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    pub async fn delete_routine(&self, user_id: i32, routine_id: i32) -> ApiResult<()>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let count = match diesel::delete(routines::table)
+            .filter(routines::id.eq(routine_id))
+            .filter(routines::user_id.eq(user_id))
+            .execute(&mut conn)
+            .await{
+                Ok(c) => c,
+                Err(DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) =>
+                    return Err(ApiError::Conflict("Routine is used by a program, remove it from the program first".to_string())),
+                Err(err) => return Err(err.into())
+            };
+
+        if count == 0 {
+            return Err(ApiError::NotFound("Routine not found".to_string()));
+        }
+        Ok(())
+    }
+}
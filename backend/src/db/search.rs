@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use chrono::NaiveDate;
+use serde::Serialize;
+use crate::db::database::DBOperations;
+use crate::error::{ApiError, ApiResult};
+use diesel::{sql_types::{BigInt, Date, Float8, Int4, Nullable, Text}, QueryableByName};
+use diesel_async::{pooled_connection::deadpool::Pool, AsyncPgConnection, RunQueryDsl};
+
+/// Matches from every searchable table, best first. The `search_vector` columns come from the
+/// full_text_search migration. The `hits` CTE ranks and limits the page, so headlines are only built
+/// for the rows on it. Inside it the tsquery is written out in each branch instead of coming from a
+/// CTE of its own: that CTE is referenced more than once, so Postgres would materialise it and the
+/// `@@` conditions could no longer use the GIN indexes.
+const SEARCH_QUERY: &str = "
+    WITH hits AS (
+        SELECT 'session' AS kind, s.id, s.title::text AS title, s.notes AS body, s.date,
+               ts_rank(s.search_vector, websearch_to_tsquery('english', $1))::float8 AS rank
+        FROM fittrack.workout_sessions s
+        WHERE s.user_id = $2 AND s.search_vector @@ websearch_to_tsquery('english', $1)
+        UNION ALL
+        SELECT 'variation', v.id, v.name::text, v.description, NULL::date,
+               ts_rank(v.search_vector, websearch_to_tsquery('english', $1))::float8
+        FROM fittrack.variations v
+        WHERE (v.user_id = $2 OR v.user_id = 0) AND v.search_vector @@ websearch_to_tsquery('english', $1)
+        UNION ALL
+        SELECT 'cardio_exercise', c.id, c.name::text, NULL::text, NULL::date,
+               ts_rank(c.search_vector, websearch_to_tsquery('english', $1))::float8
+        FROM fittrack.cardio_exercises c
+        WHERE (c.user_id = $2 OR c.user_id = 0) AND c.search_vector @@ websearch_to_tsquery('english', $1)
+        ORDER BY rank DESC, kind, id
+        LIMIT $3
+    )
+    SELECT kind, id, title, date, rank,
+           NULLIF(ts_headline('english', coalesce(body, ''), websearch_to_tsquery('english', $1), 'MaxFragments=1, MaxWords=20, MinWords=5, StartSel=«, StopSel=»'), '') AS snippet
+    FROM hits
+    ORDER BY rank DESC, kind, id";
+
+/// A search result. `kind` is `session`, `variation` or `cardio_exercise`; `snippet` is the
+/// matching part of the notes or description as plain text, unescaped, with the terms wrapped in
+/// `«` and `»` for the client to highlight. It must never be rendered as HTML.
+#[derive(Debug, Serialize, QueryableByName)]
+pub struct SearchHit{
+    #[diesel(sql_type = Text)]
+    pub kind: String,
+    #[diesel(sql_type = Int4)]
+    pub id: i32,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub title: Option<String>,
+    #[diesel(sql_type = Nullable<Date>)]
+    pub date: Option<NaiveDate>,
+    #[diesel(sql_type = Float8)]
+    pub rank: f64,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub snippet: Option<String>,
+}
+
+pub struct SearchDB{
+    database: Arc<DBOperations>,
+    pool: Option<Pool<AsyncPgConnection>>
+}
+
+impl SearchDB{
+    pub fn new(database: Arc<DBOperations>) -> Self{
+        SearchDB { database, pool: None }
+    }
+
+    pub async fn init(&mut self) -> ApiResult<()>{
+        let pool = match self.database.get_pool().await{
+            Ok(p) => p,
+            Err(err) => return Err(err.into())
+        };
+
+        self.pool = Some(pool);
+        Ok(())
+    }
+
+    /// Searches the user's sessions and their own plus the shared catalogue's exercises.
+    /// `terms` uses web search syntax: quoted phrases, `or` and `-` to exclude.
+    pub async fn search(&self, user_id: i32, terms: &str, limit: i64) -> ApiResult<Vec<SearchHit>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match diesel::sql_query(SEARCH_QUERY)
+            .bind::<Text, _>(terms)
+            .bind::<Int4, _>(user_id)
+            .bind::<BigInt, _>(limit)
+            .load::<SearchHit>(&mut conn)
+            .await{
+                Ok(hits) => Ok(hits),
+                Err(err) => Err(err.into())
+            }
+    }
+}
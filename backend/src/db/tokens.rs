@@ -0,0 +1,311 @@
+use std::sync::Arc;
+use crate::error::{ApiError, ApiResult};
+use chrono::Utc;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::{pooled_connection::deadpool::Pool, scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use crate::{db::{database::DBOperations, model::{ApiToken, NewApiToken, NewPasswordResetToken, NewRefreshToken, PasswordResetToken, RefreshToken}}, schema::fittrack::{api_tokens, password_reset_tokens, refresh_tokens, users}};
+
+pub struct TokenDB{
+    database: Arc<DBOperations>,
+    pool: Option<Pool<AsyncPgConnection>>,
+}
+
+impl TokenDB{
+    pub fn new(database: Arc<DBOperations>) -> Self{
+        TokenDB {
+            database,
+            pool: None
+        }
+    }
+
+    pub async fn init(&mut self) -> ApiResult<()>{
+        let pool = match self.database.get_pool().await{
+            Ok(p) => p,
+            Err(err) => return Err(err.into())
+        };
+        self.pool = Some(pool);
+        Ok(())
+    }
+
+    pub async fn add_refresh_token(&self, token: NewRefreshToken) -> ApiResult<RefreshToken>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let inserted = match diesel::insert_into(refresh_tokens::table)
+            .values(&token)
+            .get_result(&mut conn)
+            .await{
+                Ok(t) => t,
+                Err(err) => return Err(err.into())
+            };
+        Ok(inserted)
+    }
+
+    pub async fn get_refresh_token(&self, token_hash: &str) -> ApiResult<Option<RefreshToken>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let token = match refresh_tokens::table
+            .filter(refresh_tokens::token_hash.eq(token_hash))
+            .first::<RefreshToken>(&mut conn)
+            .await
+            .optional(){
+                Ok(t) => t,
+                Err(err) => return Err(err.into())
+            };
+        Ok(token)
+    }
+
+    /// Marks the presented token as used and issues its successor in one transaction.
+    /// Returns `None` when the token was consumed concurrently, which callers treat as reuse.
+    pub async fn rotate_refresh_token(&self, token_id: i32, next: NewRefreshToken) -> ApiResult<Option<RefreshToken>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let now = Utc::now().naive_utc();
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let consumed = diesel::update(refresh_tokens::table)
+                .filter(refresh_tokens::id.eq(token_id))
+                .filter(refresh_tokens::used_at.is_null())
+                .filter(refresh_tokens::revoked_at.is_null())
+                .set(refresh_tokens::used_at.eq(now))
+                .execute(conn)
+                .await?;
+            if consumed == 0 {
+                return Ok(None);
+            }
+            let inserted = diesel::insert_into(refresh_tokens::table)
+                .values(&next)
+                .get_result::<RefreshToken>(conn)
+                .await?;
+            Ok(Some(inserted))
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(t) => Ok(t),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    pub async fn revoke_family(&self, family_id: &str) -> ApiResult<usize>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let revoked = match diesel::update(refresh_tokens::table)
+            .filter(refresh_tokens::family_id.eq(family_id))
+            .filter(refresh_tokens::revoked_at.is_null())
+            .set(refresh_tokens::revoked_at.eq(Utc::now().naive_utc()))
+            .execute(&mut conn)
+            .await{
+                Ok(count) => count,
+                Err(err) => return Err(err.into())
+            };
+        Ok(revoked)
+    }
+
+    pub async fn revoke_user_refresh_tokens(&self, user_id: i32) -> ApiResult<usize>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let revoked = match diesel::update(refresh_tokens::table)
+            .filter(refresh_tokens::user_id.eq(user_id))
+            .filter(refresh_tokens::revoked_at.is_null())
+            .set(refresh_tokens::revoked_at.eq(Utc::now().naive_utc()))
+            .execute(&mut conn)
+            .await{
+                Ok(count) => count,
+                Err(err) => return Err(err.into())
+            };
+        Ok(revoked)
+    }
+
+    /// Stores a new reset token and retires any earlier unused ones for the same user.
+    pub async fn add_reset_token(&self, token: NewPasswordResetToken) -> ApiResult<PasswordResetToken>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let now = Utc::now().naive_utc();
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            diesel::update(password_reset_tokens::table)
+                .filter(password_reset_tokens::user_id.eq(token.user_id))
+                .filter(password_reset_tokens::used_at.is_null())
+                .set(password_reset_tokens::used_at.eq(now))
+                .execute(conn)
+                .await?;
+            diesel::insert_into(password_reset_tokens::table)
+                .values(&token)
+                .get_result::<PasswordResetToken>(conn)
+                .await
+        }.scope_boxed()).await;
+
+        match result {
+            Ok(t) => Ok(t),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Burns an unexpired, unused reset token and returns the user it belongs to.
+    pub async fn consume_reset_token(&self, token_hash: &str) -> ApiResult<Option<i32>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let now = Utc::now().naive_utc();
+        let user_id = match diesel::update(password_reset_tokens::table)
+            .filter(password_reset_tokens::token_hash.eq(token_hash))
+            .filter(password_reset_tokens::used_at.is_null())
+            .filter(password_reset_tokens::expires_at.gt(now))
+            .set(password_reset_tokens::used_at.eq(now))
+            .returning(password_reset_tokens::user_id)
+            .get_result::<i32>(&mut conn)
+            .await
+            .optional(){
+                Ok(id) => id,
+                Err(err) => return Err(err.into())
+            };
+        Ok(user_id)
+    }
+
+    pub async fn add_api_token(&self, token: NewApiToken<'_>) -> ApiResult<ApiToken>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let inserted = match diesel::insert_into(api_tokens::table)
+            .values(&token)
+            .get_result(&mut conn)
+            .await{
+                Ok(t) => t,
+                Err(err) => return Err(err.into())
+            };
+        Ok(inserted)
+    }
+
+    pub async fn get_api_tokens(&self, user_id: i32) -> ApiResult<Vec<ApiToken>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let tokens = match api_tokens::table
+            .filter(api_tokens::user_id.eq(user_id))
+            .order(api_tokens::created_at.desc())
+            .get_results(&mut conn)
+            .await{
+                Ok(t) => t,
+                Err(err) => return Err(err.into())
+            };
+        Ok(tokens)
+    }
+
+    pub async fn revoke_api_token(&self, user_id: i32, token_id: i32) -> ApiResult<()>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let count = match diesel::update(api_tokens::table)
+            .filter(api_tokens::id.eq(token_id))
+            .filter(api_tokens::user_id.eq(user_id))
+            .filter(api_tokens::revoked_at.is_null())
+            .set(api_tokens::revoked_at.eq(Utc::now().naive_utc()))
+            .execute(&mut conn)
+            .await{
+                Ok(c) => c,
+                Err(err) => return Err(err.into())
+            };
+        if count == 0 {
+            return Err(ApiError::NotFound("API token not found or already revoked".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Resolves an active API token to its owner's username and stamps its last use.
+    pub async fn use_api_token(&self, token_hash: &str) -> ApiResult<Option<(ApiToken, String)>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let found = match api_tokens::table
+            .inner_join(users::table)
+            .filter(api_tokens::token_hash.eq(token_hash))
+            .filter(api_tokens::revoked_at.is_null())
+            .select((ApiToken::as_select(), users::username))
+            .first::<(ApiToken, String)>(&mut conn)
+            .await
+            .optional(){
+                Ok(t) => t,
+                Err(err) => return Err(err.into())
+            };
+
+        if let Some((token, _)) = &found {
+            if let Err(err) = diesel::update(api_tokens::table)
+                .filter(api_tokens::id.eq(token.id))
+                .set(api_tokens::last_used_at.eq(Utc::now().naive_utc()))
+                .execute(&mut conn)
+                .await{
+                    return Err(err.into())
+                }
+        }
+        Ok(found)
+    }
+}
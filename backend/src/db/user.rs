@@ -1,18 +1,17 @@
 use std::sync::Arc;
-use anyhow::bail;
-use anyhow::Result;
+use crate::error::{ApiError, ApiResult};
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
-use diesel_async::AsyncPgConnection;
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection};
 use log::{debug, info, warn};
 use password_hash::PasswordHasher;
 use password_hash::{SaltString, rand_core::OsRng};
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use once_cell::sync::Lazy;
-use crate::db::model::NewUser;
+use crate::db::model::{AccountDeletion, NewAccountDeletion, NewBodyMeasurement, NewUser};
 use crate::db::model::UpdateUser;
 use crate::db::{database::DBOperations, model::User};
-use crate::schema::fittrack::users;
+use crate::schema::fittrack::{account_deletions, api_tokens, body_measurements, cardio_logs, password_reset_tokens, refresh_tokens, sets, users, workout_sessions};
 use diesel_async::pooled_connection::deadpool::Pool;
 pub static ARGON: Lazy<Argon2> = Lazy::new(|| Argon2::default());
 
@@ -26,28 +25,28 @@ impl UserDB {
         UserDB { database, pool: None}
     }
 
-    pub async fn init(&mut self) -> Result<(),>{
+    pub async fn init(&mut self) -> ApiResult<()>{
         let pool = match self.database.get_pool().await{
             Ok(pool) => pool,
             Err(err) => {
-                anyhow::bail!("{}",err);
+                return Err(err.into());
             }
         };
         self.pool = Some(pool);
         Ok(())
     }
 
-    pub async fn add_user<'a>(&self, mut user: NewUser<'a>) -> Result<bool,>{
+    pub async fn add_user<'a>(&self, mut user: NewUser<'a>) -> ApiResult<bool,>{
         let pool = match &self.pool{
             Some(pok) => pok,
             None => {
-                anyhow::bail!("Pool is not initialised");
+                return Err(ApiError::Internal("Pool is not initialised".to_string()));
             }
         };
         let mut conn = match pool.get().await{
             Ok(cok) => cok,
             Err(err) => {
-                anyhow::bail!("{}",err);
+                return Err(err.into());
             }
         };
         let username = user.username;
@@ -60,39 +59,57 @@ impl UserDB {
             },
             Ok(false) => (),
             Err(err) => {
-                return Err(anyhow::anyhow!("Error searching username: {}", err));
+                return Err(err);
             }
         };
         
         let salt = SaltString::generate(&mut OsRng);
         let hashed = match ARGON.hash_password(password.as_bytes(), &salt){
             Ok(h) => h,
-            Err(e) => return Err(anyhow::anyhow!("Error hashing password: {}",e)),
+            Err(e) => return Err(ApiError::Internal(format!("Error hashing password: {}",e))),
         }.to_string();
         
         user.password = &hashed;
-        let inserted_user: User = match diesel::insert_into(users::table)
-        .values(&user)
-        .get_result(&mut conn)
-        .await{
-            Ok(iok) => iok,
-            Err(err) => anyhow::bail!("{}",err)
-        };
-        debug!("Inserted user: {:?}", inserted_user);
+        // The weight given at sign-up is the first entry of the weight history, stored with the
+        // user so a failed reading doesn't leave an account behind
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let inserted_user: User = diesel::insert_into(users::table)
+                .values(&user)
+                .get_result(conn)
+                .await?;
+            if let Some(weight) = inserted_user.weight {
+                let reading = NewBodyMeasurement{
+                    user_id: inserted_user.id,
+                    measured_on: chrono::Utc::now().date_naive(),
+                    weight: Some(weight),
+                    ..Default::default()
+                };
+                diesel::insert_into(body_measurements::table)
+                    .values(&reading)
+                    .execute(conn)
+                    .await?;
+            }
+            Ok(inserted_user)
+        }.scope_boxed()).await;
+
+        match result{
+            Ok(inserted_user) => debug!("Inserted user: {:?}", inserted_user),
+            Err(err) => return Err(err.into())
+        };
         Ok(true)
     }
 
-    pub async fn search_username(&self, username: String) -> Result<bool,>{
+    pub async fn search_username(&self, username: String) -> ApiResult<bool,>{
         let pool = match &self.pool{
             Some(pok) => pok,
             None => {
-                anyhow::bail!("Pool is not initialised");
+                return Err(ApiError::Internal("Pool is not initialised".to_string()));
             }
         };
         let mut conn = match pool.get().await{
             Ok(cok) => cok,
             Err(err) => {
-                anyhow::bail!("{}",err);
+                return Err(err.into());
             }
         };
 
@@ -104,11 +121,11 @@ impl UserDB {
         match exists {
         Ok(_) => Ok(true),
         Err(diesel::result::Error::NotFound) => Ok(false), 
-        Err(e) => anyhow::bail!("{}",e), 
+        Err(e) => Err(e.into()), 
     }
     }
     
-    pub async fn verify_password(&self, username:String, password: String) -> Result<Option<i32>>{
+    pub async fn verify_password(&self, username:String, password: String) -> ApiResult<Option<i32>>{
         let (id, pass) = match self.get_id_and_password_by_username(username.clone()).await{
             Ok(Some(p)) => p,
             Ok(None) => {
@@ -116,12 +133,12 @@ impl UserDB {
                 return Ok(None);
             }
             Err(err) => {
-                return Err(anyhow::anyhow!("Error getting password by username: {}",err));
+                return Err(err);
             }
         };
         let parsed_hash = match PasswordHash::new(&pass) {
             Ok(h) => h,
-            Err(err) => return Err(anyhow::anyhow!("{}",err)),
+            Err(err) => return Err(ApiError::Internal(format!("Error parsing password hash: {}",err))),
         };
 
         match ARGON.verify_password(password.as_bytes(), &parsed_hash).is_ok() {
@@ -130,17 +147,17 @@ impl UserDB {
         }
     }
 
-    pub async fn get_id_and_password_by_username(&self, username:String) -> Result<Option<(i32, String)>>{
+    pub async fn get_id_and_password_by_username(&self, username:String) -> ApiResult<Option<(i32, String)>>{
         let pool = match &self.pool{
             Some(pok) => pok,
             None => {
-                anyhow::bail!("Pool is not initialised");
+                return Err(ApiError::Internal("Pool is not initialised".to_string()));
             }
         };
         let mut conn = match pool.get().await{
             Ok(cok) => cok,
             Err(err) => {
-                anyhow::bail!("{}",err);
+                return Err(err.into());
             }
         };
         let res = users::table
@@ -152,28 +169,28 @@ impl UserDB {
         match res {
             Ok(res) => Ok(Some(res)), 
             Err(diesel::result::Error::NotFound) => Ok(None), 
-            Err(err) => return Err(anyhow::anyhow!("{}",err)),
+            Err(err) => Err(err.into()),
         }
     }
 
-    pub async fn update_password(&self, username: String, password: String) -> Result<(),>{
+    pub async fn update_password(&self, username: String, password: String) -> ApiResult<()>{
         let pool = match &self.pool{
             Some(pok) => pok,
             None => {
-                anyhow::bail!("Pool is not initialised");
+                return Err(ApiError::Internal("Pool is not initialised".to_string()));
             }
         };
         let mut conn = match pool.get().await{
             Ok(cok) => cok,
             Err(err) => {
-                anyhow::bail!("{}",err);
+                return Err(err.into());
             }
         };
 
         let salt = SaltString::generate(&mut OsRng);
         let hashed = match ARGON.hash_password(password.as_bytes(), &salt){
             Ok(h) => h,
-            Err(e) => return Err(anyhow::anyhow!("Error hashing password: {}",e)),
+            Err(e) => return Err(ApiError::Internal(format!("Error hashing password: {}",e))),
         }.to_string();
 
         let username_clone = username.clone();
@@ -186,49 +203,68 @@ impl UserDB {
                     info!("Password updated for user {}", username_clone);
                     return Ok(())
                 },
-                Ok(_) => anyhow::bail!("Username not found"),
-                Err(err) => anyhow::bail!("{}",err)
+                Ok(_) => return Err(ApiError::NotFound("Username not found".to_string())),
+                Err(err) => return Err(err.into())
             };
     }
 
-    pub async fn update_user_details<'a>(&self, user_id: i32, username: String, user: UpdateUser<'a>) -> Result<(),>{
+    pub async fn update_user_details<'a>(&self, user_id: i32, username: String, user: UpdateUser<'a>) -> ApiResult<()>{
         let pool = match &self.pool{
             Some(pok) => pok,
             None => {
-                bail!("Pool is not intialised");
+                return Err(ApiError::Internal("Pool is not intialised".to_string()));
             }
         };
         let mut conn = match pool.get().await{
             Ok(cok) => cok,
             Err(err) => {
-                bail!("{}",err);
+                return Err(err.into());
             }
         };
 
-        match diesel::update(users::table)
-            .filter(users::id.eq(user_id))
-            .filter(users::username.eq(username))
-            .set(user)
-            .execute(&mut conn)
-            .await{
-                Ok(_) => debug!("Updated user details for user id {}", user_id),
-                Err(err) => bail!("{}",err)
-            };
+        // A new weight is logged as today's reading so the history keeps every change
+        let weight = user.weight;
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            diesel::update(users::table)
+                .filter(users::id.eq(user_id))
+                .filter(users::username.eq(username))
+                .set(user)
+                .execute(conn)
+                .await?;
+            if let Some(weight) = weight {
+                let reading = NewBodyMeasurement{
+                    user_id,
+                    measured_on: chrono::Utc::now().date_naive(),
+                    weight: Some(weight),
+                    ..Default::default()
+                };
+                diesel::insert_into(body_measurements::table)
+                    .values(&reading)
+                    .execute(conn)
+                    .await?;
+            }
+            Ok(())
+        }.scope_boxed()).await;
+
+        match result{
+            Ok(_) => debug!("Updated user details for user id {}", user_id),
+            Err(err) => return Err(err.into())
+        };
 
         Ok(())
     }
 
-    pub async fn get_user_by_id(&self, user_id: i32) -> Result<Option<User>>{
+    pub async fn get_user_by_id(&self, user_id: i32) -> ApiResult<Option<User>>{
         let pool = match &self.pool{
             Some(pok) => pok,
             None => {
-                bail!("Pool is not intialised");
+                return Err(ApiError::Internal("Pool is not intialised".to_string()));
             }
         };
         let mut conn = match pool.get().await{
             Ok(cok) => cok,
             Err(err) => {
-                bail!("{}",err);
+                return Err(err.into());
             }
         };
 
@@ -240,7 +276,135 @@ impl UserDB {
         match res {
             Ok(user) => Ok(Some(user)), 
             Err(diesel::result::Error::NotFound) => Ok(None), 
-            Err(err) => bail!("{}",err),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub async fn user_exists(&self, user_id: i32) -> ApiResult<bool>{
+        let pool = match &self.pool{
+            Some(pok) => pok,
+            None => {
+                return Err(ApiError::Internal("Pool is not intialised".to_string()));
+            }
+        };
+        let mut conn = match pool.get().await{
+            Ok(cok) => cok,
+            Err(err) => {
+                return Err(err.into());
+            }
+        };
+
+        match diesel::select(diesel::dsl::exists(users::table.filter(users::id.eq(user_id))))
+                .get_result::<bool>(&mut conn)
+                .await{
+            Ok(exists) => Ok(exists),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub async fn get_user_by_email(&self, email: String) -> ApiResult<Option<User>>{
+        let pool = match &self.pool{
+            Some(pok) => pok,
+            None => {
+                return Err(ApiError::Internal("Pool is not intialised".to_string()));
+            }
+        };
+        let mut conn = match pool.get().await{
+            Ok(cok) => cok,
+            Err(err) => {
+                return Err(err.into());
+            }
+        };
+
+        let res = users::table
+                .filter(users::email.eq(email))
+                .first::<User>(&mut conn)
+                .await;
+
+        match res {
+            Ok(user) => Ok(Some(user)), 
+            Err(diesel::result::Error::NotFound) => Ok(None), 
+            Err(err) => Err(err.into()),
         }
     }
-}
\ No newline at end of file
+
+    /// Hard-deletes the user and, through the cascading foreign keys, every row they own. Tokens
+    /// are deleted explicitly first so revocation doesn't depend on the cascade. The audit row is
+    /// written in the same transaction; returns `None` when the user no longer exists.
+    pub async fn delete_user(&self, user_id: i32, deletion_id: String, exported: bool) -> ApiResult<Option<AccountDeletion>>{
+        let pool = match &self.pool{
+            Some(pok) => pok,
+            None => {
+                return Err(ApiError::Internal("Pool is not intialised".to_string()));
+            }
+        };
+        let mut conn = match pool.get().await{
+            Ok(cok) => cok,
+            Err(err) => {
+                return Err(err.into());
+            }
+        };
+
+        let result = conn.transaction::<_, diesel::result::Error, _>(|conn| async move {
+            let found: Option<i32> = users::table
+                .filter(users::id.eq(user_id))
+                .select(users::id)
+                .for_update()
+                .first(conn)
+                .await
+                .optional()?;
+            if found.is_none() {
+                return Ok(None);
+            }
+
+            let sessions: i64 = workout_sessions::table
+                .filter(workout_sessions::user_id.eq(user_id))
+                .count()
+                .get_result(conn)
+                .await?;
+            let set_count: i64 = sets::table
+                .filter(sets::user_id.eq(user_id))
+                .count()
+                .get_result(conn)
+                .await?;
+            let log_count: i64 = cardio_logs::table
+                .filter(cardio_logs::user_id.eq(user_id))
+                .count()
+                .get_result(conn)
+                .await?;
+
+            let mut tokens_revoked = diesel::delete(refresh_tokens::table.filter(refresh_tokens::user_id.eq(user_id)))
+                .execute(conn)
+                .await?;
+            tokens_revoked += diesel::delete(api_tokens::table.filter(api_tokens::user_id.eq(user_id)))
+                .execute(conn)
+                .await?;
+            tokens_revoked += diesel::delete(password_reset_tokens::table.filter(password_reset_tokens::user_id.eq(user_id)))
+                .execute(conn)
+                .await?;
+
+            diesel::delete(users::table.filter(users::id.eq(user_id)))
+                .execute(conn)
+                .await?;
+
+            let audit = NewAccountDeletion{
+                deletion_id,
+                exported,
+                sessions: sessions as i32,
+                sets: set_count as i32,
+                cardio_logs: log_count as i32,
+                tokens_revoked: tokens_revoked as i32,
+            };
+            let deletion: AccountDeletion = diesel::insert_into(account_deletions::table)
+                .values(&audit)
+                .get_result(conn)
+                .await?;
+            Ok(Some(deletion))
+        }.scope_boxed()).await;
+
+        match result{
+            Ok(deletion) => Ok(deletion),
+            Err(err) => Err(err.into())
+        }
+    }
+}
@@ -1,12 +1,24 @@
 use std::sync::Arc;
 use std::collections::HashMap;
-use chrono::NaiveDate;
-use diesel::{ExpressionMethods, QueryDsl, BoolExpressionMethods};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{ExpressionMethods, QueryDsl, BoolExpressionMethods, NullableExpressionMethods, OptionalExtension, PgTextExpressionMethods};
 use diesel_async::{pooled_connection::deadpool::Pool, AsyncPgConnection};
-use crate::{db::{database::DBOperations, model::{CardioLog, WorkoutSession, WorkoutSet, MuscleGroup, Variation, CardioExercise}}, schema::fittrack::{cardio_logs, sets, workout_sessions, variations, muscle_groups, cardio_exercises}};
-use anyhow::{bail, Result};
+use crate::{db::{database::DBOperations, model::{CardioLog, TrackPoint, WorkoutSession, WorkoutSet, MuscleGroup, Variation, CardioExercise}}, schema::fittrack::{cardio_logs, cardio_track_points, sets, workout_sessions, variations, muscle_groups, cardio_exercises}};
+use crate::error::{ApiError, ApiResult};
 use diesel_async::RunQueryDsl;
 
+/// Narrows the workout history. `after` is the `(start_time, id)` of the last session already seen.
+#[derive(Debug, Default)]
+pub struct HistoryFilter{
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub after: Option<(NaiveDateTime, i32)>,
+    pub title: Option<String>,
+    pub variation_id: Option<i32>,
+    pub muscle_group_id: Option<i32>,
+    pub has_cardio: Option<bool>,
+}
+
 pub struct WorkoutDB{
     database: Arc<DBOperations>,
     pool: Option<Pool<AsyncPgConnection>>,
@@ -20,19 +32,21 @@ impl WorkoutDB{
         }
     }
 
-    pub async fn init(&mut self) -> Result<(),>{
+    pub async fn init(&mut self) -> ApiResult<()>{
         let pool = match self.database.get_pool().await{
             Ok(p) => p,
-            Err(err) => bail!("{}",err)
+            Err(err) => return Err(err.into())
         };
         self.pool = Some(pool);
         Ok(())
     }
 
-    pub async fn get_history(&self, user_id: i32, limit: i64, start_date: Option<NaiveDate>, end_date: Option<NaiveDate>) -> Result<Vec<WorkoutSession>> {
+    /// A page of sessions, newest first, ordered by `(start_time, id)` so equal start times page
+    /// consistently. Starts after `filter.after` when set.
+    pub async fn get_history(&self, user_id: i32, limit: i64, filter: &HistoryFilter) -> ApiResult<Vec<WorkoutSession>> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => bail!("Pool not initialized"),
+            None => return Err(ApiError::Internal("Pool not initialized".to_string())),
         };
         let mut conn = pool.get().await?;
 
@@ -41,26 +55,102 @@ impl WorkoutDB{
         let mut query = workout_sessions::table.into_boxed();
         query = query.filter(workout_sessions::user_id.eq(user_id));
 
-        if let Some(s) = start_date {
+        if let Some(s) = filter.start_date {
             query = query.filter(workout_sessions::start_time.ge(s.and_hms_opt(0, 0, 0).unwrap()));
         }
 
-        if let Some(e) = end_date {
+        if let Some(e) = filter.end_date {
             query = query.filter(workout_sessions::start_time.le(e.and_hms_opt(23, 59, 59).unwrap()));
         }
 
+        if let Some((start_time, id)) = filter.after {
+            query = query.filter(workout_sessions::start_time.lt(start_time)
+                .or(workout_sessions::start_time.eq(start_time).and(workout_sessions::id.lt(id))));
+        }
+
+        if let Some(title) = &filter.title {
+            let escaped = title.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            query = query.filter(workout_sessions::title.ilike(format!("%{}%", escaped)));
+        }
+
+        if let Some(variation_id) = filter.variation_id {
+            query = query.filter(workout_sessions::id.nullable().eq_any(sets::table
+                .filter(sets::user_id.eq(user_id))
+                .filter(sets::variation_id.eq(variation_id))
+                .select(sets::workout_session_id)));
+        }
+
+        if let Some(muscle_group_id) = filter.muscle_group_id {
+            query = query.filter(workout_sessions::id.nullable().eq_any(sets::table
+                .inner_join(variations::table)
+                .filter(sets::user_id.eq(user_id))
+                .filter(variations::muscle_group_id.eq(muscle_group_id))
+                .select(sets::workout_session_id)));
+        }
+
+        // NOT IN is empty as soon as the subquery yields a NULL, so unattached logs are left out
+        let with_cardio = cardio_logs::table
+            .filter(cardio_logs::user_id.eq(user_id))
+            .filter(cardio_logs::workout_session_id.is_not_null())
+            .select(cardio_logs::workout_session_id);
+        match filter.has_cardio {
+            Some(true) => query = query.filter(workout_sessions::id.nullable().eq_any(with_cardio)),
+            Some(false) => query = query.filter(workout_sessions::id.nullable().ne_all(with_cardio)),
+            None => (),
+        }
+
         let history = query
-            .order(workout_sessions::start_time.desc())
+            .order((workout_sessions::start_time.desc(), workout_sessions::id.desc()))
             .limit(limit)
             .get_results(&mut conn)
             .await?;
         Ok(history)
     }
 
-    pub async fn get_session_details(&self, user_id: i32, session_id: i32) -> Result<(WorkoutSession, Vec<WorkoutSet>, Vec<CardioLog>)> {
+    /// Number of sets and cardio logs in each of the given sessions, keyed by session id.
+    pub async fn get_session_counts(&self, user_id: i32, session_ids: Vec<i32>) -> ApiResult<HashMap<i32, (i64, i64)>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let set_counts: Vec<(Option<i32>, i64)> = sets::table
+            .filter(sets::user_id.eq(user_id))
+            .filter(sets::workout_session_id.eq_any(&session_ids))
+            .group_by(sets::workout_session_id)
+            .select((sets::workout_session_id, diesel::dsl::count_star()))
+            .get_results(&mut conn)
+            .await?;
+        let log_counts: Vec<(Option<i32>, i64)> = cardio_logs::table
+            .filter(cardio_logs::user_id.eq(user_id))
+            .filter(cardio_logs::workout_session_id.eq_any(&session_ids))
+            .group_by(cardio_logs::workout_session_id)
+            .select((cardio_logs::workout_session_id, diesel::dsl::count_star()))
+            .get_results(&mut conn)
+            .await?;
+
+        let mut counts: HashMap<i32, (i64, i64)> = HashMap::new();
+        for (id, count) in set_counts.into_iter(){
+            if let Some(id) = id {
+                counts.entry(id).or_default().0 = count;
+            }
+        }
+        for (id, count) in log_counts.into_iter(){
+            if let Some(id) = id {
+                counts.entry(id).or_default().1 = count;
+            }
+        }
+        Ok(counts)
+    }
+
+    pub async fn get_session_details(&self, user_id: i32, session_id: i32) -> ApiResult<(WorkoutSession, Vec<WorkoutSet>, Vec<CardioLog>)> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => bail!("Pool not initialized"),
+            None => return Err(ApiError::Internal("Pool not initialized".to_string())),
         };
         let mut conn = pool.get().await?;
 
@@ -75,6 +165,7 @@ impl WorkoutDB{
         let session_sets: Vec<WorkoutSet> = sets::table
             .filter(sets::workout_session_id.eq(session_id))
             .filter(sets::user_id.eq(user_id)) // Redundant security but good
+            .order((sets::set_index.asc(), sets::id.asc()))
             .get_results(&mut conn)
             .await?;
 
@@ -88,14 +179,14 @@ impl WorkoutDB{
     }
 
 
-    pub async fn get_monthly_workout_details(&self, user_id: i32, year: i32, month: i32 ) -> Result<Vec<WorkoutSession>>{
+    pub async fn get_monthly_workout_details(&self, user_id: i32, year: i32, month: i32 ) -> ApiResult<Vec<WorkoutSession>>{
         let pool = match &self.pool{
             Some(p) => p,
-            None => bail!("Pool not initialised",)
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
         };
         let mut conn = match pool.get().await{
             Ok(c)  => c,
-            Err(err) => bail!(err)
+            Err(err) => return Err(err.into())
         };
 
 
@@ -108,17 +199,122 @@ impl WorkoutDB{
         Ok(monthly_workout)
     }
 
-    pub async fn get_performance_details(&self, user_id: i32, variation_id: i32, start_date: NaiveDate, end_date: NaiveDate) -> Result<Vec<WorkoutSet>,>{
+    /// Track of one of the user's cardio logs, `None` when the log isn't theirs.
+    pub async fn get_track_points(&self, user_id: i32, cardio_log_id: i32) -> ApiResult<Option<Vec<TrackPoint>>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let owned = cardio_logs::table
+            .filter(cardio_logs::id.eq(cardio_log_id))
+            .filter(cardio_logs::user_id.eq(user_id))
+            .select(cardio_logs::id)
+            .first::<i32>(&mut conn)
+            .await
+            .optional()?;
+        if owned.is_none() {
+            return Ok(None);
+        }
+
+        match cardio_track_points::table
+            .filter(cardio_track_points::cardio_log_id.eq(cardio_log_id))
+            .order(cardio_track_points::seq.asc())
+            .get_results(&mut conn)
+            .await{
+                Ok(points) => Ok(Some(points)),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    pub async fn get_cardio_logs(&self, user_id: i32, start_date: NaiveDate, end_date: NaiveDate, cardio_exercise_id: Option<i32>) -> ApiResult<Vec<CardioLog>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let mut query = cardio_logs::table
+            .filter(cardio_logs::user_id.eq(user_id))
+            .filter(cardio_logs::performed_on.ge(start_date))
+            .filter(cardio_logs::performed_on.le(end_date))
+            .filter(cardio_logs::completed.eq(true))
+            .into_boxed();
+        if let Some(id) = cardio_exercise_id {
+            query = query.filter(cardio_logs::cardio_exercise_id.eq(id));
+        }
+
+        match query
+            .order((cardio_logs::performed_on.asc(), cardio_logs::id.asc()))
+            .get_results(&mut conn)
+            .await{
+                Ok(logs) => Ok(logs),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    /// Cardio logged inside any of the given sessions.
+    pub async fn get_session_cardio_logs(&self, user_id: i32, session_ids: Vec<i32>) -> ApiResult<Vec<CardioLog>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match cardio_logs::table
+            .filter(cardio_logs::user_id.eq(user_id))
+            .filter(cardio_logs::workout_session_id.eq_any(session_ids))
+            .get_results(&mut conn)
+            .await{
+                Ok(logs) => Ok(logs),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    /// Sessions that started between the two dates, oldest first.
+    pub async fn get_sessions(&self, user_id: i32, start_date: NaiveDate, end_date: NaiveDate) -> ApiResult<Vec<WorkoutSession>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match workout_sessions::table
+            .filter(workout_sessions::user_id.eq(user_id))
+            .filter(workout_sessions::date.ge(start_date))
+            .filter(workout_sessions::date.le(end_date))
+            .order(workout_sessions::start_time.asc())
+            .get_results(&mut conn)
+            .await{
+                Ok(sessions) => Ok(sessions),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    pub async fn get_performance_details(&self, user_id: i32, variation_id: i32, start_date: NaiveDate, end_date: NaiveDate) -> ApiResult<Vec<WorkoutSet>>{
         let pool = match &self.pool{
             Some(p) => p,
-            None => bail!("Pool not initialised")
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
         };
 
         let mut conn = match pool.get().await{
             Ok(c) => c,
             Err(err) => {
                 println!("Error getting connection: {}", err);
-                bail!(err)
+                return Err(err.into())
             }
         };
 
@@ -127,6 +323,7 @@ impl WorkoutDB{
             .filter(sets::variation_id.eq(variation_id))
             .filter(sets::performed_on.ge(start_date))
             .filter(sets::performed_on.le(end_date))
+            .filter(sets::completed.eq(true))
             .get_results(&mut conn)
             .await;
 
@@ -135,22 +332,72 @@ impl WorkoutDB{
                 Ok(data)
             },
             Err(e) => {
-                bail!(e)
+                Err(e.into())
             }
         }
     }
 
-    pub async fn get_sets_for_musclegroups(&self, user_id: i32, muscle_group_ids: Vec<i32>, start_date: NaiveDate, end_date: NaiveDate) -> Result<Vec<WorkoutSet>,>{
+    pub async fn get_variation_sets(&self, user_id: i32, variation_id: i32) -> ApiResult<Vec<WorkoutSet>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let result = sets::table
+            .filter(sets::user_id.eq(user_id))
+            .filter(sets::variation_id.eq(variation_id))
+            .filter(sets::completed.eq(true))
+            .order((sets::performed_on.asc(), sets::id.asc()))
+            .get_results(&mut conn)
+            .await;
+
+        match result {
+            Ok(data) => Ok(data),
+            Err(e) => Err(e.into())
+        }
+    }
+
+    pub async fn get_session_variation_ids(&self, user_id: i32, session_id: i32) -> ApiResult<Vec<i32>,>{
         let pool = match &self.pool{
             Some(p) => p,
-            None => bail!("Pool not initialised")
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        let result = sets::table
+            .filter(sets::user_id.eq(user_id))
+            .filter(sets::workout_session_id.eq(session_id))
+            .select(sets::variation_id)
+            .distinct()
+            .get_results::<i32>(&mut conn)
+            .await;
+
+        match result {
+            Ok(data) => Ok(data),
+            Err(e) => Err(e.into())
+        }
+    }
+
+    pub async fn get_sets_for_musclegroups(&self, user_id: i32, muscle_group_ids: Vec<i32>, start_date: NaiveDate, end_date: NaiveDate) -> ApiResult<Vec<WorkoutSet>>{
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
         };
 
         let mut conn = match pool.get().await{
             Ok(c) => c,
             Err(err) => {
                 println!("Error getting connection: {}", err);
-                bail!(err)
+                return Err(err.into())
             }
         };
 
@@ -159,6 +406,7 @@ impl WorkoutDB{
             .filter(sets::user_id.eq(user_id))
             .filter(sets::performed_on.ge(start_date))
             .filter(sets::performed_on.le(end_date))
+            .filter(sets::completed.eq(true))
             .filter(variations::muscle_group_id.eq_any(muscle_group_ids))
             .filter(variations::user_id.eq(user_id).or(variations::user_id.eq(0)))
             .select(sets::all_columns)
@@ -170,20 +418,20 @@ impl WorkoutDB{
                 Ok(data)
             },
             Err(e) => {
-                bail!(e)
+                Err(e.into())
             }
         }
     }
 
-    pub async fn get_varaition_ids(&self, user_id: i32, muscle_group_ids: Vec<i32>) -> Result<HashMap<i32, i32>> {
+    pub async fn get_varaition_ids(&self, user_id: i32, muscle_group_ids: Vec<i32>) -> ApiResult<HashMap<i32, i32>> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => bail!("Pool not initialised")
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
         };
 
         let mut conn = match pool.get().await {
             Ok(c) => c,
-            Err(err) => bail!(err)
+            Err(err) => return Err(err.into())
         };
 
         let results = match variations::table
@@ -193,7 +441,7 @@ impl WorkoutDB{
             .get_results::<(i32, i32)>(&mut conn)
             .await{
                 Ok(res) => res,
-                Err(err) => bail!(err)
+                Err(err) => return Err(err.into())
             };
 
         let mut map: HashMap<i32, i32> = HashMap::new();
@@ -203,10 +451,10 @@ impl WorkoutDB{
         Ok(map)
     }
 
-    pub async fn get_all_muscle_groups(&self,user_id: i32) -> Result<Vec<MuscleGroup>> {
+    pub async fn get_all_muscle_groups(&self,user_id: i32) -> ApiResult<Vec<MuscleGroup>> {
         let pool = match &self.pool{
             Some(p) => p,
-            None => bail!("Pool not initialised")
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
         };
         let mut conn = pool.get().await?;
         
@@ -217,10 +465,10 @@ impl WorkoutDB{
         Ok(results)
     }
 
-    pub async fn get_all_variations(&self,user_id: i32) -> Result<Vec<Variation>> {
+    pub async fn get_all_variations(&self,user_id: i32) -> ApiResult<Vec<Variation>> {
         let pool = match &self.pool{
             Some(p) => p,
-            None => bail!("Pool not initialised")
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
         };
         let mut conn = pool.get().await?;
         
@@ -231,10 +479,10 @@ impl WorkoutDB{
         Ok(results)
     }
 
-    pub async fn get_all_cardio_exercises(&self,user_id: i32) -> Result<Vec<CardioExercise>> {
+    pub async fn get_all_cardio_exercises(&self,user_id: i32) -> ApiResult<Vec<CardioExercise>> {
         let pool = match &self.pool{
             Some(p) => p,
-            None => bail!("Pool not initialised")
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
         };
         let mut conn = pool.get().await?;
         
@@ -244,4 +492,85 @@ impl WorkoutDB{
             .await?;
         Ok(results)
     }
-}
\ No newline at end of file
+
+    /// Looks a session up by id regardless of owner so callers can tell missing from forbidden.
+    pub async fn get_session(&self, session_id: i32) -> ApiResult<Option<WorkoutSession>> {
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match workout_sessions::table
+            .filter(workout_sessions::id.eq(session_id))
+            .first::<WorkoutSession>(&mut conn)
+            .await
+            .optional(){
+                Ok(s) => Ok(s),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    pub async fn get_variation(&self, variation_id: i32) -> ApiResult<Option<Variation>> {
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match variations::table
+            .filter(variations::id.eq(variation_id))
+            .first::<Variation>(&mut conn)
+            .await
+            .optional(){
+                Ok(v) => Ok(v),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    pub async fn get_muscle_group(&self, muscle_group_id: i32) -> ApiResult<Option<MuscleGroup>> {
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match muscle_groups::table
+            .filter(muscle_groups::id.eq(muscle_group_id))
+            .first::<MuscleGroup>(&mut conn)
+            .await
+            .optional(){
+                Ok(m) => Ok(m),
+                Err(err) => Err(err.into())
+            }
+    }
+
+    pub async fn get_cardio_exercise(&self, cardio_exercise_id: i32) -> ApiResult<Option<CardioExercise>> {
+        let pool = match &self.pool{
+            Some(p) => p,
+            None => return Err(ApiError::Internal("Pool not initialised".to_string()))
+        };
+        let mut conn = match pool.get().await{
+            Ok(c) => c,
+            Err(err) => return Err(err.into())
+        };
+
+        match cardio_exercises::table
+            .filter(cardio_exercises::id.eq(cardio_exercise_id))
+            .first::<CardioExercise>(&mut conn)
+            .await
+            .optional(){
+                Ok(c) => Ok(c),
+                Err(err) => Err(err.into())
+            }
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel_async::pooled_connection::deadpool::PoolError;
+use log::{error, warn};
+use serde::Serialize;
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+/// Error shared by the db, service and api layers. Each variant maps to one HTTP status and
+/// is rendered as the same JSON body, internal details are logged but never sent to clients.
+#[derive(Debug)]
+pub enum ApiError{
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    Validation(String),
+    InvalidFields(Vec<FieldError>),
+    Internal(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError{
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a>{
+    success: bool,
+    error: &'a str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<&'a [FieldError]>,
+}
+
+impl ApiError{
+    pub fn code(&self) -> &'static str{
+        match self{
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Validation(_) | ApiError::InvalidFields(_) => "validation_failed",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> &str{
+        match self{
+            ApiError::BadRequest(m)
+            | ApiError::Unauthorized(m)
+            | ApiError::Forbidden(m)
+            | ApiError::NotFound(m)
+            | ApiError::Conflict(m)
+            | ApiError::Validation(m)
+            | ApiError::Internal(m) => m,
+            ApiError::InvalidFields(_) => "Request validation failed",
+        }
+    }
+}
+
+impl fmt::Display for ApiError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for ApiError{}
+
+impl ResponseError for ApiError{
+    fn status_code(&self) -> StatusCode{
+        match self{
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Validation(_) | ApiError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse{
+        let message = match self{
+            ApiError::Internal(detail) => {
+                error!("Internal error: {}", detail);
+                "Internal server error"
+            }
+            _ => self.message(),
+        };
+        let errors = match self{
+            ApiError::InvalidFields(fields) => Some(fields.as_slice()),
+            _ => None,
+        };
+        HttpResponse::build(self.status_code()).json(ErrorBody{
+            success: false,
+            error: self.code(),
+            message,
+            errors,
+        })
+    }
+}
+
+impl From<DieselError> for ApiError{
+    fn from(err: DieselError) -> Self{
+        match err{
+            DieselError::NotFound => ApiError::NotFound("Record not found".to_string()),
+            // Postgres' details name the key and echo its value, which may be another user's
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                warn!("Unique violation on {}: {}", info.constraint_name().unwrap_or("unknown constraint"), info.details().unwrap_or(info.message()));
+                ApiError::Conflict("Record already exists".to_string())
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) =>
+                ApiError::NotFound("Referenced record not found".to_string()),
+            DieselError::DatabaseError(DatabaseErrorKind::CheckViolation, info) =>
+                ApiError::Validation(info.message().to_string()),
+            other => ApiError::Internal(other.to_string()),
+        }
+    }
+}
+
+impl From<PoolError> for ApiError{
+    fn from(err: PoolError) -> Self{
+        ApiError::Internal(format!("Error getting connection: {}", err))
+    }
+}
+
+impl From<anyhow::Error> for ApiError{
+    fn from(err: anyhow::Error) -> Self{
+        ApiError::Internal(err.to_string())
+    }
+}
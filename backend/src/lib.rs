@@ -6,3 +6,4 @@ pub mod api;
 pub mod services;
 pub mod db;
 pub mod configuration;
+pub mod error;
@@ -13,7 +13,7 @@ async fn main() -> std::io::Result<()> {
         Ok(c) => c,
         Err(err) => {
             error!("{}",err);
-            return Ok(());
+            return Err(std::io::Error::other(format!("Error loading config: {}", err)));
         }
     };
 
@@ -21,7 +21,8 @@ async fn main() -> std::io::Result<()> {
     let mut db = Database::new();
 
     if let Err(err) = db.init().await{
-        error!("Error initialising database, {}",err)
+        error!("Error initialising database, {}",err);
+        return Err(std::io::Error::other(format!("Error initialising database: {}", err)));
     };
     let db_ops = db.database.unwrap();
     info!("Database initialized successfully.");
@@ -29,15 +30,27 @@ async fn main() -> std::io::Result<()> {
     let mut service_ins = Service::new(db_ops);
     if let Err(err) = service_ins.init().await{
         error!("Error initialising services, {}",err);
+        // Non-zero exit so supervisors see a failed start rather than a clean stop
+        return Err(std::io::Error::other(format!("Error initialising services: {}", err)));
     };
     info!("Services initialized successfully.");
 
-    let auth_service = service_ins.auth_service.unwrap();
-    let post_service = service_ins.post_service.unwrap();
-    let get_service = service_ins.get_service.unwrap();
-    let put_service = service_ins.put_service.unwrap();
-    let jwt_service = service_ins.jwt_service.unwrap();
-    let mut api_ins = api::API::new(auth_service,jwt_service,post_service, get_service,put_service);
+    let services = api::ApiServices{
+        auth_service: service_ins.auth_service.unwrap(),
+        jwt_service: service_ins.jwt_service.unwrap(),
+        post_service: service_ins.post_service.unwrap(),
+        get_service: service_ins.get_service.unwrap(),
+        put_service: service_ins.put_service.unwrap(),
+        records_service: service_ins.records_service.unwrap(),
+        session_service: service_ins.session_service.unwrap(),
+        routine_service: service_ins.routine_service.unwrap(),
+        program_service: service_ins.program_service.unwrap(),
+        metrics_service: service_ins.metrics_service.unwrap(),
+        export_service: service_ins.export_service.unwrap(),
+        import_service: service_ins.import_service.unwrap(),
+        search_service: service_ins.search_service.unwrap()
+    };
+    let mut api_ins = api::API::new(services);
     api_ins.init().await;
     info!("API initialized successfully.");
 
@@ -50,6 +63,7 @@ async fn main() -> std::io::Result<()> {
             .allowed_origin("http://localhost:3000")
             .allow_any_method()
             .allow_any_header()
+            .expose_headers([api::login::DELETION_ID_HEADER])
             .supports_credentials();
 
         App::new()
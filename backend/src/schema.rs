@@ -1,11 +1,73 @@
 // @generated automatically by Diesel CLI.
 
 pub mod fittrack {
+    diesel::table! {
+        fittrack.account_deletions (id) {
+            id -> Int4,
+            deletion_id -> Text,
+            deleted_at -> Timestamp,
+            exported -> Bool,
+            sessions -> Int4,
+            sets -> Int4,
+            cardio_logs -> Int4,
+            tokens_revoked -> Int4,
+        }
+    }
+
+    diesel::table! {
+        fittrack.api_tokens (id) {
+            id -> Int4,
+            user_id -> Int4,
+            #[max_length = 100]
+            name -> Varchar,
+            #[max_length = 64]
+            token_hash -> Varchar,
+            #[max_length = 20]
+            scope -> Varchar,
+            created_at -> Timestamp,
+            last_used_at -> Nullable<Timestamp>,
+            revoked_at -> Nullable<Timestamp>,
+        }
+    }
+
+    diesel::table! {
+        fittrack.body_measurements (id) {
+            id -> Int4,
+            user_id -> Int4,
+            measured_on -> Date,
+            weight -> Nullable<Float8>,
+            body_fat_percent -> Nullable<Float8>,
+            neck -> Nullable<Float8>,
+            chest -> Nullable<Float8>,
+            waist -> Nullable<Float8>,
+            hips -> Nullable<Float8>,
+            arms -> Nullable<Float8>,
+            thighs -> Nullable<Float8>,
+            notes -> Nullable<Text>,
+            created_at -> Timestamp,
+        }
+    }
+
     diesel::table! {
         fittrack.cardio_exercises (id) {
             id -> Int4,
             name -> Varchar,
             user_id -> Nullable<Int4>,
+            met -> Float8,
+        }
+    }
+
+    diesel::table! {
+        fittrack.cardio_track_points (id) {
+            id -> Int8,
+            cardio_log_id -> Int4,
+            seq -> Int4,
+            recorded_at -> Nullable<Timestamp>,
+            latitude -> Nullable<Float8>,
+            longitude -> Nullable<Float8>,
+            elevation_m -> Nullable<Float8>,
+            heart_rate -> Nullable<Int4>,
+            distance_m -> Nullable<Float8>,
         }
     }
 
@@ -17,6 +79,13 @@ pub mod fittrack {
             cardio_exercise_id -> Int4,
             duration_minutes -> Int4,
             performed_on -> Date,
+            distance_m -> Nullable<Float8>,
+            elevation_gain_m -> Nullable<Float8>,
+            avg_heart_rate -> Nullable<Int4>,
+            max_heart_rate -> Nullable<Int4>,
+            calories -> Nullable<Int4>,
+            started_at -> Nullable<Timestamp>,
+            completed -> Bool,
         }
     }
 
@@ -28,6 +97,122 @@ pub mod fittrack {
         }
     }
 
+    diesel::table! {
+        fittrack.password_reset_tokens (id) {
+            id -> Int4,
+            user_id -> Int4,
+            #[max_length = 64]
+            token_hash -> Varchar,
+            expires_at -> Timestamp,
+            created_at -> Timestamp,
+            used_at -> Nullable<Timestamp>,
+        }
+    }
+
+    diesel::table! {
+        fittrack.personal_records (id) {
+            id -> Int4,
+            user_id -> Int4,
+            variation_id -> Int4,
+            set_id -> Nullable<Int4>,
+            workout_session_id -> Nullable<Int4>,
+            #[max_length = 20]
+            record_type -> Varchar,
+            value -> Float8,
+            weight -> Float8,
+            reps -> Int4,
+            achieved_on -> Date,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        fittrack.program_days (id) {
+            id -> Int4,
+            program_week_id -> Int4,
+            day_number -> Int4,
+            routine_id -> Int4,
+        }
+    }
+
+    diesel::table! {
+        fittrack.program_weeks (id) {
+            id -> Int4,
+            program_id -> Int4,
+            week_number -> Int4,
+            deload -> Bool,
+        }
+    }
+
+    diesel::table! {
+        fittrack.programs (id) {
+            id -> Int4,
+            user_id -> Int4,
+            #[max_length = 100]
+            name -> Varchar,
+            notes -> Nullable<Text>,
+            deload_percent -> Float8,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        fittrack.progression_rules (id) {
+            id -> Int4,
+            program_id -> Int4,
+            variation_id -> Nullable<Int4>,
+            #[max_length = 20]
+            rule_type -> Varchar,
+            increment -> Nullable<Float8>,
+            percent -> Nullable<Float8>,
+        }
+    }
+
+    diesel::table! {
+        fittrack.refresh_tokens (id) {
+            id -> Int4,
+            user_id -> Int4,
+            #[max_length = 64]
+            family_id -> Varchar,
+            #[max_length = 64]
+            token_hash -> Varchar,
+            expires_at -> Timestamp,
+            created_at -> Timestamp,
+            used_at -> Nullable<Timestamp>,
+            revoked_at -> Nullable<Timestamp>,
+        }
+    }
+
+    diesel::table! {
+        fittrack.routine_exercises (id) {
+            id -> Int4,
+            routine_id -> Int4,
+            position -> Int4,
+            variation_id -> Nullable<Int4>,
+            cardio_exercise_id -> Nullable<Int4>,
+            target_sets -> Int4,
+            reps_min -> Nullable<Int4>,
+            reps_max -> Nullable<Int4>,
+            target_weight -> Nullable<Float8>,
+            rest_seconds -> Nullable<Int4>,
+            target_duration_minutes -> Nullable<Int4>,
+            notes -> Nullable<Text>,
+        }
+    }
+
+    diesel::table! {
+        fittrack.routines (id) {
+            id -> Int4,
+            user_id -> Int4,
+            #[max_length = 100]
+            name -> Varchar,
+            notes -> Nullable<Text>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
     diesel::table! {
         fittrack.sets (id) {
             id -> Int4,
@@ -37,6 +222,15 @@ pub mod fittrack {
             weight -> Float8,
             reps -> Int4,
             performed_on -> Date,
+            set_index -> Nullable<Int4>,
+            #[max_length = 10]
+            set_type -> Varchar,
+            rpe -> Nullable<Float8>,
+            rir -> Nullable<Int4>,
+            rest_seconds -> Nullable<Int4>,
+            #[max_length = 16]
+            tempo -> Nullable<Varchar>,
+            completed -> Bool,
         }
     }
 
@@ -55,6 +249,12 @@ pub mod fittrack {
             weight -> Nullable<Float8>,
             height -> Nullable<Float8>,
             dob -> Nullable<Date>,
+            #[max_length = 2]
+            weight_unit -> Varchar,
+            #[max_length = 2]
+            length_unit -> Varchar,
+            #[max_length = 6]
+            sex -> Nullable<Varchar>,
         }
     }
 
@@ -77,25 +277,59 @@ pub mod fittrack {
             start_time -> Timestamp,
             end_time -> Timestamp,
             notes -> Nullable<Text>,
+            program_day_id -> Nullable<Int4>,
         }
     }
 
+    diesel::joinable!(api_tokens -> users (user_id));
+    diesel::joinable!(body_measurements -> users (user_id));
     diesel::joinable!(cardio_exercises -> users (user_id));
     diesel::joinable!(cardio_logs -> cardio_exercises (cardio_exercise_id));
     diesel::joinable!(cardio_logs -> users (user_id));
     diesel::joinable!(cardio_logs -> workout_sessions (workout_session_id));
+    diesel::joinable!(cardio_track_points -> cardio_logs (cardio_log_id));
     diesel::joinable!(muscle_groups -> users (user_id));
+    diesel::joinable!(password_reset_tokens -> users (user_id));
+    diesel::joinable!(personal_records -> sets (set_id));
+    diesel::joinable!(personal_records -> users (user_id));
+    diesel::joinable!(personal_records -> variations (variation_id));
+    diesel::joinable!(personal_records -> workout_sessions (workout_session_id));
+    diesel::joinable!(program_days -> program_weeks (program_week_id));
+    diesel::joinable!(program_days -> routines (routine_id));
+    diesel::joinable!(program_weeks -> programs (program_id));
+    diesel::joinable!(programs -> users (user_id));
+    diesel::joinable!(progression_rules -> programs (program_id));
+    diesel::joinable!(progression_rules -> variations (variation_id));
+    diesel::joinable!(refresh_tokens -> users (user_id));
+    diesel::joinable!(routine_exercises -> cardio_exercises (cardio_exercise_id));
+    diesel::joinable!(routine_exercises -> routines (routine_id));
+    diesel::joinable!(routine_exercises -> variations (variation_id));
+    diesel::joinable!(routines -> users (user_id));
     diesel::joinable!(sets -> users (user_id));
     diesel::joinable!(sets -> variations (variation_id));
     diesel::joinable!(sets -> workout_sessions (workout_session_id));
     diesel::joinable!(variations -> muscle_groups (muscle_group_id));
     diesel::joinable!(variations -> users (user_id));
+    diesel::joinable!(workout_sessions -> program_days (program_day_id));
     diesel::joinable!(workout_sessions -> users (user_id));
 
     diesel::allow_tables_to_appear_in_same_query!(
+        account_deletions,
+        api_tokens,
+        body_measurements,
         cardio_exercises,
         cardio_logs,
+        cardio_track_points,
         muscle_groups,
+        password_reset_tokens,
+        personal_records,
+        program_days,
+        program_weeks,
+        programs,
+        progression_rules,
+        refresh_tokens,
+        routine_exercises,
+        routines,
         sets,
         users,
         variations,
@@ -2,7 +2,13 @@ use std::sync::Arc;
 use log::{debug, error, info};
 use serde::Serialize;
 use password_hash::{SaltString, rand_core::OsRng, PasswordHasher};
-use crate::{api::login::{ForgotPasswordRequest, LoginRequest, RegisterRequest, UpdateUserInfo}, db::{model::{NewUser, UpdateUser}, user::{UserDB, ARGON}}};
+use chrono::{Duration, Utc};
+use crate::{api::login::{ForgotPasswordRequest, LoginRequest, RegisterRequest, ResetPasswordRequest, UpdateUserInfo}, 
+            db::{model::{AccountDeletion, NewPasswordResetToken, NewUser, UpdateUser}, tokens::TokenDB, user::{UserDB, ARGON}},
+            error::{ApiError, ApiResult},
+            services::{crypto::{generate_token, hash_token}, mailer::{MailMessage, Mailer}, units::Units}};
+
+const RESET_TOKEN_MINUTES: i64 = 30;
 
 #[derive(Serialize)]
 pub struct AuthResponse{
@@ -14,131 +20,169 @@ pub struct AuthResponse{
 
 pub struct AuthService{
     user: Arc<UserDB>,
+    tokens: Arc<TokenDB>,
+    mailer: Arc<dyn Mailer>,
+    reset_url: String,
 }
 impl AuthService{
-    pub fn new(user: Arc<UserDB>) -> Self{
-        AuthService { user }
+    pub fn new(user: Arc<UserDB>, tokens: Arc<TokenDB>, mailer: Arc<dyn Mailer>, reset_url: String) -> Self{
+        AuthService { user, tokens, mailer, reset_url }
     } 
-    pub async fn login(&self,request: LoginRequest) -> AuthResponse{
+    pub async fn login(&self,request: LoginRequest) -> ApiResult<AuthResponse>{
         info!("Authenticating user: {}", request.username);
         let username = request.username.clone();
         let password = request.password;
         match self.user.verify_password(username, password).await{
             Ok(Some(id)) =>{
                 info!("User {} authenticated successfully", request.username);
-                return AuthResponse{
+                Ok(AuthResponse{
                     username: request.username,
                     user_id: Some(id),
                     success: true,
                     message: "Login successful".to_string()
-                }
+                })
             }
             Ok(None) => {
                 error!("No user found or Invalid credentials for user: {}", request.username);
-                return AuthResponse{
-                    username: request.username,
-                    user_id: None,
-                    success: false,
-                    message: "Invalid credentials".to_string(),
-                }
+                Err(ApiError::Unauthorized("Invalid credentials".to_string()))
             }
             Err(err) =>{
                 error!("Error during authentication for user {}: {}", request.username, err);
-                return AuthResponse{
-                    username: request.username,
-                    user_id: None,
-                    success: false,
-                    message: format!("Error during authentication: {}",err),
-                }
+                Err(err)
             }
         }
     }
-    pub async fn register(&self, request: RegisterRequest) -> AuthResponse{
+    pub async fn register(&self, request: RegisterRequest) -> ApiResult<AuthResponse>{
         if !request.password.eq(&request.confirmpassword){
             debug!("Passwords do not match for user: {}", request.username);
-            return AuthResponse{
-                username: request.username,
-                user_id: None,
-                success: false,
-                message: "Passwords do not match".to_string(),
-            };
+            return Err(ApiError::Validation("Passwords do not match".to_string()));
         }
+        let weight_unit = request.weight_unit.unwrap_or_default();
+        let length_unit = request.length_unit.unwrap_or_default();
         let user = NewUser{
             fullname: &request.fullname,
             username: &request.username.clone(),
             email: &request.email,
             password: &request.password,
-            weight: Some(request.weight),
-            height: Some(request.height),
+            weight: Some(weight_unit.to_kg(request.weight)),
+            height: Some(length_unit.to_cm(request.height)),
             dob: Some(request.dob.parse().unwrap_or_else(|_| chrono::NaiveDate::from_ymd_opt(1970,1,1).unwrap())),
+            weight_unit: weight_unit.as_str(),
+            length_unit: length_unit.as_str(),
+            sex: request.sex.map(|s| s.as_str()),
         };
         match self.user.add_user(user).await{
             Ok(true) => {
                 info!("User registered successfully");
-                return AuthResponse{
+                Ok(AuthResponse{
                     username: request.username,
                     user_id: None,
                     success: true,
                     message: "User registered".to_string()
-                }
+                })
             }
             Ok(false) => {
                 info!("User already exists");
-                return AuthResponse{
-                    username: request.username,
-                    user_id: None,
-                    success: false,
-                    message: "Username already exists".to_string(),
-                }
+                Err(ApiError::Conflict("Username already exists".to_string()))
             },
             Err(err) => {
                 error!("Error during registration: {}",err);
-                return AuthResponse{
-                    username: request.username,
-                    user_id: None,
-                    success: false,
-                    message: "Couldn't register user".to_string()
-                }
+                Err(err)
             }
         }
     }
 
-    pub async fn forgot_password(&self, forgot_password: ForgotPasswordRequest) -> AuthResponse{
-        let username = forgot_password.username.clone();
-        let password = forgot_password.password;
-        let confirmpassword = forgot_password.confirmpassword;
-
-        if !password.eq(&confirmpassword){
-            return AuthResponse{
-                username: forgot_password.username,
-                user_id: None,
-                success: false,
-                message: "Passwords do not match".to_string(),
-            };
+    /// Always reports success so the endpoint can't be used to probe which emails are registered.
+    pub async fn forgot_password(&self, forgot_password: ForgotPasswordRequest) -> ApiResult<AuthResponse>{
+        let accepted = AuthResponse{
+            username: String::new(),
+            user_id: None,
+            success: true,
+            message: "If the email is registered, a reset link has been sent".to_string()
+        };
+
+        let user = match self.user.get_user_by_email(forgot_password.email.clone()).await{
+            Ok(Some(u)) => u,
+            Ok(None) => {
+                info!("Password reset requested for unknown email");
+                return Ok(accepted);
+            }
+            Err(err) => {
+                error!("Error looking up user by email: {}", err);
+                return Err(err);
+            }
+        };
+
+        let token = generate_token();
+        let reset_token = NewPasswordResetToken{
+            user_id: user.id,
+            token_hash: hash_token(&token),
+            expires_at: (Utc::now() + Duration::minutes(RESET_TOKEN_MINUTES)).naive_utc(),
+        };
+        // Failures past this point only happen for registered emails, so they get the same reply
+        if let Err(err) = self.tokens.add_reset_token(reset_token).await{
+            error!("Error storing reset token for user id {}: {}", user.id, err);
+            return Ok(accepted);
         }
-        match self.user.update_password(username, password).await{
-            Ok(_) => {
-                info!("Password updated successfully for user: {}", forgot_password.username);
-                AuthResponse{
-                    username: forgot_password.username,
-                    user_id: None,
-                    success: true,
-                    message: "Password updated".to_string()
-                }
+
+        let message = MailMessage{
+            to: user.email.clone(),
+            subject: "Reset your FitTrack password".to_string(),
+            body: format!("Hi {},\n\nUse the link below to set a new password. It expires in {} minutes and can only be used once.\n\n{}?token={}\n\nIf you didn't ask for this, you can ignore this email.",
+                user.fullname, RESET_TOKEN_MINUTES, self.reset_url, token),
+        };
+        if let Err(err) = self.mailer.send(&message){
+            error!("Error sending reset email for user id {}: {}", user.id, err);
+            return Ok(accepted);
+        }
+
+        info!("Password reset token issued for user id: {}", user.id);
+        Ok(accepted)
+    }
+
+    pub async fn reset_password(&self, reset: ResetPasswordRequest) -> ApiResult<AuthResponse>{
+        if !reset.password.eq(&reset.confirmpassword){
+            return Err(ApiError::Validation("Passwords do not match".to_string()));
+        }
+
+        let user_id = match self.tokens.consume_reset_token(&hash_token(&reset.token)).await{
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                info!("Invalid or expired reset token presented");
+                return Err(ApiError::BadRequest("Invalid or expired reset token".to_string()));
             }
             Err(err) => {
-                error!("Error updating password: {}", err);
-                return AuthResponse{
-                    username: forgot_password.username,
-                    user_id: None,
-                    success: false,
-                    message: "Couldn't update password".to_string()
-                }
+                error!("Error consuming reset token: {}", err);
+                return Err(err);
             }
-        }   
+        };
+
+        let username = match self.user.get_user_by_id(user_id).await{
+            Ok(Some(u)) => u.username,
+            Ok(None) => return Err(ApiError::BadRequest("Invalid or expired reset token".to_string())),
+            Err(err) => {
+                error!("Error fetching user for reset: {}", err);
+                return Err(err);
+            }
+        };
+
+        if let Err(err) = self.user.update_password(username.clone(), reset.password).await{
+            error!("Error updating password: {}", err);
+            return Err(err);
+        }
+        info!("Password reset successfully for user: {}", username);
+        if let Err(err) = self.tokens.revoke_user_refresh_tokens(user_id).await{
+            error!("Error revoking sessions after password reset: {}", err);
+        }
+        Ok(AuthResponse{
+            username,
+            user_id: None,
+            success: true,
+            message: "Password updated".to_string()
+        })
     }
 
-    pub async fn update_user_details(&self, user_id: i32, username: String, user: UpdateUserInfo) -> AuthResponse{
+    pub async fn update_user_details(&self, user_id: i32, username: String, user: UpdateUserInfo) -> ApiResult<AuthResponse>{
         info!("Updating user details for user id: {}", user_id);
         let hashed_password = if let Some(pass) = &user.password {
             let salt = SaltString::generate(&mut OsRng);
@@ -146,48 +190,70 @@ impl AuthService{
                 Ok(h) => Some(h.to_string()),
                 Err(e) => {
                     error!("Error hashing password: {}", e);
-                    return AuthResponse {
-                        username: username,
-                        user_id: Some(user_id),
-                        success: false,
-                        message: "Error updating password".to_string()
-                    };
+                    return Err(ApiError::Internal(format!("Error hashing password: {}", e)));
                 }
             }
         } else {
             None
         };
 
+        // Body measurements without a unit in the request are in the user's current preference
+        let units = match self.user.get_user_by_id(user_id).await?{
+            Some(u) => Units::of(&u),
+            None => return Err(ApiError::NotFound("User not found".to_string())),
+        };
         let userinfo = UpdateUser{
             fullname: user.fullname.as_deref(),
             email: user.email.as_deref(),
             password: hashed_password.as_deref(),
-            weight: user.weight,
-            height: user.height,
+            weight: user.weight.map(|w| units.weight_in(w, user.weight_unit)),
+            height: user.height.map(|h| units.length_in(h, user.length_unit)),
+            weight_unit: user.weight_unit.map(|u| u.as_str()),
+            length_unit: user.length_unit.map(|u| u.as_str()),
+            sex: user.sex.map(|s| s.as_str()),
             dob: match user.dob{
                 Some(dob_str) => Some(dob_str.parse().unwrap_or_else(|_| chrono::NaiveDate::from_ymd_opt(1970,1,1).unwrap())),
                 None => None,
             },
         };
-        match self.user.update_user_details(user_id, username.clone(), userinfo).await{
-            Ok(_) => {
-                info!("User details updated successfully for user id: {}", user_id);
-                AuthResponse{
-                    username,
-                    user_id: Some(user_id),
-                    success: true,
-                    message: "User details updated".to_string()
-                }
+        if let Err(err) = self.user.update_user_details(user_id, username.clone(), userinfo).await{
+            error!("Error updating user details: {}", err);
+            return Err(err);
+        }
+        info!("User details updated successfully for user id: {}", user_id);
+        Ok(AuthResponse{
+            username,
+            user_id: Some(user_id),
+            success: true,
+            message: "User details updated".to_string()
+        })
+    }
+
+    /// Re-checks the password of the signed-in user before destructive account changes.
+    pub async fn confirm_password(&self, user_id: i32, username: String, password: String) -> ApiResult<()>{
+        match self.user.verify_password(username, password).await?{
+            Some(id) if id == user_id => Ok(()),
+            _ => {
+                error!("Password confirmation failed for user id: {}", user_id);
+                Err(ApiError::Unauthorized("Invalid credentials".to_string()))
             }
+        }
+    }
+
+    /// Erases the account and everything it owns, leaving only the audit record.
+    pub async fn delete_account(&self, user_id: i32, exported: bool) -> ApiResult<AccountDeletion>{
+        info!("Deleting account for user id: {}", user_id);
+        match self.user.delete_user(user_id, generate_token(), exported).await{
+            Ok(Some(deletion)) => {
+                info!("Account {} deleted: {} sessions, {} sets, {} cardio logs, {} tokens revoked",
+                    user_id, deletion.sessions, deletion.sets, deletion.cardio_logs, deletion.tokens_revoked);
+                Ok(deletion)
+            }
+            Ok(None) => Err(ApiError::NotFound("User not found".to_string())),
             Err(err) => {
-                error!("Error updating user details: {}", err);
-                return AuthResponse{
-                    username,
-                    user_id: Some(user_id),
-                    success: false,
-                    message: "Couldn't update user details".to_string()
-                }
+                error!("Error deleting account for user id {}: {}", user_id, err);
+                Err(err)
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,18 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Random 256-bit token, hex encoded. Handed to the client once and only stored hashed.
+pub fn generate_token() -> String{
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+/// Tokens are high entropy, so an unsalted SHA-256 is enough and keeps them searchable.
+pub fn hash_token(token: &str) -> String{
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String{
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,615 @@
+use std::collections::{BTreeSet, HashMap};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+use crate::{api::validation::{MAX_MINUTES_PER_DAY, MAX_REPS, MAX_WEIGHT}, db::model::SetType, error::{ApiError, ApiResult}, services::units::{DistanceUnit, WeightUnit}};
+
+/// Rows that can't be read are skipped, but only this many are reported back.
+const MAX_WARNINGS: usize = 50;
+/// Names scoring below this against every existing exercise get a new one.
+pub const MATCH_THRESHOLD: f64 = 0.75;
+const DATE_TIME_FORMATS: [&str; 5] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%d %b %Y, %H:%M", "%d %b %Y %H:%M"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportSource{
+    Strong,
+    Hevy,
+    Fitnotes,
+}
+
+impl ImportSource{
+    /// Recognises the app from the CSV header, used when the client doesn't name one.
+    pub fn detect(headers: &StringRecord) -> Option<Self>{
+        let has = |name: &str| headers.iter().any(|h| h == name);
+        if has("Workout Name") && has("Exercise Name") {
+            Some(ImportSource::Strong)
+        } else if has("exercise_title") && has("start_time") {
+            Some(ImportSource::Hevy)
+        } else if has("Exercise") && has("Category") {
+            Some(ImportSource::Fitnotes)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str{
+        match self{
+            ImportSource::Strong => "Strong",
+            ImportSource::Hevy => "Hevy",
+            ImportSource::Fitnotes => "FitNotes",
+        }
+    }
+}
+
+/// Units assumed for columns whose unit the export doesn't state (Strong's weight and distance).
+#[derive(Debug, Clone, Copy)]
+pub struct ImportDefaults{
+    pub weight: WeightUnit,
+    pub distance: DistanceUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExerciseKind{
+    Strength,
+    Cardio,
+}
+
+#[derive(Debug)]
+pub struct ParsedSet{
+    pub exercise: String,
+    /// FitNotes category, used to pick a muscle group for new variations
+    pub category: Option<String>,
+    pub weight_kg: f64,
+    pub reps: i32,
+    pub set_type: SetType,
+    pub rpe: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct ParsedCardio{
+    pub exercise: String,
+    pub duration_minutes: i32,
+    pub distance_m: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct ParsedWorkout{
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub sets: Vec<ParsedSet>,
+    pub cardio: Vec<ParsedCardio>,
+}
+
+#[derive(Debug)]
+pub struct ParsedImport{
+    pub source: ImportSource,
+    /// In the order they first appear in the file
+    pub workouts: Vec<ParsedWorkout>,
+    pub skipped_rows: usize,
+    pub warnings: Vec<String>,
+}
+
+impl ParsedImport{
+    fn warn(&mut self, line: u64, message: &str){
+        self.skipped_rows += 1;
+        if self.warnings.len() < MAX_WARNINGS {
+            self.warnings.push(format!("line {}: {}", line, message));
+        }
+    }
+
+    /// Every distinct exercise name with its kind, sorted.
+    pub fn exercises(&self) -> BTreeSet<(ExerciseKind, &str)>{
+        let mut names = BTreeSet::new();
+        for workout in self.workouts.iter(){
+            names.extend(workout.sets.iter().map(|s| (ExerciseKind::Strength, s.exercise.as_str())));
+            names.extend(workout.cardio.iter().map(|c| (ExerciseKind::Cardio, c.exercise.as_str())));
+        }
+        names
+    }
+}
+
+/// Field lookup by header name, tolerant of missing columns and blank cells.
+struct Row<'a>{
+    columns: &'a HashMap<String, usize>,
+    record: &'a StringRecord,
+}
+
+impl Row<'_>{
+    fn text(&self, name: &str) -> Option<&str>{
+        self.columns.get(name)
+            .and_then(|i| self.record.get(*i))
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+    }
+
+    fn number(&self, name: &str) -> Option<f64>{
+        self.text(name).and_then(|v| v.replace(',', ".").parse().ok())
+    }
+}
+
+enum RowEntry{
+    Set(ParsedSet),
+    Cardio(ParsedCardio),
+}
+
+/// A row reduced to the workout it belongs to and what was done.
+struct RowData{
+    title: Option<String>,
+    notes: Option<String>,
+    start: NaiveDateTime,
+    end: Option<NaiveDateTime>,
+    entry: RowEntry,
+}
+
+pub fn parse(source: Option<ImportSource>, data: &[u8], defaults: &ImportDefaults) -> ApiResult<ParsedImport>{
+    let text = match std::str::from_utf8(data){
+        Ok(t) => t.trim_start_matches('\u{feff}'),
+        Err(_) => return Err(ApiError::BadRequest("File is not valid UTF-8".to_string())),
+    };
+    let first_line = text.lines().next().unwrap_or("");
+    let delimiter = if first_line.matches(';').count() > first_line.matches(',').count() { b';' } else { b',' };
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let headers = match reader.headers(){
+        Ok(h) => h.clone(),
+        Err(err) => return Err(ApiError::BadRequest(format!("Invalid CSV file: {}", err))),
+    };
+    let source = match source.or_else(|| ImportSource::detect(&headers)){
+        Some(s) => s,
+        None => return Err(ApiError::BadRequest("Unrecognised CSV, expected a Strong, Hevy or FitNotes export".to_string())),
+    };
+    let columns: HashMap<String, usize> = headers.iter().enumerate().map(|(i, h)| (h.trim().to_string(), i)).collect();
+
+    let mut parsed = ParsedImport { source, workouts: Vec::new(), skipped_rows: 0, warnings: Vec::new() };
+    let mut index: HashMap<(NaiveDateTime, Option<String>), usize> = HashMap::new();
+    for result in reader.records(){
+        let record = match result{
+            Ok(r) => r,
+            Err(err) => {
+                let line = err.position().map(|p| p.line()).unwrap_or(0);
+                parsed.warn(line, "malformed row");
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+        let row = Row { columns: &columns, record: &record };
+        let data = match source{
+            ImportSource::Strong => strong_row(&row, defaults),
+            ImportSource::Hevy => hevy_row(&row),
+            ImportSource::Fitnotes => fitnotes_row(&row),
+        };
+        let data = match data{
+            Ok(Some(d)) => d,
+            Ok(None) => continue,
+            Err(message) => {
+                parsed.warn(line, &message);
+                continue;
+            }
+        };
+
+        let key = (data.start, data.title.clone());
+        let i = *index.entry(key).or_insert_with(|| {
+            parsed.workouts.push(ParsedWorkout {
+                title: data.title,
+                notes: data.notes,
+                start: data.start,
+                end: data.start,
+                sets: Vec::new(),
+                cardio: Vec::new(),
+            });
+            parsed.workouts.len() - 1
+        });
+        let workout = &mut parsed.workouts[i];
+        if let Some(end) = data.end {
+            workout.end = workout.end.max(end);
+        }
+        match data.entry{
+            RowEntry::Set(set) => workout.sets.push(set),
+            RowEntry::Cardio(cardio) => workout.cardio.push(cardio),
+        }
+    }
+
+    // FitNotes only records dates, so the session spans the cardio done that day
+    if source == ImportSource::Fitnotes {
+        for workout in parsed.workouts.iter_mut(){
+            let minutes: i64 = workout.cardio.iter().map(|c| c.duration_minutes as i64).sum();
+            workout.end = workout.start + Duration::minutes(minutes);
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_date_time(text: &str) -> Option<NaiveDateTime>{
+    DATE_TIME_FORMATS.iter().find_map(|f| NaiveDateTime::parse_from_str(text, f).ok())
+}
+
+/// "1h 5m", "45m" or "30s" as written by Strong.
+fn parse_strong_duration(text: &str) -> Option<i64>{
+    let mut seconds = 0;
+    for part in text.split_whitespace(){
+        let (value, unit) = part.split_at(part.find(|c: char| !c.is_ascii_digit())?);
+        let value: i64 = value.parse().ok()?;
+        seconds += match unit{
+            "h" => value * 3600,
+            "m" | "min" => value * 60,
+            "s" => value,
+            _ => return None,
+        };
+    }
+    Some(seconds)
+}
+
+/// "H:MM:SS" or "MM:SS" as written by FitNotes.
+fn parse_clock(text: &str) -> Option<i64>{
+    let parts: Vec<i64> = text.split(':').map(|p| p.trim().parse().ok()).collect::<Option<_>>()?;
+    match parts.as_slice(){
+        [h, m, s] => Some(h * 3600 + m * 60 + s),
+        [m, s] => Some(m * 60 + s),
+        _ => None,
+    }
+}
+
+fn set_entry(exercise: &str, category: Option<&str>, weight_kg: f64, reps: f64, set_type: SetType, rpe: Option<f64>) -> Result<RowEntry, String>{
+    if !(0.0..=MAX_WEIGHT).contains(&weight_kg) {
+        return Err(format!("weight must be between 0 and {} kg", MAX_WEIGHT));
+    }
+    if reps < 1.0 || reps > MAX_REPS as f64 {
+        return Err(format!("reps must be between 1 and {}", MAX_REPS));
+    }
+    Ok(RowEntry::Set(ParsedSet {
+        exercise: exercise.to_string(),
+        category: category.map(|c| c.to_string()),
+        weight_kg,
+        reps: reps.round() as i32,
+        set_type,
+        rpe: rpe.filter(|r| (1.0..=10.0).contains(r)),
+    }))
+}
+
+fn cardio_entry(exercise: &str, seconds: i64, distance_m: Option<f64>) -> Result<RowEntry, String>{
+    let minutes = ((seconds + 30) / 60).max(1);
+    if minutes > MAX_MINUTES_PER_DAY as i64 {
+        return Err("activity can't last longer than 24 hours".to_string());
+    }
+    Ok(RowEntry::Cardio(ParsedCardio {
+        exercise: exercise.to_string(),
+        duration_minutes: minutes as i32,
+        distance_m: distance_m.filter(|d| *d > 0.0),
+    }))
+}
+
+/// The columns every app's row is reduced to before it becomes a set or cardio entry.
+struct RowValues<'a>{
+    exercise: &'a str,
+    category: Option<&'a str>,
+    weight_kg: f64,
+    reps: Option<f64>,
+    seconds: Option<i64>,
+    distance_m: Option<f64>,
+    set_type: SetType,
+    rpe: Option<f64>,
+}
+
+impl RowValues<'_>{
+    /// Rows with reps are sets; rows with only time or distance are cardio.
+    fn entry(self) -> Result<Option<RowEntry>, String>{
+        match self.reps.filter(|r| *r > 0.0){
+            Some(reps) => set_entry(self.exercise, self.category, self.weight_kg, reps, self.set_type, self.rpe).map(Some),
+            None => match (self.seconds.filter(|s| *s > 0), self.distance_m.filter(|d| *d > 0.0)){
+                (None, None) => Ok(None),
+                (seconds, distance) => cardio_entry(self.exercise, seconds.unwrap_or(0), distance).map(Some),
+            },
+        }
+    }
+}
+
+fn strong_row(row: &Row, defaults: &ImportDefaults) -> Result<Option<RowData>, String>{
+    let start = row.text("Date").and_then(parse_date_time).ok_or("missing or invalid Date")?;
+    let exercise = row.text("Exercise Name").ok_or("missing Exercise Name")?;
+    let set_type = match row.text("Set Order"){
+        Some("W") => SetType::Warmup,
+        Some("D") => SetType::Drop,
+        Some("F") => SetType::Failure,
+        Some(order) if order.parse::<i32>().is_ok() => SetType::Working,
+        // Rest timer and note rows
+        _ => return Ok(None),
+    };
+    let entry = RowValues {
+        exercise,
+        category: None,
+        weight_kg: defaults.weight.to_kg(row.number("Weight").unwrap_or(0.0)),
+        reps: row.number("Reps"),
+        seconds: row.number("Seconds").map(|s| s as i64),
+        distance_m: row.number("Distance").map(|d| defaults.distance.to_m(d)),
+        set_type,
+        rpe: row.number("RPE"),
+    }.entry()?;
+    Ok(entry.map(|entry| RowData {
+        title: row.text("Workout Name").map(|t| t.to_string()),
+        notes: row.text("Workout Notes").map(|n| n.to_string()),
+        start,
+        end: row.text("Duration").and_then(parse_strong_duration).map(|s| start + Duration::seconds(s)),
+        entry,
+    }))
+}
+
+fn hevy_row(row: &Row) -> Result<Option<RowData>, String>{
+    let start = row.text("start_time").and_then(parse_date_time).ok_or("missing or invalid start_time")?;
+    let exercise = row.text("exercise_title").ok_or("missing exercise_title")?;
+    let set_type = match row.text("set_type"){
+        Some("warmup") => SetType::Warmup,
+        Some("dropset") => SetType::Drop,
+        Some("failure") => SetType::Failure,
+        _ => SetType::Working,
+    };
+    let weight_kg = match (row.number("weight_kg"), row.number("weight_lbs")){
+        (Some(kg), _) => kg,
+        (None, Some(lbs)) => WeightUnit::Lb.to_kg(lbs),
+        (None, None) => 0.0,
+    };
+    let distance_m = match (row.number("distance_km"), row.number("distance_miles")){
+        (Some(km), _) => Some(DistanceUnit::Km.to_m(km)),
+        (None, Some(mi)) => Some(DistanceUnit::Mi.to_m(mi)),
+        (None, None) => None,
+    };
+    let entry = RowValues {
+        exercise,
+        category: None,
+        weight_kg,
+        reps: row.number("reps"),
+        seconds: row.number("duration_seconds").map(|s| s as i64),
+        distance_m,
+        set_type,
+        rpe: row.number("rpe"),
+    }.entry()?;
+    Ok(entry.map(|entry| RowData {
+        title: row.text("title").map(|t| t.to_string()),
+        notes: row.text("description").map(|n| n.to_string()),
+        start,
+        end: row.text("end_time").and_then(parse_date_time),
+        entry,
+    }))
+}
+
+fn fitnotes_row(row: &Row) -> Result<Option<RowData>, String>{
+    let date = row.text("Date").and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()).ok_or("missing or invalid Date")?;
+    let exercise = row.text("Exercise").ok_or("missing Exercise")?;
+    let category = row.text("Category");
+    let weight_kg = match (row.number("Weight (kgs)"), row.number("Weight (lbs)")){
+        (Some(kg), _) => kg,
+        (None, Some(lbs)) => WeightUnit::Lb.to_kg(lbs),
+        (None, None) => 0.0,
+    };
+    let distance_m = match (row.number("Distance"), row.text("Distance Unit")){
+        (Some(d), Some("m")) => Some(d),
+        (Some(d), Some("mi") | Some("miles")) => Some(DistanceUnit::Mi.to_m(d)),
+        (Some(d), Some("ft")) => Some(d * 0.3048),
+        (Some(d), _) => Some(DistanceUnit::Km.to_m(d)),
+        (None, _) => None,
+    };
+    let reps = if category.is_some_and(|c| c.eq_ignore_ascii_case("cardio")) { None } else { row.number("Reps") };
+    let entry = RowValues {
+        exercise,
+        category,
+        weight_kg,
+        reps,
+        seconds: row.text("Time").and_then(parse_clock),
+        distance_m,
+        set_type: SetType::Working,
+        rpe: None,
+    }.entry()?;
+    Ok(entry.map(|entry| RowData {
+        title: None,
+        notes: None,
+        start: date.and_time(NaiveTime::MIN),
+        end: None,
+        entry,
+    }))
+}
+
+/// Lowercased words with punctuation dropped, plurals folded and sorted, so "Bench Press (Barbell)"
+/// and "barbell bench press" compare equal.
+pub fn normalize(name: &str) -> String{
+    let lowered = name.to_lowercase();
+    let mut words: Vec<String> = lowered
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| match w.strip_suffix('s'){
+            Some(stem) if stem.len() > 2 && !stem.ends_with('s') => stem.to_string(),
+            _ => w.to_string(),
+        })
+        .collect();
+    words.sort();
+    words.dedup();
+    words.join(" ")
+}
+
+fn bigrams(text: &str) -> Vec<(char, char)>{
+    let chars: Vec<char> = text.chars().collect();
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Dice coefficient over character bigrams of the normalized names, from 0 to 1.
+pub fn similarity(a: &str, b: &str) -> f64{
+    let (a, b) = (normalize(a), normalize(b));
+    if a == b {
+        return 1.0;
+    }
+    let (a, b) = (bigrams(&a), bigrams(&b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let mut remaining = b.clone();
+    let mut shared = 0;
+    for pair in a.iter(){
+        if let Some(i) = remaining.iter().position(|p| p == pair) {
+            remaining.swap_remove(i);
+            shared += 1;
+        }
+    }
+    2.0 * shared as f64 / (a.len() + b.len()) as f64
+}
+
+/// The closest candidate at or above [MATCH_THRESHOLD], with its score.
+pub fn best_match<'a>(name: &str, candidates: impl Iterator<Item = (i32, &'a str)>) -> Option<(i32, &'a str, f64)>{
+    candidates
+        .map(|(id, candidate)| (id, candidate, similarity(name, candidate)))
+        .filter(|(_, _, score)| *score >= MATCH_THRESHOLD)
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const METRIC: ImportDefaults = ImportDefaults { weight: WeightUnit::Kg, distance: DistanceUnit::Km };
+    const IMPERIAL: ImportDefaults = ImportDefaults { weight: WeightUnit::Lb, distance: DistanceUnit::Mi };
+
+    const STRONG: &str = "\u{feff}Date;Workout Name;Duration;Exercise Name;Set Order;Weight;Reps;Distance;Seconds;Notes;Workout Notes;RPE
+2024-01-02 18:00:00;Push;1h 5m;Bench Press (Barbell);W;60;10;0;0;;Felt good;
+2024-01-02 18:00:00;Push;1h 5m;Bench Press (Barbell);1;135;5;0;0;;Felt good;8
+2024-01-02 18:00:00;Push;1h 5m;Bench Press (Barbell);Rest Timer;0;0;0;90;;Felt good;
+2024-01-02 18:00:00;Push;1h 5m;Bench Press (Barbell);D;95;8;0;0;;Felt good;
+2024-01-02 18:00:00;Push;1h 5m;Running;1;0;0;3;1200;;Felt good;
+";
+
+    const HEVY: &str = "title,start_time,end_time,description,exercise_title,superset_id,exercise_notes,set_index,set_type,weight_kg,reps,distance_km,duration_seconds,rpe
+Legs,\"2 Jan 2024, 18:00\",\"2 Jan 2024, 19:10\",,Squat (Barbell),,,0,warmup,40,10,,,
+Legs,\"2 Jan 2024, 18:00\",\"2 Jan 2024, 19:10\",,Squat (Barbell),,,1,normal,100,5,,,9
+Legs,\"2 Jan 2024, 18:00\",\"2 Jan 2024, 19:10\",,Rowing Machine,,,0,normal,,,2,600,
+";
+
+    const FITNOTES: &str = "Date,Exercise,Category,Weight (kgs),Reps,Distance,Distance Unit,Time
+2024-01-03,Deadlift,Back,140,3,,,
+2024-01-03,Cycling,Cardio,,,10,km,0:30:00
+2024-01-04,Deadlift,Back,145,3,,,
+";
+
+    fn headers(line: &str, delimiter: u8) -> StringRecord{
+        csv::ReaderBuilder::new().delimiter(delimiter).from_reader(line.as_bytes()).headers().unwrap().clone()
+    }
+
+    fn at(date: &str) -> NaiveDateTime{
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn detects_each_app_from_its_header(){
+        assert_eq!(ImportSource::detect(&headers(STRONG.trim_start_matches('\u{feff}'), b';')), Some(ImportSource::Strong));
+        assert_eq!(ImportSource::detect(&headers(HEVY, b',')), Some(ImportSource::Hevy));
+        assert_eq!(ImportSource::detect(&headers(FITNOTES, b',')), Some(ImportSource::Fitnotes));
+        assert_eq!(ImportSource::detect(&headers("date,weight,reps\n", b',')), None);
+        assert!(parse(None, b"date,weight,reps\n2024-01-02,100,5\n", &METRIC).is_err());
+    }
+
+    #[test]
+    fn parses_dates_and_durations(){
+        assert_eq!(parse_date_time("2024-01-02 18:00:00"), Some(at("2024-01-02 18:00")));
+        assert_eq!(parse_date_time("2024-01-02T18:00:00"), Some(at("2024-01-02 18:00")));
+        assert_eq!(parse_date_time("2 Jan 2024, 18:00"), Some(at("2024-01-02 18:00")));
+        assert_eq!(parse_date_time("02/01/2024"), None);
+
+        assert_eq!(parse_strong_duration("1h 5m"), Some(3900));
+        assert_eq!(parse_strong_duration("45m"), Some(2700));
+        assert_eq!(parse_strong_duration("30s"), Some(30));
+        assert_eq!(parse_strong_duration("1 hour"), None);
+
+        assert_eq!(parse_clock("0:30:00"), Some(1800));
+        assert_eq!(parse_clock("12:30"), Some(750));
+        assert_eq!(parse_clock("30"), None);
+    }
+
+    #[test]
+    fn parses_strong_export_in_default_units(){
+        let parsed = parse(None, STRONG.as_bytes(), &IMPERIAL).unwrap();
+        assert_eq!(parsed.source, ImportSource::Strong);
+        assert_eq!(parsed.skipped_rows, 0);
+        assert_eq!(parsed.workouts.len(), 1);
+
+        let workout = &parsed.workouts[0];
+        assert_eq!(workout.title.as_deref(), Some("Push"));
+        assert_eq!(workout.notes.as_deref(), Some("Felt good"));
+        assert_eq!(workout.start, at("2024-01-02 18:00"));
+        assert_eq!(workout.end, at("2024-01-02 19:05"));
+
+        // The rest timer row is dropped, the rest keep their set type
+        let types: Vec<SetType> = workout.sets.iter().map(|s| s.set_type).collect();
+        assert_eq!(types, vec![SetType::Warmup, SetType::Working, SetType::Drop]);
+        assert!((workout.sets[1].weight_kg - 61.235).abs() < 0.01);
+        assert_eq!(workout.sets[1].reps, 5);
+        assert_eq!(workout.sets[1].rpe, Some(8.0));
+
+        assert_eq!(workout.cardio.len(), 1);
+        assert_eq!(workout.cardio[0].duration_minutes, 20);
+        assert!((workout.cardio[0].distance_m.unwrap() - 4828.03).abs() < 0.1);
+
+        let metric = parse(Some(ImportSource::Strong), STRONG.as_bytes(), &METRIC).unwrap();
+        assert_eq!(metric.workouts[0].sets[1].weight_kg, 135.0);
+        assert_eq!(metric.workouts[0].cardio[0].distance_m, Some(3000.0));
+    }
+
+    #[test]
+    fn parses_hevy_export_ignoring_default_units(){
+        let parsed = parse(None, HEVY.as_bytes(), &IMPERIAL).unwrap();
+        assert_eq!(parsed.source, ImportSource::Hevy);
+        let workout = &parsed.workouts[0];
+        assert_eq!(workout.title.as_deref(), Some("Legs"));
+        assert_eq!(workout.end, at("2024-01-02 19:10"));
+        assert_eq!(workout.sets.len(), 2);
+        assert_eq!(workout.sets[0].set_type, SetType::Warmup);
+        assert_eq!(workout.sets[1].weight_kg, 100.0);
+        assert_eq!(workout.sets[1].rpe, Some(9.0));
+        assert_eq!(workout.cardio[0].exercise, "Rowing Machine");
+        assert_eq!(workout.cardio[0].duration_minutes, 10);
+        assert_eq!(workout.cardio[0].distance_m, Some(2000.0));
+    }
+
+    #[test]
+    fn parses_fitnotes_export_into_one_workout_per_day(){
+        let parsed = parse(None, FITNOTES.as_bytes(), &METRIC).unwrap();
+        assert_eq!(parsed.source, ImportSource::Fitnotes);
+        assert_eq!(parsed.workouts.len(), 2);
+
+        let first = &parsed.workouts[0];
+        assert_eq!(first.start, at("2024-01-03 00:00"));
+        assert_eq!(first.end, at("2024-01-03 00:30"));
+        assert_eq!(first.sets[0].category.as_deref(), Some("Back"));
+        assert_eq!(first.sets[0].weight_kg, 140.0);
+        assert_eq!(first.cardio[0].duration_minutes, 30);
+        assert_eq!(first.cardio[0].distance_m, Some(10000.0));
+        assert_eq!(parsed.workouts[1].end, parsed.workouts[1].start);
+    }
+
+    #[test]
+    fn skips_invalid_rows_with_a_warning(){
+        let data = "Date,Exercise,Category,Weight (kgs),Reps,Distance,Distance Unit,Time
+2024-01-03,Deadlift,Back,140,3,,,
+yesterday,Deadlift,Back,140,3,,,
+2024-01-03,Deadlift,Back,9999,3,,,
+";
+        let parsed = parse(Some(ImportSource::Fitnotes), data.as_bytes(), &METRIC).unwrap();
+        assert_eq!(parsed.workouts[0].sets.len(), 1);
+        assert_eq!(parsed.skipped_rows, 2);
+        assert!(parsed.warnings[0].starts_with("line 3:"));
+    }
+
+    #[test]
+    fn matches_names_above_threshold(){
+        let variations = [(1, "Barbell Bench Press"), (2, "Squat"), (3, "Romanian Deadlift")];
+        let matched = |name: &str| best_match(name, variations.iter().copied()).map(|(id, _, _)| id);
+
+        assert_eq!(matched("Bench Press (Barbell)"), Some(1));
+        assert_eq!(matched("Squats"), Some(2));
+        assert_eq!(matched("Romanian Deadlifts"), Some(3));
+        assert_eq!(matched("Deadlift"), None);
+        assert_eq!(matched("Overhead Press"), None);
+        assert!(similarity("Incline Bench Press", "Bench Press") < MATCH_THRESHOLD);
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use crate::{api::validation::MAX_MINUTES_PER_DAY, db::model::{CardioExercise, CardioLog, WorkoutSession}};
+
+/// Metabolic equivalent used for session time not spent on cardio.
+pub const STRENGTH_MET: f64 = 5.0;
+/// Matches the `cardio_exercises.met` column default.
+pub const DEFAULT_CARDIO_MET: f64 = 7.0;
+
+/// MET values of the cardio exercises visible to a user, keyed by exercise id.
+pub struct MetTable(HashMap<i32, f64>);
+
+impl MetTable{
+    pub fn new(exercises: &[CardioExercise]) -> Self{
+        MetTable(exercises.iter().map(|e| (e.id, e.met)).collect())
+    }
+
+    pub fn cardio(&self, cardio_exercise_id: i32) -> f64{
+        self.0.get(&cardio_exercise_id).copied().unwrap_or(DEFAULT_CARDIO_MET)
+    }
+}
+
+/// kcal = MET × body weight in kg × hours.
+pub fn kcal(met: f64, kg: f64, minutes: f64) -> f64{
+    met * kg * minutes / 60.0
+}
+
+/// Logged calories win over the MET estimate.
+pub fn cardio_kcal(log: &CardioLog, mets: &MetTable, kg: f64) -> f64{
+    match log.calories{
+        Some(calories) => calories as f64,
+        None => kcal(mets.cardio(log.cardio_exercise_id), kg, log.duration_minutes as f64),
+    }
+}
+
+/// Session time left once the cardio logged inside it is taken out.
+pub fn strength_minutes(session: &WorkoutSession, cardio_minutes: i64) -> i64{
+    let total = (session.end_time - session.start_time).num_minutes().clamp(0, MAX_MINUTES_PER_DAY as i64);
+    (total - cardio_minutes).max(0)
+}
+
+/// Estimated kcal burned in a session: its completed cardio plus the remaining time as strength work.
+pub fn session_kcal(session: &WorkoutSession, cardio: &[CardioLog], mets: &MetTable, kg: f64) -> f64{
+    let completed: Vec<&CardioLog> = cardio.iter()
+        .filter(|c| c.completed && c.workout_session_id == Some(session.id))
+        .collect();
+    let cardio_minutes: i64 = completed.iter().map(|c| c.duration_minutes as i64).sum();
+    let strength = kcal(STRENGTH_MET, kg, strength_minutes(session, cardio_minutes) as f64);
+    strength + completed.iter().map(|c| cardio_kcal(c, mets, kg)).sum::<f64>()
+}
@@ -0,0 +1,175 @@
+use std::{collections::VecDeque, io::Write, sync::{Arc, Mutex}};
+use actix_web::web::Bytes;
+use futures_util::{stream, Stream};
+use log::{error, info};
+use zip::{write::{SimpleFileOptions, StreamWriter}, CompressionMethod, ZipWriter};
+use crate::{db::export::{ExportDB, ExportTable}, error::{ApiError, ApiResult}};
+
+/// Rows fetched per query, so only one page of a table is ever held in memory.
+const PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat{
+    Csv,
+    Json,
+}
+
+impl ExportFormat{
+    fn path(&self, table: ExportTable) -> String{
+        match self{
+            ExportFormat::Csv => format!("csv/{}.csv", table.name()),
+            ExportFormat::Json => format!("json/{}.json", table.name()),
+        }
+    }
+}
+
+/// Archive bytes written by the zip writer and not yet sent to the client.
+#[derive(Clone, Default)]
+struct Pending(Arc<Mutex<Vec<u8>>>);
+
+impl Pending{
+    fn take(&self) -> Bytes{
+        match self.0.lock(){
+            Ok(mut buf) => Bytes::from(std::mem::take(&mut *buf)),
+            Err(_) => Bytes::new(),
+        }
+    }
+}
+
+impl Write for Pending{
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize>{
+        match self.0.lock(){
+            Ok(mut buf) => {
+                buf.extend_from_slice(data);
+                Ok(data.len())
+            }
+            Err(_) => Err(std::io::Error::other("export buffer poisoned")),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()>{
+        Ok(())
+    }
+}
+
+/// The archive entry being written and where its next page starts.
+struct Entry{
+    table: ExportTable,
+    format: ExportFormat,
+    after_id: i32,
+    first_page: bool,
+}
+
+struct ExportState{
+    export: Arc<ExportDB>,
+    user_id: i32,
+    zip: Option<ZipWriter<StreamWriter<Pending>>>,
+    pending: Pending,
+    queue: VecDeque<(ExportTable, ExportFormat)>,
+    current: Option<Entry>,
+}
+
+fn zip_error(err: impl std::fmt::Display) -> ApiError{
+    ApiError::Internal(format!("Error writing export archive: {}", err))
+}
+
+impl ExportState{
+    /// Moves the archive forward by one step: opening an entry, writing one page or finishing.
+    /// Returns false once the archive is complete.
+    async fn advance(&mut self) -> ApiResult<bool>{
+        let zip = match self.zip.as_mut(){
+            Some(z) => z,
+            None => return Ok(false),
+        };
+        let entry = match self.current.as_mut(){
+            Some(e) => e,
+            None => {
+                match self.queue.pop_front(){
+                    Some((table, format)) => {
+                        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+                        zip.start_file(format.path(table), options).map_err(zip_error)?;
+                        if format == ExportFormat::Json {
+                            zip.write_all(b"[\n").map_err(zip_error)?;
+                        }
+                        self.current = Some(Entry { table, format, after_id: 0, first_page: true });
+                    }
+                    None => {
+                        if let Some(zip) = self.zip.take() {
+                            zip.finish().map_err(zip_error)?;
+                        }
+                    }
+                }
+                return Ok(true);
+            }
+        };
+
+        let rows = self.export.get_page(self.user_id, entry.table, entry.after_id, PAGE_SIZE).await?;
+        match entry.format{
+            ExportFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(entry.first_page)
+                    .from_writer(&mut *zip);
+                rows.write_csv(&mut writer).map_err(zip_error)?;
+            }
+            ExportFormat::Json => rows.write_json(zip, entry.first_page).map_err(zip_error)?,
+        }
+        entry.first_page = entry.first_page && rows.is_empty();
+
+        if (rows.len() as i64) < PAGE_SIZE {
+            if entry.format == ExportFormat::Json {
+                zip.write_all(b"\n]\n").map_err(zip_error)?;
+            }
+            self.current = None;
+        } else if let Some(id) = rows.last_id() {
+            entry.after_id = id;
+        }
+        Ok(true)
+    }
+}
+
+pub struct ExportService{
+    export: Arc<ExportDB>,
+}
+
+impl ExportService{
+    pub fn new(export: Arc<ExportDB>) -> Self{
+        ExportService { export }
+    }
+
+    /// Zip archive of every table in both CSV and JSON, produced page by page as the client reads
+    /// it. Values are in storage units: kg, cm and metres.
+    pub fn export(&self, user_id: i32) -> impl Stream<Item = ApiResult<Bytes>> + 'static{
+        info!("Exporting data for user_id: {}", user_id);
+        let pending = Pending::default();
+        let queue = ExportTable::ALL.iter()
+            .flat_map(|t| [(*t, ExportFormat::Csv), (*t, ExportFormat::Json)])
+            .collect();
+        let state = ExportState {
+            export: self.export.clone(),
+            user_id,
+            zip: Some(ZipWriter::new_stream(pending.clone())),
+            pending,
+            queue,
+            current: None,
+        };
+
+        stream::try_unfold(state, |mut state| async move {
+            loop {
+                let more = match state.advance().await{
+                    Ok(more) => more,
+                    Err(err) => {
+                        error!("Export failed for user_id {}: {}", state.user_id, err);
+                        return Err(err);
+                    }
+                };
+                let chunk = state.pending.take();
+                if !chunk.is_empty() {
+                    return Ok(Some((chunk, state)));
+                }
+                if !more {
+                    return Ok(None);
+                }
+            }
+        })
+    }
+}
@@ -1,9 +1,8 @@
-use std::{collections::HashMap, sync::Arc};
-use anyhow::{Result, bail};
-use chrono::{Datelike, NaiveDate};
+use std::{collections::{BTreeMap, HashMap}, sync::Arc};
+use chrono::{Datelike, DateTime, NaiveDate, NaiveDateTime};
 use log::{debug, error, info, warn};
-use serde::Serialize;
-use crate::db::{model::User, user::UserDB, workouts::WorkoutDB};
+use serde::{Deserialize, Serialize};
+use crate::{api::workouts::CardioLogDetails, db::{measurements::MeasurementDB, model::{BodyMeasurement, TrackPoint, User, WorkoutSession, WorkoutSet}, user::UserDB, workouts::{HistoryFilter, WorkoutDB}}, error::{ApiError, ApiResult}, services::{energy::{self, MetTable}, units::{DistanceUnit, Units}}};
 
 
 const LEVEL_1:i64 = 30;
@@ -29,20 +28,210 @@ pub struct MuscleGroupVolume {
     pub total_sets: i64,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OneRepMaxFormula{
+    #[default]
+    Epley,
+    Brzycki,
+    Lombardi,
+}
+
+impl OneRepMaxFormula{
+    pub fn estimate(&self, weight: f64, reps: i32) -> f64{
+        if reps <= 0 {
+            return 0.0;
+        }
+        if reps == 1 {
+            return weight;
+        }
+        let reps_f = reps as f64;
+        match self{
+            OneRepMaxFormula::Epley => weight * (1.0 + reps_f / 30.0),
+            // Brzycki breaks down past 36 reps, clamp so the denominator stays positive
+            OneRepMaxFormula::Brzycki => weight * 36.0 / (37.0 - reps_f.min(36.0)),
+            OneRepMaxFormula::Lombardi => weight * reps_f.powf(0.1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket{
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+/// A single series of the body measurement log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyMetric{
+    Weight,
+    BodyFat,
+    Neck,
+    Chest,
+    Waist,
+    Hips,
+    Arms,
+    Thighs,
+}
+
+impl BodyMetric{
+    pub fn value(&self, m: &BodyMeasurement) -> Option<f64>{
+        match self{
+            BodyMetric::Weight => m.weight,
+            BodyMetric::BodyFat => m.body_fat_percent,
+            BodyMetric::Neck => m.neck,
+            BodyMetric::Chest => m.chest,
+            BodyMetric::Waist => m.waist,
+            BodyMetric::Hips => m.hips,
+            BodyMetric::Arms => m.arms,
+            BodyMetric::Thighs => m.thighs,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrendPoint{
+    pub date: NaiveDate,
+    /// Mean of the readings taken that day
+    pub value: f64,
+    /// Mean of the daily values over the trailing window ending on this day
+    pub moving_average: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MeasurementTrend{
+    pub metric: BodyMetric,
+    /// kg, lb, cm, in or %
+    pub unit: &'static str,
+    pub window_days: i64,
+    pub points: Vec<TrendPoint>,
+    /// Last moving average minus the first one in the range
+    pub change: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OneRepMaxPoint{
+    pub label: String,
+    pub date: NaiveDate,
+    pub e1rm: f64,
+    pub weight: f64,
+    pub reps: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CardioPeriod{
+    pub label: String,
+    pub sessions: i64,
+    pub total_minutes: i64,
+    pub total_distance: f64,
+    /// Seconds per km or mile, only from logs that recorded a distance
+    pub avg_pace_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CardioSummary{
+    pub cardio_exercise_id: i32,
+    pub distance_unit: DistanceUnit,
+    pub periods: Vec<CardioPeriod>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OneRepMaxMetrics{
+    pub variation_id: i32,
+    pub formula: OneRepMaxFormula,
+    pub points: Vec<OneRepMaxPoint>,
+    pub all_time_best: Option<OneRepMaxPoint>,
+}
+
+impl OneRepMaxPoint{
+    fn converted(mut self, units: &Units) -> Self{
+        self.e1rm = units.weight_out(self.e1rm);
+        self.weight = units.weight_out(self.weight);
+        self
+    }
+}
+
+/// A history entry with its set and cardio counts and estimated energy use, the latter left empty
+/// when the user has no body weight on record.
+#[derive(Debug, Serialize)]
+pub struct SessionSummary{
+    #[serde(flatten)]
+    pub session: WorkoutSession,
+    pub set_count: i64,
+    pub cardio_count: i64,
+    pub estimated_calories: Option<f64>,
+}
+
+/// One page of history. `next_cursor` is passed back as `cursor` for the following page and is
+/// empty on the last one.
+#[derive(Debug, Serialize)]
+pub struct HistoryPage{
+    pub sessions: Vec<SessionSummary>,
+    pub next_cursor: Option<String>,
+}
+
+/// A GPS track point. The `_m` fields stay in metres; `distance` and `elevation` repeat them in the
+/// units named by the enclosing [TrackPoints].
+#[derive(Debug, Serialize)]
+pub struct TrackPointDetails{
+    #[serde(flatten)]
+    pub point: TrackPoint,
+    pub distance: Option<f64>,
+    pub elevation: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackPoints{
+    pub distance_unit: DistanceUnit,
+    /// `m` or `ft`
+    pub elevation_unit: &'static str,
+    pub points: Vec<TrackPointDetails>,
+}
+
+/// Position in the history, the `(start_time, id)` of the last session on a page. Sent to
+/// clients as `<start time in microseconds>_<id>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryCursor{
+    pub start_time: NaiveDateTime,
+    pub id: i32,
+}
+
+impl HistoryCursor{
+    pub fn of(session: &WorkoutSession) -> Self{
+        HistoryCursor { start_time: session.start_time, id: session.id }
+    }
+
+    pub fn parse(cursor: &str) -> Option<Self>{
+        let (micros, id) = cursor.split_once('_')?;
+        let start_time = DateTime::from_timestamp_micros(micros.parse().ok()?)?.naive_utc();
+        Some(HistoryCursor { start_time, id: id.parse().ok()? })
+    }
+
+    pub fn encode(&self) -> String{
+        format!("{}_{}", self.start_time.and_utc().timestamp_micros(), self.id)
+    }
+}
+
 pub struct GetService{
     pub workout: Arc<WorkoutDB>,
-    pub user: Arc<UserDB>
+    pub user: Arc<UserDB>,
+    pub measurements: Arc<MeasurementDB>,
 }
 
 impl GetService{
-    pub fn new(workout: Arc<WorkoutDB>, user: Arc<UserDB>) -> Self{
+    pub fn new(workout: Arc<WorkoutDB>, user: Arc<UserDB>, measurements: Arc<MeasurementDB>) -> Self{
         GetService { 
             workout,
-            user
+            user,
+            measurements
         }
     }
 
-    pub async fn get_workout_levels(&self, user_id: i32, year: i32, month: i32) -> Result<Vec<WorkoutLevels>,>{
+    pub async fn get_workout_levels(&self, user_id: i32, year: i32, month: i32) -> ApiResult<Vec<WorkoutLevels>>{
         let workout_details = match self.workout.get_monthly_workout_details(user_id, year, month).await{
             Ok(details) => details,
             Err(err) => {
@@ -90,10 +279,11 @@ impl GetService{
         Ok(workout_levels)
     }
 
-    pub async fn get_performance_details(&self, user_id: i32, variation_id: i32, start_date: NaiveDate, end_date: NaiveDate)-> Result<Vec<PerformanceMetrics>,> {
+    pub async fn get_performance_details(&self, user_id: i32, variation_id: i32, start_date: NaiveDate, end_date: NaiveDate)-> ApiResult<Vec<PerformanceMetrics>> {
+        let units = self.get_units(user_id).await?;
         let performance_data = match self.workout.get_performance_details(user_id, variation_id, start_date, end_date).await{
             Ok(data) => data,
-            Err(err) => bail!(err)
+            Err(err) => return Err(err)
         };
 
         // 1. Generate chronological list of weeks
@@ -123,7 +313,7 @@ impl GetService{
         let performance_metrics = weeks_order.into_iter()
             .map(|week| PerformanceMetrics{
                 week: week.clone(),
-                volume: *performance_map.get(&week).unwrap_or(&0.0)
+                volume: units.weight_out(*performance_map.get(&week).unwrap_or(&0.0))
             })
             .collect::<Vec<PerformanceMetrics>>();
 
@@ -132,15 +322,69 @@ impl GetService{
         Ok(performance_metrics)
     }
 
-    pub async fn get_numberof_sets_per_musclegroup(&self, user_id: i32, start_date: NaiveDate, end_date: NaiveDate, muscle_group_ids: Vec<i32>) -> Result<Vec<MuscleGroupVolume>,> {
+    pub async fn get_one_rep_max_details(&self, user_id: i32, variation_id: i32, start_date: NaiveDate, end_date: NaiveDate, formula: OneRepMaxFormula, bucket: Bucket) -> ApiResult<OneRepMaxMetrics> {
+        let units = self.get_units(user_id).await?;
+        let history = match self.workout.get_variation_sets(user_id, variation_id).await{
+            Ok(data) => data,
+            Err(err) => return Err(err)
+        };
+
+        let all_time_best = history.iter()
+            .filter_map(|set| Self::to_one_rep_max_point(set, formula, bucket))
+            .fold(None, |best: Option<OneRepMaxPoint>, point| match best {
+                Some(b) if b.e1rm >= point.e1rm => Some(b),
+                _ => Some(point),
+            });
+
+        // Keep the best estimate per bucket, preserving chronological order
+        let mut points: Vec<OneRepMaxPoint> = Vec::new();
+        for set in history.iter().filter(|s| s.performed_on >= start_date && s.performed_on <= end_date){
+            let point = match Self::to_one_rep_max_point(set, formula, bucket){
+                Some(p) => p,
+                None => continue,
+            };
+            match points.last_mut(){
+                Some(last) if last.label == point.label => {
+                    if point.e1rm > last.e1rm {
+                        *last = point;
+                    }
+                }
+                _ => points.push(point),
+            }
+        }
+
+        info!("Estimated 1RM fetched for user_id: {}, variation_id: {}", user_id, variation_id);
+        debug!("Estimated 1RM points: {:?}", points);
+        Ok(OneRepMaxMetrics{
+            variation_id,
+            formula,
+            points: points.into_iter().map(|p| p.converted(&units)).collect(),
+            all_time_best: all_time_best.map(|p| p.converted(&units)),
+        })
+    }
+
+    fn to_one_rep_max_point(set: &WorkoutSet, formula: OneRepMaxFormula, bucket: Bucket) -> Option<OneRepMaxPoint>{
+        if set.reps <= 0 || set.weight <= 0.0 {
+            return None;
+        }
+        Some(OneRepMaxPoint{
+            label: Self::get_bucket_label(set.performed_on, bucket),
+            date: set.performed_on,
+            e1rm: formula.estimate(set.weight, set.reps),
+            weight: set.weight,
+            reps: set.reps,
+        })
+    }
+
+    pub async fn get_numberof_sets_per_musclegroup(&self, user_id: i32, start_date: NaiveDate, end_date: NaiveDate, muscle_group_ids: Vec<i32>) -> ApiResult<Vec<MuscleGroupVolume>> {
         let sets = match self.workout.get_sets_for_musclegroups(user_id, muscle_group_ids.clone(), start_date, end_date).await{
             Ok(sets) => sets,
-            Err(err) => bail!(err)
+            Err(err) => return Err(err)
         };
 
         let var_map = match self.workout.get_varaition_ids(user_id, muscle_group_ids).await{
             Ok(map) => map,
-            Err(err) => bail!(err)
+            Err(err) => return Err(err)
         };
         let mut set_map: HashMap<i32, i64> = HashMap::new();
         for set in sets.iter(){
@@ -169,28 +413,233 @@ impl GetService{
         Ok(results)
     }
 
-    pub async fn get_history(&self, user_id: i32, limit: i64, start_date: Option<chrono::NaiveDate>, end_date: Option<chrono::NaiveDate>) -> anyhow::Result<Vec<crate::db::model::WorkoutSession>> {
+    /// Totals per cardio exercise and period over the range. Every period in the range is listed,
+    /// so charts don't have to fill gaps.
+    pub async fn get_cardio_summary(&self, user_id: i32, start_date: NaiveDate, end_date: NaiveDate, bucket: Bucket, cardio_exercise_id: Option<i32>) -> ApiResult<Vec<CardioSummary>> {
+        let units = self.get_units(user_id).await?;
+        let logs = self.workout.get_cardio_logs(user_id, start_date, end_date, cardio_exercise_id).await?;
+
+        let mut labels = Vec::new();
+        let mut cursor = start_date;
+        while cursor <= end_date {
+            let label = Self::get_bucket_label(cursor, bucket);
+            if labels.last() != Some(&label) {
+                labels.push(label);
+            }
+            cursor += chrono::Duration::days(1);
+        }
+
+        // (sessions, minutes, metres, minutes of logs with a distance)
+        let mut totals: HashMap<(i32, String), (i64, i64, f64, i64)> = HashMap::new();
+        let mut exercise_ids: Vec<i32> = Vec::new();
+        for log in logs.iter(){
+            if !exercise_ids.contains(&log.cardio_exercise_id) {
+                exercise_ids.push(log.cardio_exercise_id);
+            }
+            let entry = totals.entry((log.cardio_exercise_id, Self::get_bucket_label(log.performed_on, bucket)))
+                .or_insert((0, 0, 0.0, 0));
+            entry.0 += 1;
+            entry.1 += log.duration_minutes as i64;
+            if let Some(m) = log.distance_m.filter(|m| *m > 0.0) {
+                entry.2 += m;
+                entry.3 += log.duration_minutes as i64;
+            }
+        }
+        exercise_ids.sort_unstable();
+
+        let summaries: Vec<CardioSummary> = exercise_ids.into_iter()
+            .map(|id| CardioSummary{
+                cardio_exercise_id: id,
+                distance_unit: units.distance,
+                periods: labels.iter()
+                    .map(|label| {
+                        let (sessions, minutes, metres, paced_minutes) = totals.get(&(id, label.clone())).copied().unwrap_or((0, 0, 0.0, 0));
+                        let distance = metres / units.distance.metres_per_unit();
+                        CardioPeriod{
+                            label: label.clone(),
+                            sessions,
+                            total_minutes: minutes,
+                            total_distance: units.distance_out(metres),
+                            avg_pace_seconds: (distance > 0.0).then(|| (paced_minutes as f64 * 60.0 / distance).round()),
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        info!("Cardio summary fetched for user_id: {}", user_id);
+        debug!("Cardio summary: {:?}", summaries);
+        Ok(summaries)
+    }
+
+    pub async fn get_track_points(&self, user_id: i32, cardio_log_id: i32) -> ApiResult<TrackPoints> {
+        let units = self.get_units(user_id).await?;
+        match self.workout.get_track_points(user_id, cardio_log_id).await?{
+            Some(points) => Ok(TrackPoints {
+                distance_unit: units.distance,
+                elevation_unit: units.distance.elevation_unit(),
+                points: points.into_iter()
+                    .map(|p| TrackPointDetails {
+                        distance: p.distance_m.map(|d| units.distance_out(d)),
+                        elevation: p.elevation_m.map(|e| units.elevation_out(e)),
+                        point: p,
+                    })
+                    .collect(),
+            }),
+            None => Err(ApiError::NotFound("Cardio log not found".to_string())),
+        }
+    }
+
+    pub async fn get_history(&self, user_id: i32, limit: i64, filter: HistoryFilter) -> ApiResult<HistoryPage> {
         info!("Fetching workout history for user_id: {}", user_id);
-        self.workout.get_history(user_id, limit, start_date, end_date).await
+        // One extra row tells whether there is another page
+        let mut sessions = self.workout.get_history(user_id, limit + 1, &filter).await?;
+        let next_cursor = if sessions.len() as i64 > limit {
+            sessions.truncate(limit as usize);
+            sessions.last().map(|s| HistoryCursor::of(s).encode())
+        } else {
+            None
+        };
+        if sessions.is_empty() {
+            return Ok(HistoryPage { sessions: Vec::new(), next_cursor });
+        }
+
+        let ids: Vec<i32> = sessions.iter().map(|s| s.id).collect();
+        let counts = self.workout.get_session_counts(user_id, ids.clone()).await?;
+        let weight = self.get_body_weight(user_id).await?;
+        let (cardio, mets) = match weight{
+            Some(_) => {
+                let cardio = self.workout.get_session_cardio_logs(user_id, ids).await?;
+                (cardio, Some(MetTable::new(&self.workout.get_all_cardio_exercises(user_id).await?)))
+            }
+            None => (Vec::new(), None),
+        };
+        let sessions = sessions.into_iter()
+            .map(|session| {
+                let estimated_calories = match (weight, &mets){
+                    (Some(kg), Some(mets)) => Some(energy::session_kcal(&session, &cardio, mets, kg).round()),
+                    _ => None,
+                };
+                let (set_count, cardio_count) = counts.get(&session.id).copied().unwrap_or_default();
+                SessionSummary { session, set_count, cardio_count, estimated_calories }
+            })
+            .collect();
+        Ok(HistoryPage { sessions, next_cursor })
     }
 
-    pub async fn get_session_details(&self, user_id: i32, session_id: i32) -> anyhow::Result<(crate::db::model::WorkoutSession, Vec<crate::db::model::WorkoutSet>, Vec<crate::db::model::CardioLog>)> {
+    /// Also returns the session's estimated energy use, if the user has a body weight on record.
+    pub async fn get_session_details(&self, user_id: i32, session_id: i32) -> ApiResult<(WorkoutSession, Vec<WorkoutSet>, Vec<CardioLogDetails>, Option<f64>)> {
         info!("Fetching session details for user_id: {}, session_id: {}", user_id, session_id);
-        self.workout.get_session_details(user_id, session_id).await
+        let units = self.get_units(user_id).await?;
+        let (session, sets, logs) = self.workout.get_session_details(user_id, session_id).await?;
+        let estimated_calories = match self.get_body_weight(user_id).await?{
+            Some(kg) => {
+                let mets = MetTable::new(&self.workout.get_all_cardio_exercises(user_id).await?);
+                Some(energy::session_kcal(&session, &logs, &mets, kg).round())
+            }
+            None => None,
+        };
+        let logs = logs.into_iter().map(|l| CardioLogDetails::new(l, &units)).collect();
+        Ok((session, sets.into_iter().map(|s| units.set_out(s)).collect(), logs, estimated_calories))
+    }
+
+    /// Latest body weight in kg, kept in step with the measurement log.
+    async fn get_body_weight(&self, user_id: i32) -> ApiResult<Option<f64>>{
+        match self.user.get_user_by_id(user_id).await?{
+            Some(user) => Ok(user.weight),
+            None => Err(ApiError::NotFound("User not found".to_string())),
+        }
     }
 
-    pub async fn get_user_info(&self, user_id: i32) -> anyhow::Result<User,>{
+    /// Body weight and height come back in the user's preferred units.
+    pub async fn get_user_info(&self, user_id: i32) -> ApiResult<User>{
         info!("Fetching user info for user_id: {}", user_id);
         match self.user.get_user_by_id(user_id).await{
-            Ok(Some(user)) => Ok(user),
-            Ok(None) => Err(anyhow::anyhow!("User not found")),
+            Ok(Some(user)) => Ok(Units::of(&user).user_out(user)),
+            Ok(None) => Err(ApiError::NotFound("User not found".to_string())),
             Err(err) => {
                 error!("Error fetching user info: {}", err);
-                Err(anyhow::anyhow!("Error fetching user info"))
+                Err(err)
             }
         }
     }
     
+    pub async fn get_measurements(&self, user_id: i32, start_date: NaiveDate, end_date: NaiveDate) -> ApiResult<Vec<BodyMeasurement>>{
+        info!("Fetching body measurements for user_id: {}", user_id);
+        let units = self.get_units(user_id).await?;
+        let measurements = self.measurements.get_measurements(user_id, start_date, end_date).await?;
+        Ok(measurements.into_iter().map(|m| units.measurement_out(m)).collect())
+    }
+
+    /// Daily values of one metric with a trailing moving average. Readings from before
+    /// `start_date` feed the first averages, so the line doesn't start cold.
+    pub async fn get_measurement_trend(&self, user_id: i32, metric: BodyMetric, start_date: NaiveDate, end_date: NaiveDate, window_days: i64) -> ApiResult<MeasurementTrend>{
+        let units = self.get_units(user_id).await?;
+        let lookback = start_date - chrono::Duration::days(window_days - 1);
+        let measurements = self.measurements.get_measurements(user_id, lookback, end_date).await?;
+
+        let mut days: BTreeMap<NaiveDate, (f64, u32)> = BTreeMap::new();
+        for m in measurements.iter(){
+            if let Some(value) = metric.value(m) {
+                let day = days.entry(m.measured_on).or_insert((0.0, 0));
+                day.0 += value;
+                day.1 += 1;
+            }
+        }
+        let daily: Vec<(NaiveDate, f64)> = days.into_iter().map(|(d, (sum, n))| (d, sum / n as f64)).collect();
+
+        let convert = |v: f64| match metric{
+            BodyMetric::Weight => units.weight_out(v),
+            BodyMetric::BodyFat => (v * 100.0).round() / 100.0,
+            _ => units.length_out(v),
+        };
+        let mut points = Vec::new();
+        for (i, (date, value)) in daily.iter().enumerate(){
+            if *date < start_date {
+                continue;
+            }
+            let window_start = *date - chrono::Duration::days(window_days - 1);
+            let window: Vec<f64> = daily[..=i].iter().rev()
+                .take_while(|(d, _)| *d >= window_start)
+                .map(|(_, v)| *v)
+                .collect();
+            let moving_average = window.iter().sum::<f64>() / window.len() as f64;
+            points.push(TrendPoint{
+                date: *date,
+                value: convert(*value),
+                moving_average: convert(moving_average),
+            });
+        }
+
+        let change = match (points.first(), points.last()){
+            (Some(first), Some(last)) if points.len() > 1 => Some(((last.moving_average - first.moving_average) * 100.0).round() / 100.0),
+            _ => None,
+        };
+        let unit = match metric{
+            BodyMetric::Weight => units.weight.as_str(),
+            BodyMetric::BodyFat => "%",
+            _ => units.length.as_str(),
+        };
+        Ok(MeasurementTrend{ metric, unit, window_days, points, change })
+    }
+
+    pub async fn get_units(&self, user_id: i32) -> ApiResult<Units>{
+        match self.user.get_user_by_id(user_id).await?{
+            Some(user) => Ok(Units::of(&user)),
+            None => Err(ApiError::NotFound("User not found".to_string())),
+        }
+    }
+
+    /// Labels are unique across years so ranges spanning several of them don't merge buckets.
+    pub fn get_bucket_label(date: NaiveDate, bucket: Bucket) -> String{
+        match bucket{
+            Bucket::Day => date.to_string(),
+            // ISO week-numbering year, so the days around New Year stay in one week
+            Bucket::Week => date.format("%G-W%V").to_string(),
+            Bucket::Month => format!("{}-{:02}", date.year(), date.month()),
+        }
+    }
+
     pub fn get_week_label(date: NaiveDate) -> String{
         let days_from_monday = date.weekday().num_days_from_monday();
         let monday = date - chrono::Duration::days(days_from_monday as i64);
@@ -208,17 +657,17 @@ impl GetService{
         }
     }
 
-    pub async fn get_muscle_groups(&self,user_id: i32) -> Result<Vec<crate::db::model::MuscleGroup>> {
+    pub async fn get_muscle_groups(&self,user_id: i32) -> ApiResult<Vec<crate::db::model::MuscleGroup>> {
         debug!("Fetching muscle groups for user_id: {}", user_id);
         self.workout.get_all_muscle_groups(user_id).await
     }
 
-    pub async fn get_variations(&self,user_id: i32) -> Result<Vec<crate::db::model::Variation>> {
+    pub async fn get_variations(&self,user_id: i32) -> ApiResult<Vec<crate::db::model::Variation>> {
         debug!("Fetching variations for user_id: {}", user_id);
         self.workout.get_all_variations(user_id).await
     }
 
-    pub async fn get_cardio_exercises(&self,user_id: i32) -> Result<Vec<crate::db::model::CardioExercise>> {
+    pub async fn get_cardio_exercises(&self,user_id: i32) -> ApiResult<Vec<crate::db::model::CardioExercise>> {
         debug!("Fetching cardio exercises for user_id: {}", user_id);
         self.workout.get_all_cardio_exercises(user_id).await
     }
@@ -0,0 +1,199 @@
+use std::{collections::{HashMap, HashSet}, sync::Arc};
+use log::{error, info};
+use serde::Serialize;
+use crate::{db::{logger::{ImportBatch, ImportRef, ImportedSession, LoggerDB}, model::{NewCardioLog, NewWorkoutSession, NewWorkoutSet}, workouts::WorkoutDB},
+            error::{ApiError, ApiResult},
+            services::{csv_import::{self, ExerciseKind, ImportDefaults, ImportSource}, records_service::RecordsService}};
+
+/// User-owned muscle group that new variations go into when the file doesn't say which one.
+const IMPORT_MUSCLE_GROUP: &str = "Imported";
+
+/// Where an exercise name from the file ends up.
+#[derive(Debug, Serialize)]
+pub struct ExerciseMapping{
+    pub name: String,
+    pub kind: ExerciseKind,
+    /// The existing exercise the rows are logged against, empty when a new one is created
+    pub matched_id: Option<i32>,
+    pub matched_name: Option<String>,
+    pub score: Option<f64>,
+    pub create: bool,
+}
+
+/// What an import wrote, or on a dry run what it would write.
+#[derive(Debug, Serialize)]
+pub struct ImportReport{
+    pub source: ImportSource,
+    pub dry_run: bool,
+    pub sessions: usize,
+    pub sets: usize,
+    pub cardio_logs: usize,
+    /// Sessions left out because one starting at the same time is already logged
+    pub duplicate_sessions: usize,
+    pub skipped_rows: usize,
+    pub exercises: Vec<ExerciseMapping>,
+    pub warnings: Vec<String>,
+}
+
+pub struct ImportService{
+    logger: Arc<LoggerDB>,
+    workout: Arc<WorkoutDB>,
+    records: Arc<RecordsService>,
+}
+
+impl ImportService{
+    pub fn new(logger: Arc<LoggerDB>, workout: Arc<WorkoutDB>, records: Arc<RecordsService>) -> Self{
+        ImportService { logger, workout, records }
+    }
+
+    pub async fn import(&self, user_id: i32, source: Option<ImportSource>, defaults: ImportDefaults, dry_run: bool, data: &[u8]) -> ApiResult<ImportReport>{
+        let mut parsed = csv_import::parse(source, data, &defaults)?;
+        let (first, last) = match (parsed.workouts.iter().map(|w| w.start).min(), parsed.workouts.iter().map(|w| w.start).max()){
+            (Some(first), Some(last)) => (first.date(), last.date()),
+            _ => return Err(ApiError::BadRequest("No sets or cardio found in the file".to_string())),
+        };
+        info!("Importing {} {} workout(s) for user_id: {}, dry run: {}", parsed.workouts.len(), parsed.source.as_str(), user_id, dry_run);
+
+        let logged: HashSet<_> = self.workout.get_sessions(user_id, first, last).await?
+            .into_iter()
+            .map(|s| s.start_time)
+            .collect();
+        let before = parsed.workouts.len();
+        parsed.workouts.retain(|w| !logged.contains(&w.start));
+        let duplicate_sessions = before - parsed.workouts.len();
+
+        let variations = self.workout.get_all_variations(user_id).await?;
+        let cardio_exercises = self.workout.get_all_cardio_exercises(user_id).await?;
+        let muscle_groups = self.workout.get_all_muscle_groups(user_id).await?;
+        let categories: HashMap<&str, &str> = parsed.workouts.iter()
+            .flat_map(|w| w.sets.iter())
+            .filter_map(|s| s.category.as_deref().map(|c| (s.exercise.as_str(), c)))
+            .collect();
+
+        let mut refs: HashMap<(ExerciseKind, &str), ImportRef> = HashMap::new();
+        let mut exercises = Vec::new();
+        let mut new_variations = Vec::new();
+        let mut new_cardio_exercises = Vec::new();
+        for (kind, name) in parsed.exercises(){
+            let found = match kind{
+                ExerciseKind::Strength => csv_import::best_match(name, variations.iter().map(|v| (v.id, v.name.as_str()))),
+                ExerciseKind::Cardio => csv_import::best_match(name, cardio_exercises.iter().map(|c| (c.id, c.name.as_str()))),
+            };
+            let reference = match (found, kind){
+                (Some((id, _, _)), _) => ImportRef::Existing(id),
+                (None, ExerciseKind::Strength) => {
+                    let group = categories.get(name)
+                        .and_then(|c| csv_import::best_match(c, muscle_groups.iter().map(|g| (g.id, g.name.as_str()))))
+                        .map(|(id, _, _)| id);
+                    new_variations.push((name.to_string(), group));
+                    ImportRef::New(new_variations.len() - 1)
+                }
+                (None, ExerciseKind::Cardio) => {
+                    new_cardio_exercises.push(name.to_string());
+                    ImportRef::New(new_cardio_exercises.len() - 1)
+                }
+            };
+            exercises.push(ExerciseMapping {
+                name: name.to_string(),
+                kind,
+                matched_id: found.map(|(id, _, _)| id),
+                matched_name: found.map(|(_, matched, _)| matched.to_string()),
+                score: found.map(|(_, _, score)| (score * 100.0).round() / 100.0),
+                create: found.is_none(),
+            });
+            refs.insert((kind, name), reference);
+        }
+
+        let sessions: Vec<ImportedSession> = parsed.workouts.iter()
+            .map(|w| {
+                let date = w.start.date();
+                let sets = w.sets.iter()
+                    .enumerate()
+                    .map(|(i, s)| (refs[&(ExerciseKind::Strength, s.exercise.as_str())], NewWorkoutSet {
+                        workout_session_id: None,
+                        user_id,
+                        variation_id: 0,
+                        weight: s.weight_kg,
+                        reps: s.reps,
+                        performed_on: date,
+                        set_index: Some(i as i32 + 1),
+                        set_type: s.set_type.as_str().to_string(),
+                        rpe: s.rpe,
+                        rir: None,
+                        rest_seconds: None,
+                        tempo: None,
+                        completed: true,
+                    }))
+                    .collect();
+                let logs = w.cardio.iter()
+                    .map(|c| (refs[&(ExerciseKind::Cardio, c.exercise.as_str())], NewCardioLog {
+                        workout_session_id: None,
+                        user_id,
+                        cardio_exercise_id: 0,
+                        duration_minutes: c.duration_minutes,
+                        performed_on: Some(date),
+                        distance_m: c.distance_m,
+                        elevation_gain_m: None,
+                        avg_heart_rate: None,
+                        max_heart_rate: None,
+                        calories: None,
+                        started_at: None,
+                        completed: true,
+                    }))
+                    .collect();
+                ImportedSession {
+                    session: NewWorkoutSession {
+                        user_id,
+                        title: Some(w.title.clone().unwrap_or_else(|| format!("Imported {} workout", parsed.source.as_str()))),
+                        notes: w.notes.clone(),
+                        date,
+                        start_time: w.start,
+                        end_time: w.end.max(w.start),
+                        program_day_id: None,
+                    },
+                    sets,
+                    logs,
+                }
+            })
+            .collect();
+
+        let mut report = ImportReport {
+            source: parsed.source,
+            dry_run,
+            sessions: sessions.len(),
+            sets: sessions.iter().map(|s| s.sets.len()).sum(),
+            cardio_logs: sessions.iter().map(|s| s.logs.len()).sum(),
+            duplicate_sessions,
+            skipped_rows: parsed.skipped_rows,
+            exercises,
+            warnings: parsed.warnings,
+        };
+        if dry_run || sessions.is_empty() {
+            return Ok(report);
+        }
+
+        let batch = ImportBatch {
+            user_id,
+            fallback_group: IMPORT_MUSCLE_GROUP.to_string(),
+            new_variations,
+            new_cardio_exercises,
+            sessions,
+        };
+        let (sessions, sets, cardio_logs, variation_ids) = match self.logger.add_import(batch).await{
+            Ok(r) => r,
+            Err(err) => {
+                error!("Error importing {} workouts for user_id {}: {}", report.source.as_str(), user_id, err);
+                return Err(err);
+            }
+        };
+        if let Err(err) = self.records.rebuild_session_records(user_id, variation_ids).await{
+            error!("Error rebuilding personal records for user_id {}: {}", user_id, err);
+        }
+
+        info!("Imported {} session(s), {} set(s) and {} cardio log(s) for user_id: {}", sessions, sets, cardio_logs, user_id);
+        report.sessions = sessions;
+        report.sets = sets;
+        report.cardio_logs = cardio_logs;
+        Ok(report)
+    }
+}
@@ -1,30 +1,89 @@
+use std::{collections::HashMap, str::FromStr};
+use anyhow::{bail, Result};
 use chrono::{Utc, Duration};
-use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey, TokenData, errors::Result as JwtResult};
+use jsonwebtoken::{encode, decode, decode_header, Algorithm, Header, Validation, EncodingKey, DecodingKey, TokenData, errors::{Error as JwtError, ErrorKind, Result as JwtResult}};
+use log::{info, warn};
 use serde::{Serialize, Deserialize};
+use crate::configuration::{Jwt, JwtKey};
+
+const DEFAULT_SECRET: &str = "SECRET";
+const DEFAULT_KID: &str = "default";
+const DEFAULT_EXPIRATION_MINUTES: i64 = 60;
+const DEV_PROFILE: &str = "dev";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub id: i32,
-    pub exp: usize,   
-    pub iat: usize,    
+    pub exp: usize,
+    pub iat: usize,
+    /// Refresh token family the access token was issued with, lets logout revoke it without the refresh cookie
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fid: Option<String>,
+}
+
+#[derive(Clone)]
+struct VerifyingKey {
+    algorithm: Algorithm,
+    key: DecodingKey,
 }
 
 #[derive(Clone)]
 pub struct JwtService {
-    secret: String,
+    kid: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    keyring: HashMap<String, VerifyingKey>,
     expiration_minutes: i64,
 }
 
 impl JwtService {
-    pub fn new() -> Self {
-        JwtService {
-            secret : "SECRET".to_string(),
-            expiration_minutes: 60, // Increased to 60 for better UX
+    pub fn new(conf: Jwt, profile: &str) -> Result<Self> {
+        let algorithm = Self::parse_algorithm(&conf.algorithm)?;
+        let kid = if conf.kid.is_empty() { DEFAULT_KID.to_string() } else { conf.kid.clone() };
+
+        Self::check_secret(algorithm, &conf.secret, profile)?;
+
+        let encoding_key = match algorithm {
+            a if Self::is_hmac(a) => EncodingKey::from_secret(Self::secret_or_default(&conf.secret).as_bytes()),
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 | Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512 =>
+                EncodingKey::from_rsa_pem(&Self::read_pem(&conf.private_key)?)?,
+            Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(&Self::read_pem(&conf.private_key)?)?,
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(&Self::read_pem(&conf.private_key)?)?,
+            _ => bail!("Unsupported JWT algorithm {:?}", algorithm),
+        };
+
+        let mut keyring = HashMap::new();
+        let active = JwtKey{
+            algorithm: conf.algorithm.clone(),
+            kid: kid.clone(),
+            secret: conf.secret.clone(),
+            public_key: conf.public_key.clone(),
+        };
+        keyring.insert(kid.clone(), Self::verifying_key(&active)?);
+        for previous in conf.previous_keys.iter() {
+            if previous.kid.is_empty() || previous.kid == kid {
+                bail!("Previous JWT keys need a kid distinct from the active one");
+            }
+            Self::check_secret(Self::parse_algorithm(&previous.algorithm)?, &previous.secret, profile)?;
+            keyring.insert(previous.kid.clone(), Self::verifying_key(previous)?);
         }
+
+        info!("JWT signing with {:?} key '{}', {} key(s) accepted for verification", algorithm, kid, keyring.len());
+        Ok(JwtService {
+            kid,
+            algorithm,
+            encoding_key,
+            keyring,
+            expiration_minutes: if conf.expiration_minutes > 0 { conf.expiration_minutes } else { DEFAULT_EXPIRATION_MINUTES },
+        })
     }
 
-    pub fn generate_token(&self, subject: &str, id: i32) -> JwtResult<String> {
+    pub fn expiration_minutes(&self) -> i64 {
+        self.expiration_minutes
+    }
+
+    pub fn generate_token(&self, subject: &str, id: i32, family_id: Option<&str>) -> JwtResult<String> {
         let now = Utc::now();
         let exp = now + Duration::minutes(self.expiration_minutes);
 
@@ -33,20 +92,89 @@ impl JwtService {
             id,
             iat: now.timestamp() as usize,
             exp: exp.timestamp() as usize,
+            fid: family_id.map(|f| f.to_owned()),
         };
 
-        encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(self.secret.as_bytes()),
-        )
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+        encode(&header, &claims, &self.encoding_key)
     }
 
     pub fn validate_token(&self, token: &str) -> JwtResult<TokenData<Claims>> {
+        self.decode_token(token, true)
+    }
+
+    /// Checks the signature but accepts expired tokens, for logout after the access token has lapsed.
+    pub fn validate_token_ignoring_expiry(&self, token: &str) -> JwtResult<TokenData<Claims>> {
+        self.decode_token(token, false)
+    }
+
+    fn decode_token(&self, token: &str, validate_exp: bool) -> JwtResult<TokenData<Claims>> {
+        let header = decode_header(token)?;
+        // Tokens minted before key ids were introduced carry no kid, check them against the active key
+        let kid = header.kid.unwrap_or_else(|| self.kid.clone());
+        let verifying = match self.keyring.get(&kid) {
+            Some(k) => k,
+            None => return Err(JwtError::from(ErrorKind::InvalidSignature)),
+        };
+        let mut validation = Validation::new(verifying.algorithm);
+        validation.validate_exp = validate_exp;
         decode::<Claims>(
             token,
-            &DecodingKey::from_secret(self.secret.as_bytes()),
-            &Validation::default(),
+            &verifying.key,
+            &validation,
         )
     }
+
+    fn verifying_key(key: &JwtKey) -> Result<VerifyingKey> {
+        let algorithm = Self::parse_algorithm(&key.algorithm)?;
+        let decoding_key = match algorithm {
+            a if Self::is_hmac(a) => DecodingKey::from_secret(Self::secret_or_default(&key.secret).as_bytes()),
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 | Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512 =>
+                DecodingKey::from_rsa_pem(&Self::read_pem(&key.public_key)?)?,
+            Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(&Self::read_pem(&key.public_key)?)?,
+            Algorithm::EdDSA => DecodingKey::from_ed_pem(&Self::read_pem(&key.public_key)?)?,
+            _ => bail!("Unsupported JWT algorithm {:?}", algorithm),
+        };
+        Ok(VerifyingKey { algorithm, key: decoding_key })
+    }
+
+    fn check_secret(algorithm: Algorithm, secret: &str, profile: &str) -> Result<()> {
+        if !Self::is_hmac(algorithm) || !(secret.is_empty() || secret == DEFAULT_SECRET) {
+            return Ok(());
+        }
+        if profile != DEV_PROFILE {
+            bail!("Refusing to start with the default JWT secret outside the dev profile, set JWT_SECRET or configure an asymmetric key");
+        }
+        warn!("Using the default JWT secret, only acceptable for local development");
+        Ok(())
+    }
+
+    fn parse_algorithm(name: &str) -> Result<Algorithm> {
+        if name.is_empty() {
+            return Ok(Algorithm::HS256);
+        }
+        match Algorithm::from_str(name) {
+            Ok(a) => Ok(a),
+            Err(err) => bail!("Invalid JWT algorithm '{}': {}", name, err),
+        }
+    }
+
+    fn is_hmac(algorithm: Algorithm) -> bool {
+        matches!(algorithm, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)
+    }
+
+    fn secret_or_default(secret: &str) -> &str {
+        if secret.is_empty() { DEFAULT_SECRET } else { secret }
+    }
+
+    fn read_pem(path: &str) -> Result<Vec<u8>> {
+        if path.is_empty() {
+            bail!("Asymmetric JWT algorithms need a PEM key path");
+        }
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(bytes),
+            Err(err) => bail!("Error reading JWT key {}: {}", path, err),
+        }
+    }
 }
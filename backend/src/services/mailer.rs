@@ -0,0 +1,62 @@
+use std::{fs::OpenOptions, io::Write};
+use anyhow::{bail, Result};
+use chrono::Utc;
+use log::info;
+
+#[derive(Debug, Clone)]
+pub struct MailMessage{
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Outgoing mail transport. Implementations must not fail silently, callers decide
+/// whether a delivery error is surfaced to the client.
+pub trait Mailer: Send + Sync{
+    fn send(&self, message: &MailMessage) -> Result<()>;
+}
+
+/// Development mailer that writes messages to the log and, when configured, appends them
+/// to an outbox file instead of delivering them.
+pub struct LogMailer{
+    from: String,
+    outbox: Option<String>,
+}
+
+impl LogMailer{
+    pub fn new(from: String, outbox: Option<String>) -> Self{
+        LogMailer {
+            from,
+            outbox
+        }
+    }
+}
+
+impl Mailer for LogMailer{
+    fn send(&self, message: &MailMessage) -> Result<()>{
+        info!("Mail to {} from {}: {}", message.to, self.from, message.subject);
+
+        let path = match &self.outbox{
+            Some(p) => p,
+            None => {
+                info!("{}", message.body);
+                return Ok(());
+            }
+        };
+        let mut file = match OpenOptions::new().create(true).append(true).open(path){
+            Ok(f) => f,
+            Err(err) => bail!("Error opening outbox {}: {}", path, err)
+        };
+        let entry = format!("Date: {}\nFrom: {}\nTo: {}\nSubject: {}\n\n{}\n\n",
+            Utc::now().to_rfc2822(),
+            self.from,
+            message.to,
+            message.subject,
+            message.body
+        );
+        if let Err(err) = file.write_all(entry.as_bytes()){
+            bail!("Error writing to outbox {}: {}", path, err);
+        }
+        Ok(())
+    }
+}
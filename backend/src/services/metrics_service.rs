@@ -0,0 +1,185 @@
+use std::{collections::HashMap, sync::Arc};
+use chrono::{Duration, NaiveDate, Utc};
+use log::info;
+use serde::Serialize;
+use crate::{db::{measurements::MeasurementDB, model::{CardioLog, Sex, User, WorkoutSession}, user::UserDB, workouts::WorkoutDB},
+            error::{ApiError, ApiResult}, services::{energy::{self, MetTable, STRENGTH_MET}, units::Units}};
+
+/// Days of logged training used to work out the activity level.
+pub const ACTIVITY_WINDOW_DAYS: i64 = 28;
+/// Multiplier applied to BMR for the day outside of logged training.
+const SEDENTARY_FACTOR: f64 = 1.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BmrFormula{
+    MifflinStJeor,
+    KatchMcArdle,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActivitySummary{
+    pub window_days: i64,
+    pub sessions: usize,
+    pub strength_minutes_per_day: f64,
+    pub cardio_minutes_per_day: f64,
+    /// Energy burned by logged training on top of the resting rate, averaged over the window.
+    pub exercise_calories_per_day: f64,
+    /// TDEE divided by BMR, when both are known.
+    pub activity_factor: Option<f64>,
+}
+
+/// Values derived from the profile and training log. Weight and height are in the user's units,
+/// energy in kcal per day. Anything whose inputs are missing is left out.
+#[derive(Debug, Serialize)]
+pub struct BodyMetrics{
+    pub age: Option<u32>,
+    pub sex: Option<String>,
+    pub weight: Option<f64>,
+    pub height: Option<f64>,
+    pub bmi: Option<f64>,
+    pub bmi_category: Option<&'static str>,
+    pub body_fat_percent: Option<f64>,
+    pub bmr: Option<f64>,
+    pub bmr_formula: Option<BmrFormula>,
+    pub tdee: Option<f64>,
+    pub activity: ActivitySummary,
+}
+
+pub struct MetricsService{
+    user: Arc<UserDB>,
+    workout: Arc<WorkoutDB>,
+    measurements: Arc<MeasurementDB>,
+}
+
+impl MetricsService{
+    pub fn new(user: Arc<UserDB>, workout: Arc<WorkoutDB>, measurements: Arc<MeasurementDB>) -> Self{
+        MetricsService { user, workout, measurements }
+    }
+
+    pub async fn get_metrics(&self, user_id: i32) -> ApiResult<BodyMetrics>{
+        info!("Computing body metrics for user_id: {}", user_id);
+        let user = match self.user.get_user_by_id(user_id).await?{
+            Some(u) => u,
+            None => return Err(ApiError::NotFound("User not found".to_string())),
+        };
+        let body_fat = self.measurements.get_latest_body_fat(user_id).await?;
+
+        let today = Utc::now().date_naive();
+        let start = today - Duration::days(ACTIVITY_WINDOW_DAYS - 1);
+        let sessions = self.workout.get_sessions(user_id, start, today).await?;
+        let cardio = self.workout.get_cardio_logs(user_id, start, today, None).await?;
+        let mets = MetTable::new(&self.workout.get_all_cardio_exercises(user_id).await?);
+
+        Ok(Self::compute(&user, body_fat, &sessions, &cardio, &mets, today))
+    }
+
+    fn compute(user: &User, body_fat: Option<f64>, sessions: &[WorkoutSession], cardio: &[CardioLog], mets: &MetTable, today: NaiveDate) -> BodyMetrics{
+        let units = Units::of(user);
+        let age = user.dob.and_then(|dob| today.years_since(dob));
+        let sex = user.sex.as_deref().and_then(Sex::parse);
+
+        let bmi = match (user.weight, user.height){
+            (Some(kg), Some(cm)) if cm > 0.0 => Some(kg / (cm / 100.0).powi(2)),
+            _ => None,
+        };
+
+        let (bmr, bmr_formula) = match (user.weight, body_fat){
+            (Some(kg), Some(fat)) => (Some(Self::katch_mcardle(kg, fat)), Some(BmrFormula::KatchMcArdle)),
+            _ => match (user.weight, user.height, age){
+                (Some(kg), Some(cm), Some(age)) => (Some(Self::mifflin_st_jeor(kg, cm, age, sex)), Some(BmrFormula::MifflinStJeor)),
+                _ => (None, None),
+            },
+        };
+
+        let mut activity = Self::activity(user.weight, sessions, cardio, mets);
+        let tdee = bmr.map(|b| b * SEDENTARY_FACTOR + activity.exercise_calories_per_day);
+        activity.activity_factor = match (tdee, bmr){
+            (Some(t), Some(b)) if b > 0.0 => Some(round(t / b, 2)),
+            _ => None,
+        };
+
+        BodyMetrics {
+            age,
+            sex: user.sex.clone(),
+            weight: user.weight.map(|w| units.weight_out(w)),
+            height: user.height.map(|h| units.length_out(h)),
+            bmi: bmi.map(|b| round(b, 1)),
+            bmi_category: bmi.map(Self::bmi_category),
+            body_fat_percent: body_fat,
+            bmr: bmr.map(|b| b.round()),
+            bmr_formula,
+            tdee: tdee.map(|t| t.round()),
+            activity,
+        }
+    }
+
+    fn bmi_category(bmi: f64) -> &'static str{
+        match bmi{
+            b if b < 18.5 => "underweight",
+            b if b < 25.0 => "normal",
+            b if b < 30.0 => "overweight",
+            _ => "obese",
+        }
+    }
+
+    /// Without a recorded sex the midpoint of the two offsets is used.
+    fn mifflin_st_jeor(kg: f64, cm: f64, age: u32, sex: Option<Sex>) -> f64{
+        let offset = match sex{
+            Some(Sex::Male) => 5.0,
+            Some(Sex::Female) => -161.0,
+            None => -78.0,
+        };
+        10.0 * kg + 6.25 * cm - 5.0 * age as f64 + offset
+    }
+
+    fn katch_mcardle(kg: f64, body_fat_percent: f64) -> f64{
+        let lean_mass = kg * (1.0 - body_fat_percent / 100.0);
+        370.0 + 21.6 * lean_mass
+    }
+
+    /// Net calories from logged training: cardio uses the logged calories when present, everything
+    /// else is estimated from MET values less the resting rate already counted in BMR.
+    fn activity(weight: Option<f64>, sessions: &[WorkoutSession], cardio: &[CardioLog], mets: &MetTable) -> ActivitySummary{
+        let mut cardio_in_session: HashMap<i32, i64> = HashMap::new();
+        for log in cardio.iter(){
+            if let Some(id) = log.workout_session_id {
+                *cardio_in_session.entry(id).or_default() += log.duration_minutes as i64;
+            }
+        }
+
+        let strength_minutes: i64 = sessions.iter()
+            .map(|s| energy::strength_minutes(s, cardio_in_session.get(&s.id).copied().unwrap_or(0)))
+            .sum();
+        let cardio_minutes: i64 = cardio.iter().map(|c| c.duration_minutes as i64).sum();
+
+        let calories = match weight{
+            Some(kg) => {
+                let strength = energy::kcal(STRENGTH_MET - 1.0, kg, strength_minutes as f64);
+                let cardio: f64 = cardio.iter()
+                    .map(|c| match c.calories{
+                        Some(kcal) => kcal as f64,
+                        None => energy::kcal(mets.cardio(c.cardio_exercise_id) - 1.0, kg, c.duration_minutes as f64),
+                    })
+                    .sum();
+                strength + cardio
+            }
+            None => 0.0,
+        };
+
+        let days = ACTIVITY_WINDOW_DAYS as f64;
+        ActivitySummary {
+            window_days: ACTIVITY_WINDOW_DAYS,
+            sessions: sessions.len(),
+            strength_minutes_per_day: round(strength_minutes as f64 / days, 1),
+            cardio_minutes_per_day: round(cardio_minutes as f64 / days, 1),
+            exercise_calories_per_day: (calories / days).round(),
+            activity_factor: None,
+        }
+    }
+}
+
+fn round(value: f64, places: i32) -> f64{
+    let factor = 10f64.powi(places);
+    (value * factor).round() / factor
+}
@@ -3,11 +3,26 @@ pub mod jwt_service;
 pub mod post_service;
 pub mod get_service;
 pub mod put_service;
+pub mod records_service;
+pub mod session_service;
+pub mod crypto;
+pub mod mailer;
+pub mod ownership_service;
+pub mod units;
+pub mod energy;
+pub mod track_import;
+pub mod routine_service;
+pub mod program_service;
+pub mod metrics_service;
+pub mod export_service;
+pub mod csv_import;
+pub mod import_service;
+pub mod search_service;
 
 use std::sync::Arc;
 use anyhow::{bail, Result};
-use crate::{db::{database::DBOperations, logger::LoggerDB, user::UserDB, workouts::WorkoutDB}, 
-            services::{auth_service::AuthService, get_service::GetService, jwt_service::JwtService, post_service::PostService, put_service::PutService}};
+use crate::{configuration::Config, db::{database::DBOperations, export::ExportDB, logger::LoggerDB, measurements::MeasurementDB, programs::ProgramDB, records::RecordsDB, routines::RoutineDB, search::SearchDB, tokens::TokenDB, user::UserDB, workouts::WorkoutDB}, 
+            services::{auth_service::AuthService, export_service::ExportService, get_service::GetService, import_service::ImportService, jwt_service::JwtService, metrics_service::MetricsService, ownership_service::OwnershipService, post_service::PostService, program_service::ProgramService, put_service::PutService, records_service::RecordsService, routine_service::RoutineService, search_service::SearchService, session_service::SessionService, mailer::{LogMailer, Mailer}}};
 
 pub struct Service{
     pub auth_service: Option<Arc<AuthService>>,
@@ -15,6 +30,14 @@ pub struct Service{
     pub get_service: Option<Arc<GetService>>,
     pub put_service: Option<Arc<PutService>>,
     pub jwt_service: Option<Arc<JwtService>>,
+    pub records_service: Option<Arc<RecordsService>>,
+    pub session_service: Option<Arc<SessionService>>,
+    pub routine_service: Option<Arc<RoutineService>>,
+    pub program_service: Option<Arc<ProgramService>>,
+    pub metrics_service: Option<Arc<MetricsService>>,
+    pub export_service: Option<Arc<ExportService>>,
+    pub import_service: Option<Arc<ImportService>>,
+    pub search_service: Option<Arc<SearchService>>,
     pub database: Arc<DBOperations>,
 }
 
@@ -26,6 +49,14 @@ impl Service{
             get_service: None,
             put_service: None,
             jwt_service: None,
+            records_service: None,
+            session_service: None,
+            routine_service: None,
+            program_service: None,
+            metrics_service: None,
+            export_service: None,
+            import_service: None,
+            search_service: None,
             database: db_ops, 
         }
     }
@@ -49,21 +80,99 @@ impl Service{
         }
         let logger_db_arc = Arc::new(logger_db);
 
+        let mut records_db = RecordsDB::new(self.database.clone());
+        if let Err(err) = records_db.init().await{
+            bail!("Error initialising records db: {}", err);
+        }
+        let records_db_arc = Arc::new(records_db);
+
+        let mut token_db = TokenDB::new(self.database.clone());
+        if let Err(err) = token_db.init().await{
+            bail!("Error initialising token db: {}", err);
+        }
+        let token_db_arc = Arc::new(token_db);
+
+        let mut routine_db = RoutineDB::new(self.database.clone());
+        if let Err(err) = routine_db.init().await{
+            bail!("Error initialising routine db: {}", err);
+        }
+        let routine_db_arc = Arc::new(routine_db);
+
+        let mut program_db = ProgramDB::new(self.database.clone());
+        if let Err(err) = program_db.init().await{
+            bail!("Error initialising program db: {}", err);
+        }
+        let program_db_arc = Arc::new(program_db);
+
+        let mut measurement_db = MeasurementDB::new(self.database.clone());
+        if let Err(err) = measurement_db.init().await{
+            bail!("Error initialising measurement db: {}", err);
+        }
+        let measurement_db_arc = Arc::new(measurement_db);
+
+        let mut export_db = ExportDB::new(self.database.clone());
+        if let Err(err) = export_db.init().await{
+            bail!("Error initialising export db: {}", err);
+        }
+        let export_db_arc = Arc::new(export_db);
+
+        let mut search_db = SearchDB::new(self.database.clone());
+        if let Err(err) = search_db.init().await{
+            bail!("Error initialising search db: {}", err);
+        }
+        let search_db_arc = Arc::new(search_db);
 
-        let auth_service = AuthService::new(user_arc.clone());
+        let conf = match Config::load(){
+            Ok(c) => c,
+            Err(err) => bail!("Error loading config: {}", err)
+        };
+        let mail = conf.get_mail_properties();
+        let outbox = if mail.outbox.is_empty() { None } else { Some(mail.outbox) };
+        let mailer: Arc<dyn Mailer> = Arc::new(LogMailer::new(mail.from, outbox));
+
+        let auth_service = AuthService::new(user_arc.clone(), token_db_arc.clone(), mailer, mail.reset_url);
         self.auth_service = Some(Arc::new(auth_service));
 
-        let jwt_service = JwtService::new();
-        self.jwt_service = Some(Arc::new(jwt_service));
+        let jwt_service = match JwtService::new(conf.get_jwt_properties(), &conf.get_server_properties().profile){
+            Ok(j) => Arc::new(j),
+            Err(err) => bail!("Error initialising jwt service: {}", err)
+        };
+        self.jwt_service = Some(jwt_service.clone());
+
+        let session_service = SessionService::new(token_db_arc.clone(), user_arc.clone(), jwt_service.clone());
+        self.session_service = Some(Arc::new(session_service));
         
-        let post_service = PostService::new(logger_db_arc.clone());
+        let records_service = Arc::new(RecordsService::new(records_db_arc.clone(), workout_db_arc.clone()));
+        self.records_service = Some(records_service.clone());
+
+        let ownership_service = Arc::new(OwnershipService::new(workout_db_arc.clone()));
+
+        let post_service = PostService::new(logger_db_arc.clone(), records_service.clone(), ownership_service.clone(), measurement_db_arc.clone());
         self.post_service = Some(Arc::new(post_service));
         
-        let get_service = GetService::new(workout_db_arc.clone(),user_arc.clone());
+        let get_service = GetService::new(workout_db_arc.clone(),user_arc.clone(), measurement_db_arc.clone());
         self.get_service = Some(Arc::new(get_service));
 
-        let put_service = PutService::new(logger_db_arc.clone());
+        let put_service = PutService::new(logger_db_arc.clone(), records_service.clone(), measurement_db_arc.clone());
         self.put_service = Some(Arc::new(put_service));
+
+        let routine_service = Arc::new(RoutineService::new(routine_db_arc.clone(), logger_db_arc.clone(), ownership_service.clone()));
+        self.routine_service = Some(routine_service.clone());
+
+        let program_service = ProgramService::new(program_db_arc.clone(), workout_db_arc.clone(), routine_service.clone(), ownership_service.clone());
+        self.program_service = Some(Arc::new(program_service));
+
+        let metrics_service = MetricsService::new(user_arc.clone(), workout_db_arc.clone(), measurement_db_arc.clone());
+        self.metrics_service = Some(Arc::new(metrics_service));
+
+        let export_service = ExportService::new(export_db_arc.clone());
+        self.export_service = Some(Arc::new(export_service));
+
+        let import_service = ImportService::new(logger_db_arc.clone(), workout_db_arc.clone(), records_service.clone());
+        self.import_service = Some(Arc::new(import_service));
+
+        let search_service = SearchService::new(search_db_arc.clone());
+        self.search_service = Some(Arc::new(search_service));
         Ok(())
     }
 }
\ No newline at end of file
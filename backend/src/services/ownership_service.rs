@@ -0,0 +1,69 @@
+use std::sync::Arc;
+use log::warn;
+use crate::{db::workouts::WorkoutDB, error::{ApiError, ApiResult}};
+
+/// Catalogue entries owned by this user are shared with everyone.
+pub const SYSTEM_USER_ID: i32 = 0;
+
+/// Checks that ids referenced in a request point at rows the caller may use. Missing rows
+/// are reported as 404, rows owned by someone else as 403.
+pub struct OwnershipService{
+    workout: Arc<WorkoutDB>,
+}
+
+impl OwnershipService{
+    pub fn new(workout: Arc<WorkoutDB>) -> Self{
+        OwnershipService {
+            workout
+        }
+    }
+
+    fn check_catalogue_owner(user_id: i32, owner: Option<i32>, kind: &str, id: i32) -> ApiResult<()>{
+        match owner{
+            Some(o) if o == user_id || o == SYSTEM_USER_ID => Ok(()),
+            _ => {
+                warn!("user_id {} referenced {} {} they don't own", user_id, kind, id);
+                Err(ApiError::Forbidden(format!("You don't have access to this {}", kind)))
+            }
+        }
+    }
+
+    pub async fn check_session(&self, user_id: i32, session_id: i32) -> ApiResult<()>{
+        match self.workout.get_session(session_id).await?{
+            Some(session) if session.user_id == user_id => Ok(()),
+            Some(_) => {
+                warn!("user_id {} referenced session {} they don't own", user_id, session_id);
+                Err(ApiError::Forbidden("You don't have access to this session".to_string()))
+            }
+            None => Err(ApiError::NotFound("Session not found".to_string())),
+        }
+    }
+
+    pub async fn check_optional_session(&self, user_id: i32, session_id: Option<i32>) -> ApiResult<()>{
+        match session_id{
+            Some(id) => self.check_session(user_id, id).await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn check_variation(&self, user_id: i32, variation_id: i32) -> ApiResult<()>{
+        match self.workout.get_variation(variation_id).await?{
+            Some(variation) => Self::check_catalogue_owner(user_id, variation.user_id, "variation", variation_id),
+            None => Err(ApiError::NotFound("Variation not found".to_string())),
+        }
+    }
+
+    pub async fn check_muscle_group(&self, user_id: i32, muscle_group_id: i32) -> ApiResult<()>{
+        match self.workout.get_muscle_group(muscle_group_id).await?{
+            Some(group) => Self::check_catalogue_owner(user_id, group.user_id, "muscle group", muscle_group_id),
+            None => Err(ApiError::NotFound("Muscle group not found".to_string())),
+        }
+    }
+
+    pub async fn check_cardio_exercise(&self, user_id: i32, cardio_exercise_id: i32) -> ApiResult<()>{
+        match self.workout.get_cardio_exercise(cardio_exercise_id).await?{
+            Some(exercise) => Self::check_catalogue_owner(user_id, exercise.user_id, "cardio exercise", cardio_exercise_id),
+            None => Err(ApiError::NotFound("Cardio exercise not found".to_string())),
+        }
+    }
+}
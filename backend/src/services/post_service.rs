@@ -1,9 +1,11 @@
-use std::sync::Arc;
+use std::{collections::BTreeSet, sync::Arc};
 use log::{error, info};
 use serde::Serialize;
-use crate::{api::workouts::{ CardioSet, CreateCardioExerciseRequest, CreateMuscleGroupRequest, CreateVariationRequest, StrengthSet, WorkoutSession}, 
-            db::{logger::LoggerDB, 
-                model::{NewCardioExercise, NewCardioLog, NewMuscleGroup, NewVariation, NewWorkoutSession, NewWorkoutSet}}};
+use crate::{api::{measurements::MeasurementRequest, validation::MAX_MINUTES_PER_DAY, workouts::{ CardioSet, CreateCardioExerciseRequest, CreateMuscleGroupRequest, CreateVariationRequest, FullSessionRequest, ImportTrackQuery, StrengthSet, WorkoutSession}}, 
+            db::{logger::LoggerDB, measurements::MeasurementDB,
+                model::{self, BodyMeasurement, CardioLog, NewBodyMeasurement, NewCardioExercise, NewCardioLog, NewMuscleGroup, NewTrackPoint, NewVariation, NewWorkoutSession, NewWorkoutSet, PersonalRecord, WorkoutSet}},
+            error::{ApiError, ApiResult},
+            services::{ownership_service::OwnershipService, records_service::RecordsService, track_import::{self, TrackFormat}}};
 
 #[derive(Debug, Serialize)]
 pub struct PostResponse{
@@ -11,20 +13,28 @@ pub struct PostResponse{
     pub id: Option<i32>,
     pub success: bool,
     pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub new_records: Vec<PersonalRecord>,
 }
 
 pub struct PostService{
-    logger: Arc<LoggerDB>
+    logger: Arc<LoggerDB>,
+    records: Arc<RecordsService>,
+    ownership: Arc<OwnershipService>,
+    measurements: Arc<MeasurementDB>,
 }
 
 impl PostService{
-    pub fn new(logger: Arc<LoggerDB>) -> Self{
+    pub fn new(logger: Arc<LoggerDB>, records: Arc<RecordsService>, ownership: Arc<OwnershipService>, measurements: Arc<MeasurementDB>) -> Self{
         PostService{
-            logger
+            logger,
+            records,
+            ownership,
+            measurements
         }
     }
 
-    pub async fn add_workout_session(&self, session_request: WorkoutSession) -> PostResponse{
+    pub async fn add_workout_session(&self, session_request: WorkoutSession) -> ApiResult<PostResponse>{
         let workout_session = NewWorkoutSession{
             user_id: session_request.user_id,
             date: session_request.date,
@@ -32,164 +42,344 @@ impl PostService{
             notes: session_request.notes,
             start_time: session_request.start_time,
             end_time: session_request.end_time,
+            program_day_id: None,
         };
-        match self.logger.add_workout_session(workout_session).await{
-            Ok(session) => {
-                info!("Workout session added with ID: {}", session.id);
-                PostResponse { 
-                    user_id: session.user_id,
-                    id: Some(session.id), 
-                    success: true, 
-                    message: "Session Added".to_string()
-                }
-            },
+        let session = match self.logger.add_workout_session(workout_session).await{
+            Ok(s) => s,
             Err(err) => {
-                error!("Error adding workout session: {}", err);
-                PostResponse{
-                    user_id: 1, 
-                    id: None,
-                    success: false,
-                    message: format!("{}",err)
-                }
+                error!("Error adding workout session for user_id {}: {}", session_request.user_id, err);
+                return Err(err);
             }
+        };
+        info!("Workout session added with ID: {}", session.id);
+        Ok(PostResponse { 
+            user_id: session.user_id,
+            id: Some(session.id), 
+            success: true, 
+            message: "Session Added".to_string(),
+            new_records: Vec::new(),
+        })
+    }
+
+    /// Logs a whole session at once. References are checked up front so the transaction only
+    /// fails on database errors, and PRs are rebuilt once per variation afterwards.
+    pub async fn add_full_session(&self, user_id: i32, request: FullSessionRequest) -> ApiResult<(model::WorkoutSession, Vec<WorkoutSet>, Vec<CardioLog>)>{
+        let variation_ids: BTreeSet<i32> = request.sets.iter().map(|s| s.variation_id).collect();
+        for variation_id in variation_ids.iter(){
+            self.ownership.check_variation(user_id, *variation_id).await?;
         }
+        let cardio_ids: BTreeSet<i32> = request.cardio_logs.iter().map(|c| c.cardio_exercise_id).collect();
+        for cardio_id in cardio_ids.iter(){
+            self.ownership.check_cardio_exercise(user_id, *cardio_id).await?;
+        }
+
+        let session = NewWorkoutSession{
+            user_id,
+            date: request.date,
+            title: request.title,
+            notes: request.notes,
+            start_time: request.start_time,
+            end_time: request.end_time,
+            program_day_id: None,
+        };
+        // Sets without an explicit position keep the order they were sent in
+        let sets = request.sets.into_iter()
+            .enumerate()
+            .map(|(i, s)| NewWorkoutSet{
+                workout_session_id: None,
+                user_id,
+                variation_id: s.variation_id,
+                weight: s.weight,
+                reps: s.reps,
+                performed_on: request.date,
+                set_index: Some(s.details.set_index.unwrap_or(i as i32 + 1)),
+                set_type: s.details.set_type.unwrap_or_default().as_str().to_string(),
+                rpe: s.details.rpe,
+                rir: s.details.rir,
+                rest_seconds: s.details.rest_seconds,
+                tempo: s.details.tempo,
+                completed: true,
+            })
+            .collect();
+        let logs = request.cardio_logs.into_iter()
+            .map(|c| NewCardioLog{
+                workout_session_id: None,
+                user_id,
+                cardio_exercise_id: c.cardio_exercise_id,
+                duration_minutes: c.duration,
+                performed_on: Some(request.date),
+                distance_m: c.details.distance,
+                elevation_gain_m: c.details.elevation_gain,
+                avg_heart_rate: c.details.avg_heart_rate,
+                max_heart_rate: c.details.max_heart_rate,
+                calories: c.details.calories,
+                started_at: c.details.started_at,
+                completed: true,
+            })
+            .collect();
+
+        let (session, sets, logs) = match self.logger.add_full_session(session, sets, logs).await{
+            Ok(r) => r,
+            Err(err) => {
+                error!("Error adding full workout session for user_id {}: {}", user_id, err);
+                return Err(err);
+            }
+        };
+        if let Err(err) = self.records.rebuild_session_records(user_id, variation_ids.into_iter().collect()).await{
+            error!("Error rebuilding personal records for user_id {}: {}", user_id, err);
+        }
+
+        info!("Full workout session {} added with {} set(s) and {} cardio log(s)", session.id, sets.len(), logs.len());
+        Ok((session, sets, logs))
     }
 
-    pub async fn add_workout_set(&self, session_request: StrengthSet) -> PostResponse{
+    pub async fn add_workout_set(&self, session_request: StrengthSet) -> ApiResult<PostResponse>{
+        self.ownership.check_optional_session(session_request.user_id, session_request.workout_session_id).await?;
+        self.ownership.check_variation(session_request.user_id, session_request.variation_id).await?;
+
         let workout_session = NewWorkoutSet{
             user_id: session_request.user_id,
             workout_session_id: session_request.workout_session_id,
             variation_id: session_request.variation_id,
             weight: session_request.weight,
             reps: session_request.reps,
-            performed_on: session_request.performed_on
-        };
-        if let Err(err) = self.logger.add_workout_set(workout_session).await{
-            info!("Error adding workout set: {}", err);
-            return PostResponse{
-                user_id: session_request.user_id,
-                id: None,
-                success: false,
-                message: format!("{}",err)
+            performed_on: session_request.performed_on,
+            set_index: session_request.details.set_index,
+            set_type: session_request.details.set_type.unwrap_or_default().as_str().to_string(),
+            rpe: session_request.details.rpe,
+            rir: session_request.details.rir,
+            rest_seconds: session_request.details.rest_seconds,
+            tempo: session_request.details.tempo,
+            completed: true,
+        };
+        let inserted_set = match self.logger.add_workout_set(workout_session).await{
+            Ok(set) => set,
+            Err(err) => {
+                error!("Error adding workout set for user_id {}: {}", session_request.user_id, err);
+                return Err(err);
+            }
+        };
+
+        let new_records = match self.records.record_new_set(&inserted_set).await{
+            Ok(records) => records,
+            Err(err) => {
+                error!("Error checking personal records for set {}: {}", inserted_set.id, err);
+                Vec::new()
             }
         };
 
         info!("Workout set added for user ID: {}", session_request.user_id);
-        PostResponse {
+        Ok(PostResponse {
             user_id: session_request.user_id, 
-            id: None,
+            id: Some(inserted_set.id),
             success: true, 
-            message: "Set Added".to_string()
-        }
+            message: "Set Added".to_string(),
+            new_records,
+        })
     }
 
-    pub async fn add_cardio_set(&self, session_request: CardioSet) -> PostResponse{
+    pub async fn add_cardio_set(&self, session_request: CardioSet) -> ApiResult<PostResponse>{
+        self.ownership.check_optional_session(session_request.user_id, session_request.workout_session_id).await?;
+        self.ownership.check_cardio_exercise(session_request.user_id, session_request.cardio_exercise_id).await?;
+
         let workout_session = NewCardioLog{
             user_id: session_request.user_id,
             workout_session_id: session_request.workout_session_id,
             cardio_exercise_id: session_request.cardio_exercise_id,
-            duration_minutes: session_request.duration
-            
-        };
-        if let Err(err) = self.logger.add_workout_cardio(workout_session).await{
-            error!("Error adding cardio log: {}", err);
-            return PostResponse{
-                user_id: session_request.user_id,
-                id: None,
-                success: false,
-                message: format!("{}",err)
-            };
-        }
+            duration_minutes: session_request.duration,
+            performed_on: session_request.performed_on.or(session_request.details.started_at.map(|s| s.date())),
+            distance_m: session_request.details.distance,
+            elevation_gain_m: session_request.details.elevation_gain,
+            avg_heart_rate: session_request.details.avg_heart_rate,
+            max_heart_rate: session_request.details.max_heart_rate,
+            calories: session_request.details.calories,
+            started_at: session_request.details.started_at,
+            completed: true,
+        };
+        let log = match self.logger.add_workout_cardio(workout_session).await{
+            Ok(log) => log,
+            Err(err) => {
+                error!("Error adding cardio log for user_id {}: {}", session_request.user_id, err);
+                return Err(err);
+            }
+        };
 
         info!("Cardio log added for user ID: {}", session_request.user_id);
-        PostResponse { 
+        Ok(PostResponse { 
             user_id: session_request.user_id, 
-            id: None,
+            id: Some(log.id),
             success: true, 
-            message: "Cardio Log Added".to_string()
+            message: "Cardio Log Added".to_string(),
+            new_records: Vec::new(),
+        })
+    }
+
+    /// Creates a cardio log from a GPX, TCX or FIT file, plus a session around it when the
+    /// request doesn't name one. The parsed points are kept for drawing the track later.
+    pub async fn import_cardio_track(&self, user_id: i32, request: ImportTrackQuery, data: &[u8]) -> ApiResult<(Option<model::WorkoutSession>, CardioLog, usize)>{
+        self.ownership.check_optional_session(user_id, request.workout_session_id).await?;
+        self.ownership.check_cardio_exercise(user_id, request.cardio_exercise_id).await?;
+
+        let format = match request.format.or_else(|| TrackFormat::detect(data)){
+            Some(f) => f,
+            None => return Err(ApiError::BadRequest("Unrecognised file, expected GPX, TCX or FIT".to_string())),
+        };
+        let track = track_import::parse(format, data)?;
+        let stats = track.stats()?;
+        let duration_minutes = ((stats.duration_seconds + 30) / 60).max(1) as i32;
+        if duration_minutes > MAX_MINUTES_PER_DAY {
+            return Err(ApiError::Validation("Activity can't last longer than 24 hours".to_string()));
         }
+
+        let started_at = stats.started_at;
+        let session = match request.workout_session_id{
+            Some(_) => None,
+            None => Some(NewWorkoutSession{
+                user_id,
+                title: Some(track.name.clone().unwrap_or_else(|| format!("Imported {} activity", format.as_str()))),
+                notes: None,
+                date: started_at.date(),
+                start_time: started_at,
+                end_time: started_at + chrono::Duration::seconds(stats.duration_seconds),
+                program_day_id: None,
+            }),
+        };
+        let log = NewCardioLog{
+            workout_session_id: request.workout_session_id,
+            user_id,
+            cardio_exercise_id: request.cardio_exercise_id,
+            duration_minutes,
+            performed_on: Some(started_at.date()),
+            distance_m: (stats.distance_m > 0.0).then_some(stats.distance_m),
+            elevation_gain_m: (stats.elevation_gain_m > 0.0).then_some(stats.elevation_gain_m),
+            avg_heart_rate: stats.avg_heart_rate,
+            max_heart_rate: stats.max_heart_rate,
+            calories: None,
+            started_at: Some(started_at),
+            completed: true,
+        };
+        let points: Vec<NewTrackPoint> = track.points.into_iter()
+            .enumerate()
+            .map(|(i, p)| NewTrackPoint{
+                cardio_log_id: 0,
+                seq: i as i32,
+                recorded_at: p.time,
+                latitude: p.latitude,
+                longitude: p.longitude,
+                elevation_m: p.elevation_m,
+                heart_rate: p.heart_rate,
+                distance_m: p.distance_m,
+            })
+            .collect();
+        let point_count = points.len();
+
+        let (session, log) = match self.logger.add_imported_cardio(session, log, points).await{
+            Ok(r) => r,
+            Err(err) => {
+                error!("Error importing {} track for user_id {}: {}", format.as_str(), user_id, err);
+                return Err(err);
+            }
+        };
+        info!("Imported {} track with {} point(s) as cardio log {} for user_id: {}", format.as_str(), point_count, log.id, user_id);
+        Ok((session, log, point_count))
     }
 
-    pub async fn add_muscle_group(&self, user_id: i32, request: CreateMuscleGroupRequest) -> PostResponse {
+    pub async fn add_muscle_group(&self, user_id: i32, request: CreateMuscleGroupRequest) -> ApiResult<PostResponse> {
         let new_mg = NewMuscleGroup {
             name: &request.name,
             user_id,
         };
-        match self.logger.add_muscle_group(new_mg).await {
-            Ok(mg) => {
-                info!("Adding muscle group for user_id: {}", user_id);
-                PostResponse {
-                    user_id,
-                    id: Some(mg.id),
-                    success: true,
-                    message: "Muscle Group Added".to_string()
-                }
-            },
+        let mg = match self.logger.add_muscle_group(new_mg).await {
+            Ok(mg) => mg,
             Err(err) => {
                 error!("Error adding muscle group for user_id {}: {}", user_id, err);
-                PostResponse {
-                    user_id,
-                    id: None,
-                    success: false,
-                    message: format!("{}", err)
-                }
+                return Err(err);
             }
-        }
+        };
+        info!("Adding muscle group for user_id: {}", user_id);
+        Ok(PostResponse {
+            user_id,
+            id: Some(mg.id),
+            success: true,
+            message: "Muscle Group Added".to_string(),
+            new_records: Vec::new(),
+        })
     }
 
-    pub async fn add_variation(&self, user_id: i32, request: CreateVariationRequest) -> PostResponse {
+    pub async fn add_variation(&self, user_id: i32, request: CreateVariationRequest) -> ApiResult<PostResponse> {
+        self.ownership.check_muscle_group(user_id, request.muscle_group_id).await?;
+
         let new_var = NewVariation {
             muscle_group_id: request.muscle_group_id,
             name: &request.name,
             user_id,
             description: None, // Or add to request if needed
         };
-        match self.logger.add_variation(new_var).await {
-            Ok(var) => {
-                info!("Variation added for user_id: {}", user_id);
-                PostResponse {
-                    user_id,
-                    id: Some(var.id),
-                    success: true,
-                    message: "Variation Added".to_string()
-                }
-            },
+        let var = match self.logger.add_variation(new_var).await {
+            Ok(var) => var,
             Err(err) => {
                 error!("Error adding variation for user_id {}: {}", user_id, err);
-                PostResponse {
-                    user_id,
-                    id: None,
-                    success: false,
-                    message: format!("{}", err)
-                }
+                return Err(err);
             }
-        }
+        };
+        info!("Variation added for user_id: {}", user_id);
+        Ok(PostResponse {
+            user_id,
+            id: Some(var.id),
+            success: true,
+            message: "Variation Added".to_string(),
+            new_records: Vec::new(),
+        })
     }
 
-    pub async fn add_cardio_exercise(&self, user_id: i32, request: CreateCardioExerciseRequest) -> PostResponse {
+    pub async fn add_cardio_exercise(&self, user_id: i32, request: CreateCardioExerciseRequest) -> ApiResult<PostResponse> {
         let new_ex = NewCardioExercise {
             name: &request.name,
             user_id,
+            met: request.met,
         };
-        match self.logger.add_cardio_exercise(new_ex).await {
-            Ok(ex) => {
-                info!("Cardio exercise added for user_id: {}", user_id);
-                PostResponse {
-                    user_id,
-                    id: Some(ex.id),
-                    success: true,
-                    message: "Cardio Exercise Added".to_string()
-                }
-            },
+        let ex = match self.logger.add_cardio_exercise(new_ex).await {
+            Ok(ex) => ex,
             Err(err) => {
                 error!("Error adding cardio exercise for user_id {}: {}", user_id, err);
-                PostResponse {
-                    user_id,
-                    id: None,
-                    success: false,
-                    message: format!("{}", err)
-                }
+                return Err(err);
+            }
+        };
+        info!("Cardio exercise added for user_id: {}", user_id);
+        Ok(PostResponse {
+            user_id,
+            id: Some(ex.id),
+            success: true,
+            message: "Cardio Exercise Added".to_string(),
+            new_records: Vec::new(),
+        })
+    }
+
+    /// Logs a body measurement, a weight reading also becomes the user's current weight when it's the latest.
+    pub async fn add_measurement(&self, user_id: i32, measured_on: chrono::NaiveDate, request: MeasurementRequest) -> ApiResult<BodyMeasurement> {
+        let values = request.values;
+        let data = NewBodyMeasurement{
+            user_id,
+            measured_on,
+            weight: values.weight,
+            body_fat_percent: values.body_fat_percent,
+            neck: values.neck,
+            chest: values.chest,
+            waist: values.waist,
+            hips: values.hips,
+            arms: values.arms,
+            thighs: values.thighs,
+            notes: request.notes,
+        };
+        match self.measurements.add_measurement(data).await {
+            Ok(m) => {
+                info!("Body measurement added for user_id: {}", user_id);
+                Ok(m)
+            }
+            Err(err) => {
+                error!("Error adding body measurement for user_id {}: {}", user_id, err);
+                Err(err)
             }
         }
     }
-}
\ No newline at end of file
+}
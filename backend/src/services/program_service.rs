@@ -0,0 +1,421 @@
+use std::{collections::HashSet, sync::Arc};
+use chrono::Utc;
+use log::{error, info};
+use serde::Serialize;
+use crate::{api::{programs::ProgramRequest, routines::StartRoutineQuery},
+            db::{programs::{ProgramDB, ProgramPlan}, workouts::WorkoutDB,
+                model::{CardioLog, NewProgram, NewProgramDay, NewProgramWeek, NewProgressionRule, Program, ProgramDay, ProgressionRule, ProgressionType, Routine, RoutineExercise, SetType, UpdateProgram, WorkoutSession, WorkoutSet}},
+            error::{ApiError, ApiResult},
+            services::{get_service::OneRepMaxFormula, ownership_service::OwnershipService, routine_service::RoutineService}};
+
+/// Computed loads are rounded to what a pair of the smallest common plates can make.
+pub const LOAD_STEP_KG: f64 = 2.5;
+pub const DEFAULT_DELOAD_PERCENT: f64 = 60.0;
+const DEFAULT_INCREMENT_KG: f64 = 2.5;
+
+/// What the next session of a program asks for, one entry per routine exercise.
+#[derive(Debug, Serialize)]
+pub struct NextSession{
+    pub program_id: i32,
+    pub program_day_id: i32,
+    pub week_number: i32,
+    pub day_number: i32,
+    pub deload: bool,
+    pub routine_id: i32,
+    pub routine_name: String,
+    pub exercises: Vec<PrescribedExercise>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrescribedExercise{
+    pub routine_exercise_id: i32,
+    pub position: i32,
+    pub variation_id: Option<i32>,
+    pub cardio_exercise_id: Option<i32>,
+    pub sets: i32,
+    pub reps: Option<i32>,
+    pub weight: Option<f64>,
+    /// Rule the weight came from, none means the routine's target weight was used as is
+    pub progression: Option<ProgressionType>,
+    pub rest_seconds: Option<i32>,
+    pub target_duration_minutes: Option<i32>,
+    pub notes: Option<String>,
+}
+
+fn round_load(kg: f64) -> f64{
+    (kg / LOAD_STEP_KG).round() * LOAD_STEP_KG
+}
+
+/// Working sets of the most recent session in `history`, which is ordered oldest first.
+/// Sessions from deload weeks are passed over so the block picks up from the last full week.
+fn last_session<'a>(history: &'a [WorkoutSet], deload_sessions: &HashSet<i32>) -> Vec<&'a WorkoutSet>{
+    let working: Vec<&WorkoutSet> = history.iter()
+        .filter(|s| s.set_type != SetType::Warmup.as_str())
+        .filter(|s| s.workout_session_id.is_none_or(|id| !deload_sessions.contains(&id)))
+        .collect();
+    let last = match working.last(){
+        Some(s) => *s,
+        None => return Vec::new(),
+    };
+    working.into_iter()
+        .filter(|s| match last.workout_session_id{
+            Some(id) => s.workout_session_id == Some(id),
+            None => s.workout_session_id.is_none() && s.performed_on == last.performed_on,
+        })
+        .collect()
+}
+
+/// Weight and reps for the next time `exercise` is trained, from the rule and the completed history.
+fn prescribe(rule: Option<(ProgressionType, &ProgressionRule)>, exercise: &RoutineExercise, history: &[WorkoutSet], deload_sessions: &HashSet<i32>) -> (Option<f64>, Option<i32>){
+    let target_reps = exercise.reps_max.or(exercise.reps_min);
+    let (kind, rule) = match rule{
+        Some(r) => r,
+        None => return (exercise.target_weight, target_reps),
+    };
+    let increment = rule.increment.unwrap_or(DEFAULT_INCREMENT_KG);
+    let last = last_session(history, deload_sessions);
+    let top = last.iter().map(|s| s.weight).fold(0.0, f64::max);
+    let top_sets: Vec<&&WorkoutSet> = last.iter().filter(|s| s.weight >= top).collect();
+
+    match kind{
+        ProgressionType::Linear => {
+            if last.is_empty() {
+                return (exercise.target_weight, target_reps);
+            }
+            let hit = top_sets.iter().all(|s| target_reps.is_none_or(|t| s.reps >= t));
+            (Some(if hit { top + increment } else { top }), target_reps)
+        }
+        ProgressionType::Double => {
+            let low = exercise.reps_min.or(exercise.reps_max);
+            let high = exercise.reps_max.or(exercise.reps_min);
+            let (low, high) = match (low, high){
+                (Some(l), Some(h)) => (l, h),
+                _ => return (exercise.target_weight, target_reps),
+            };
+            if last.is_empty() {
+                return (exercise.target_weight, Some(low));
+            }
+            let fewest = top_sets.iter().map(|s| s.reps).min().unwrap_or(0);
+            if fewest >= high {
+                (Some(top + increment), Some(low))
+            } else {
+                (Some(top), Some((fewest + 1).clamp(low, high)))
+            }
+        }
+        ProgressionType::E1rm => {
+            let best = history.iter()
+                .filter(|s| s.set_type != SetType::Warmup.as_str())
+                .map(|s| OneRepMaxFormula::Epley.estimate(s.weight, s.reps))
+                .fold(0.0, f64::max);
+            if best <= 0.0 {
+                return (exercise.target_weight, target_reps);
+            }
+            let percent = rule.percent.unwrap_or(100.0);
+            (Some(round_load(best * percent / 100.0)), target_reps)
+        }
+    }
+}
+
+pub struct ProgramService{
+    programs: Arc<ProgramDB>,
+    workout: Arc<WorkoutDB>,
+    routines: Arc<RoutineService>,
+    ownership: Arc<OwnershipService>,
+}
+
+impl ProgramService{
+    pub fn new(programs: Arc<ProgramDB>, workout: Arc<WorkoutDB>, routines: Arc<RoutineService>, ownership: Arc<OwnershipService>) -> Self{
+        ProgramService{
+            programs,
+            workout,
+            routines,
+            ownership
+        }
+    }
+
+    pub async fn get_programs(&self, user_id: i32) -> ApiResult<Vec<Program>>{
+        info!("Fetching programs for user_id: {}", user_id);
+        self.programs.get_programs(user_id).await
+    }
+
+    pub async fn get_program(&self, user_id: i32, program_id: i32) -> ApiResult<ProgramPlan>{
+        match self.programs.get_program(user_id, program_id).await?{
+            Some(p) => Ok(p),
+            None => Err(ApiError::NotFound("Program not found".to_string())),
+        }
+    }
+
+    /// Checks the referenced routines and variations, and numbers weeks and days in the order sent.
+    async fn build_plan(&self, user_id: i32, request: &ProgramRequest) -> ApiResult<(Vec<(NewProgramWeek, Vec<NewProgramDay>)>, Vec<NewProgressionRule>)>{
+        let mut weeks = Vec::with_capacity(request.weeks.len());
+        for (i, week) in request.weeks.iter().enumerate(){
+            let mut days = Vec::with_capacity(week.routine_ids.len());
+            for (j, routine_id) in week.routine_ids.iter().enumerate(){
+                self.routines.get_routine(user_id, *routine_id).await?;
+                days.push(NewProgramDay{
+                    program_week_id: 0,
+                    day_number: j as i32 + 1,
+                    routine_id: *routine_id,
+                });
+            }
+            weeks.push((NewProgramWeek{
+                program_id: 0,
+                week_number: i as i32 + 1,
+                deload: week.deload.unwrap_or(false),
+            }, days));
+        }
+
+        let mut rules = Vec::with_capacity(request.rules.len());
+        for rule in request.rules.iter(){
+            if let Some(variation_id) = rule.variation_id {
+                self.ownership.check_variation(user_id, variation_id).await?;
+            }
+            rules.push(NewProgressionRule{
+                program_id: 0,
+                variation_id: rule.variation_id,
+                rule_type: rule.rule.as_str().to_string(),
+                increment: rule.increment,
+                percent: rule.percent,
+            });
+        }
+        Ok((weeks, rules))
+    }
+
+    pub async fn create_program(&self, user_id: i32, request: ProgramRequest) -> ApiResult<ProgramPlan>{
+        let (weeks, rules) = self.build_plan(user_id, &request).await?;
+        let program = NewProgram{
+            user_id,
+            name: request.name.trim().to_string(),
+            notes: request.notes,
+            deload_percent: request.deload_percent.unwrap_or(DEFAULT_DELOAD_PERCENT),
+        };
+        match self.programs.add_program(program, weeks, rules).await{
+            Ok(p) => {
+                info!("Program {} created for user_id {}", p.program.id, user_id);
+                Ok(p)
+            }
+            Err(err) => {
+                error!("Error creating program for user_id {}: {}", user_id, err);
+                Err(err)
+            }
+        }
+    }
+
+    pub async fn update_program(&self, user_id: i32, program_id: i32, request: ProgramRequest) -> ApiResult<ProgramPlan>{
+        let (weeks, rules) = self.build_plan(user_id, &request).await?;
+        let data = UpdateProgram{
+            name: request.name.trim().to_string(),
+            notes: request.notes,
+            deload_percent: request.deload_percent.unwrap_or(DEFAULT_DELOAD_PERCENT),
+            updated_at: Utc::now().naive_utc(),
+        };
+        match self.programs.replace_program(user_id, program_id, data, weeks, rules).await{
+            Ok(p) => {
+                info!("Program {} updated", program_id);
+                Ok(p)
+            }
+            Err(err) => {
+                error!("Error updating program {} for user_id {}: {}", program_id, user_id, err);
+                Err(err)
+            }
+        }
+    }
+
+    pub async fn delete_program(&self, user_id: i32, program_id: i32) -> ApiResult<()>{
+        if let Err(err) = self.programs.delete_program(user_id, program_id).await{
+            error!("Error deleting program {} for user_id {}: {}", program_id, user_id, err);
+            return Err(err);
+        }
+        info!("Program deleted: {}", program_id);
+        Ok(())
+    }
+
+    /// The day after the last one the user started, wrapping back to the first once the block is done.
+    async fn next_day(&self, user_id: i32, plan: &ProgramPlan) -> ApiResult<(i32, bool, ProgramDay)>{
+        let days: Vec<(i32, bool, &ProgramDay)> = plan.weeks.iter()
+            .flat_map(|(week, days)| days.iter().map(move |d| (week.week_number, week.deload, d)))
+            .collect();
+        if days.is_empty() {
+            return Err(ApiError::BadRequest("Program has no days".to_string()));
+        }
+        let day_ids = days.iter().map(|(_, _, d)| d.id).collect();
+        let last = self.programs.get_last_program_day(user_id, day_ids).await?;
+        let index = last
+            .and_then(|id| days.iter().position(|(_, _, d)| d.id == id))
+            .map(|i| (i + 1) % days.len())
+            .unwrap_or(0);
+        let (week_number, deload, day) = days[index];
+        Ok((week_number, deload, day.clone()))
+    }
+
+    async fn plan_next(&self, user_id: i32, program_id: i32) -> ApiResult<(NextSession, Routine, Vec<RoutineExercise>)>{
+        let plan = self.get_program(user_id, program_id).await?;
+        let (week_number, deload, day) = self.next_day(user_id, &plan).await?;
+        let (routine, exercises) = self.routines.get_routine(user_id, day.routine_id).await?;
+        let deload_sessions: HashSet<i32> = self.programs.get_deload_session_ids(user_id).await?.into_iter().collect();
+
+        let mut prescribed = Vec::with_capacity(exercises.len());
+        for exercise in exercises.iter(){
+            let (mut weight, reps, progression) = match exercise.variation_id{
+                Some(variation_id) => {
+                    let rule = plan.rules.iter().find(|r| r.variation_id == Some(variation_id))
+                        .or_else(|| plan.rules.iter().find(|r| r.variation_id.is_none()))
+                        .and_then(|r| ProgressionType::parse(&r.rule_type).map(|kind| (kind, r)));
+                    let history = self.workout.get_variation_sets(user_id, variation_id).await?;
+                    let (weight, reps) = prescribe(rule, exercise, &history, &deload_sessions);
+                    (weight, reps, rule.map(|(kind, _)| kind))
+                }
+                None => (None, None, None),
+            };
+            if deload {
+                weight = weight.map(|w| round_load(w * plan.program.deload_percent / 100.0));
+            }
+            prescribed.push(PrescribedExercise{
+                routine_exercise_id: exercise.id,
+                position: exercise.position,
+                variation_id: exercise.variation_id,
+                cardio_exercise_id: exercise.cardio_exercise_id,
+                sets: exercise.target_sets,
+                reps,
+                weight,
+                progression,
+                rest_seconds: exercise.rest_seconds,
+                target_duration_minutes: exercise.target_duration_minutes,
+                notes: exercise.notes.clone(),
+            });
+        }
+
+        let next = NextSession{
+            program_id,
+            program_day_id: day.id,
+            week_number,
+            day_number: day.day_number,
+            deload,
+            routine_id: routine.id,
+            routine_name: routine.name.clone(),
+            exercises: prescribed,
+        };
+        Ok((next, routine, exercises))
+    }
+
+    pub async fn get_next_session(&self, user_id: i32, program_id: i32) -> ApiResult<NextSession>{
+        info!("Working out next session of program {} for user_id {}", program_id, user_id);
+        let (next, _, _) = self.plan_next(user_id, program_id).await?;
+        Ok(next)
+    }
+
+    /// Starts the next day of the program as a session with the prescribed weights and reps planned.
+    pub async fn start_next_session(&self, user_id: i32, program_id: i32, request: StartRoutineQuery) -> ApiResult<(WorkoutSession, Vec<WorkoutSet>, Vec<CardioLog>)>{
+        let (next, routine, exercises) = self.plan_next(user_id, program_id).await?;
+        let planned: Vec<RoutineExercise> = exercises.into_iter()
+            .zip(next.exercises.iter())
+            .map(|(mut exercise, p)| {
+                if exercise.variation_id.is_some() {
+                    exercise.target_weight = p.weight;
+                    exercise.reps_min = p.reps;
+                    exercise.reps_max = p.reps;
+                }
+                exercise
+            })
+            .collect();
+        self.routines.start_session(user_id, &routine, &planned, request, Some(next.program_day_id)).await
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn set(id: i32, session_id: i32, day: u32, weight: f64, reps: i32) -> WorkoutSet{
+        WorkoutSet{
+            id,
+            workout_session_id: Some(session_id),
+            user_id: 1,
+            variation_id: 1,
+            weight,
+            reps,
+            performed_on: NaiveDate::from_ymd_opt(2026, 10, day).unwrap(),
+            set_index: Some(id),
+            set_type: SetType::Working.as_str().to_string(),
+            rpe: None,
+            rir: None,
+            rest_seconds: None,
+            tempo: None,
+            completed: true,
+        }
+    }
+
+    fn exercise(reps_min: i32, reps_max: i32) -> RoutineExercise{
+        RoutineExercise{
+            id: 1,
+            routine_id: 1,
+            position: 1,
+            variation_id: Some(1),
+            cardio_exercise_id: None,
+            target_sets: 3,
+            reps_min: Some(reps_min),
+            reps_max: Some(reps_max),
+            target_weight: Some(80.0),
+            rest_seconds: None,
+            target_duration_minutes: None,
+            notes: None,
+        }
+    }
+
+    fn rule(kind: ProgressionType) -> ProgressionRule{
+        ProgressionRule{
+            id: 1,
+            program_id: 1,
+            variation_id: None,
+            rule_type: kind.as_str().to_string(),
+            increment: Some(2.5),
+            percent: None,
+        }
+    }
+
+    /// Session 1 is a full week at 100 kg, session 2 the deload week after it at 60 kg.
+    fn history_with_deload() -> Vec<WorkoutSet>{
+        vec![
+            set(1, 1, 5, 100.0, 5), set(2, 1, 5, 100.0, 5), set(3, 1, 5, 100.0, 5),
+            set(4, 2, 12, 60.0, 5), set(5, 2, 12, 60.0, 5), set(6, 2, 12, 60.0, 5),
+        ]
+    }
+
+    #[test]
+    fn linear_progression_skips_deload_week(){
+        let rule = rule(ProgressionType::Linear);
+        let history = history_with_deload();
+        let deload: HashSet<i32> = [2].into_iter().collect();
+
+        let (weight, reps) = prescribe(Some((ProgressionType::Linear, &rule)), &exercise(5, 5), &history, &deload);
+        assert_eq!(weight, Some(102.5));
+        assert_eq!(reps, Some(5));
+
+        // Without knowing session 2 was a deload the block would restart from 60 kg
+        let (weight, _) = prescribe(Some((ProgressionType::Linear, &rule)), &exercise(5, 5), &history, &HashSet::new());
+        assert_eq!(weight, Some(62.5));
+    }
+
+    #[test]
+    fn double_progression_skips_deload_week(){
+        let rule = rule(ProgressionType::Double);
+        let mut history = history_with_deload();
+        history[2].reps = 4;
+        let deload: HashSet<i32> = [2].into_iter().collect();
+
+        let (weight, reps) = prescribe(Some((ProgressionType::Double, &rule)), &exercise(4, 6), &history, &deload);
+        assert_eq!(weight, Some(100.0));
+        assert_eq!(reps, Some(5));
+    }
+
+    #[test]
+    fn only_deload_sessions_fall_back_to_target(){
+        let rule = rule(ProgressionType::Linear);
+        let history: Vec<WorkoutSet> = history_with_deload().into_iter().filter(|s| s.workout_session_id == Some(2)).collect();
+        let deload: HashSet<i32> = [2].into_iter().collect();
+
+        let (weight, _) = prescribe(Some((ProgressionType::Linear, &rule)), &exercise(5, 5), &history, &deload);
+        assert_eq!(weight, Some(80.0));
+    }
+}
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 use log::{error, info};
 use serde::Serialize;
-use crate::db::{logger::LoggerDB, model::{UpdateCardioLog, UpdateWorkoutSession, UpdateWorkoutSet}};
+use crate::{api::{measurements::UpdateMeasurementRequest, workouts::{UpdateCardioRequest, UpdateSetRequest}}, db::{logger::LoggerDB, measurements::MeasurementDB, model::{BodyMeasurement, UpdateBodyMeasurement, UpdateCardioLog, UpdateWorkoutSession, UpdateWorkoutSet}}, error::ApiResult, services::records_service::RecordsService};
 
 #[derive(Debug, Serialize)]
 pub struct PutResponse{
@@ -11,133 +11,180 @@ pub struct PutResponse{
     pub message: String,
 }
 pub struct PutService{
-    logger: Arc<LoggerDB>
+    logger: Arc<LoggerDB>,
+    records: Arc<RecordsService>,
+    measurements: Arc<MeasurementDB>,
 }
 
 impl PutService{
-    pub fn new(logger: Arc<LoggerDB>) -> Self{
+    pub fn new(logger: Arc<LoggerDB>, records: Arc<RecordsService>, measurements: Arc<MeasurementDB>) -> Self{
         PutService{
-            logger
+            logger,
+            records,
+            measurements
         }
     }
 
-    pub async fn update_workout_session(&self, user_id: i32, session_id: i32, title: Option<String>, date: Option<chrono::NaiveDate>, start_time: Option<chrono::NaiveDateTime>, end_time: Option<chrono::NaiveDateTime>, notes: Option<String>) -> PutResponse {
+    pub async fn update_workout_session(&self, user_id: i32, session_id: i32, title: Option<String>, date: Option<chrono::NaiveDate>, start_time: Option<chrono::NaiveDateTime>, end_time: Option<chrono::NaiveDateTime>, notes: Option<String>) -> ApiResult<PutResponse> {
         let update_data = UpdateWorkoutSession { title, date, start_time, end_time, notes };
-        match self.logger.update_workout_session(user_id, session_id, update_data).await {
-            Ok(session) =>{ 
-                info!("Workout session updated: {:?}", session);
-                PutResponse {
-                    user_id,
-                    id: Some(session.id),
-                    success: true,
-                    message: "Session Updated".to_string()
-                }
-            },
+        let session = match self.logger.update_workout_session(user_id, session_id, update_data).await {
+            Ok(session) => session,
             Err(err) => {
                 error!("Error updating workout session for user_id {}: {}", user_id, err);
-                PutResponse {
-                    user_id,
-                    id: None,
-                    success: false,
-                    message: format!("{}", err)
-                }
+                return Err(err);
             }
-        }
+        };
+        info!("Workout session updated: {:?}", session);
+        Ok(PutResponse {
+            user_id,
+            id: Some(session.id),
+            success: true,
+            message: "Session Updated".to_string()
+        })
     }
 
-    pub async fn update_workout_set(&self, user_id: i32, set_id: i32, weight: Option<f64>, reps: Option<i32>) -> PutResponse {
-        let update_data = UpdateWorkoutSet { weight, reps };
-        match self.logger.update_workout_set(user_id, set_id, update_data).await {
-            Ok(set) => {
-                info!("Workout set updated: {:?}", set);
-                PutResponse {
-                    user_id,
-                    id: Some(set.id),
-                    success: true,
-                    message: "Set Updated".to_string()
-                }
-            },
+    pub async fn update_workout_set(&self, user_id: i32, set_id: i32, request: UpdateSetRequest) -> ApiResult<PutResponse> {
+        let details = request.details;
+        let update_data = UpdateWorkoutSet {
+            weight: request.weight,
+            reps: request.reps,
+            set_index: details.set_index,
+            set_type: details.set_type.map(|t| t.as_str().to_string()),
+            rpe: details.rpe,
+            rir: details.rir,
+            rest_seconds: details.rest_seconds,
+            tempo: details.tempo,
+            completed: request.completed,
+        };
+        let set = match self.logger.update_workout_set(user_id, set_id, update_data).await {
+            Ok(set) => set,
             Err(err) => {
                 error!("Error updating workout set for user_id {}: {}", user_id, err);
-                PutResponse {
-                    user_id,
-                    id: None,
-                    success: false,
-                    message: format!("{}", err)
-                }
+                return Err(err);
             }
+        };
+        info!("Workout set updated: {:?}", set);
+        if let Err(err) = self.records.rebuild_records(user_id, set.variation_id).await{
+            error!("Error rebuilding personal records for user_id {}: {}", user_id, err);
         }
+        Ok(PutResponse {
+            user_id,
+            id: Some(set.id),
+            success: true,
+            message: "Set Updated".to_string()
+        })
     }
 
-    pub async fn update_cardio_log(&self, user_id: i32, log_id: i32, duration_minutes: Option<i32>) -> PutResponse {
-        let update_data = UpdateCardioLog { duration_minutes };
-        match self.logger.update_cardio_log(user_id, log_id, update_data).await {
-            Ok(log) => {
-                info!("Cardio log updated: {:?}", log);
-                PutResponse {
-                    user_id,
-                    id: Some(log.id),
-                    success: true,
-                    message: "Cardio Log Updated".to_string()
-                }
-            },
+    pub async fn update_cardio_log(&self, user_id: i32, log_id: i32, request: UpdateCardioRequest) -> ApiResult<PutResponse> {
+        let details = request.details;
+        let update_data = UpdateCardioLog {
+            duration_minutes: request.duration,
+            distance_m: details.distance,
+            elevation_gain_m: details.elevation_gain,
+            avg_heart_rate: details.avg_heart_rate,
+            max_heart_rate: details.max_heart_rate,
+            calories: details.calories,
+            started_at: details.started_at,
+            completed: request.completed,
+        };
+        let log = match self.logger.update_cardio_log(user_id, log_id, update_data).await {
+            Ok(log) => log,
             Err(err) => {
                 error!("Error updating cardio log for user_id {}: {}", user_id, err);
-                PutResponse {
-                    user_id, id: None, success: false, message: format!("{}", err)
-                }
+                return Err(err);
             }
-        }
+        };
+        info!("Cardio log updated: {:?}", log);
+        Ok(PutResponse {
+            user_id,
+            id: Some(log.id),
+            success: true,
+            message: "Cardio Log Updated".to_string()
+        })
     }
 
-    pub async fn delete_workout_session(&self, user_id: i32, session_id: i32) -> PutResponse {
-        match self.logger.delete_workout_session(user_id, session_id).await {
-            Ok(_) => {
-                info!("Workout session deleted: {}", session_id);
-                PutResponse {
-                    user_id, id: Some(session_id), success: true, message: "Session Deleted".to_string()
-                }
-            },
+    pub async fn delete_workout_session(&self, user_id: i32, session_id: i32) -> ApiResult<PutResponse> {
+        let variation_ids = match self.records.get_session_variation_ids(user_id, session_id).await {
+            Ok(ids) => ids,
             Err(err) => {
-                error!("Error deleting workout session for user_id {}: {}", user_id, err);
-                PutResponse {
-                    user_id, id: None, success: false, message: format!("{}", err)
-                }
+                error!("Error fetching session variations for user_id {}: {}", user_id, err);
+                Vec::new()
             }
+        };
+        if let Err(err) = self.logger.delete_workout_session(user_id, session_id).await {
+            error!("Error deleting workout session for user_id {}: {}", user_id, err);
+            return Err(err);
+        }
+        info!("Workout session deleted: {}", session_id);
+        if let Err(err) = self.records.rebuild_session_records(user_id, variation_ids).await{
+            error!("Error rebuilding personal records for user_id {}: {}", user_id, err);
         }
+        Ok(PutResponse {
+            user_id, id: Some(session_id), success: true, message: "Session Deleted".to_string()
+        })
     }
 
-    pub async fn delete_workout_set(&self, user_id: i32, set_id: i32) -> PutResponse {
-        match self.logger.delete_workout_set(user_id, set_id).await {
-            Ok(_) => {
-                info!("Workout set deleted: {}", set_id);
-                PutResponse {
-                    user_id, id: Some(set_id), success: true, message: "Set Deleted".to_string()
-                }
-            },
+    pub async fn delete_workout_set(&self, user_id: i32, set_id: i32) -> ApiResult<PutResponse> {
+        let set = match self.logger.delete_workout_set(user_id, set_id).await {
+            Ok(set) => set,
             Err(err) => {
                 error!("Error deleting workout set for user_id {}: {}", user_id, err);
-                PutResponse {
-                    user_id, id: None, success: false, message: format!("{}", err)
-                }
+                return Err(err);
             }
+        };
+        info!("Workout set deleted: {}", set_id);
+        if let Err(err) = self.records.rebuild_records(user_id, set.variation_id).await{
+            error!("Error rebuilding personal records for user_id {}: {}", user_id, err);
         }
+        Ok(PutResponse {
+            user_id, id: Some(set_id), success: true, message: "Set Deleted".to_string()
+        })
     }
 
-    pub async fn delete_cardio_log(&self, user_id: i32, log_id: i32) -> PutResponse {
-        match self.logger.delete_cardio_log(user_id, log_id).await {
-            Ok(_) => {
-                info!("Cardio log deleted: {}", log_id);
-                PutResponse {
-                    user_id, id: Some(log_id), success: true, message: "Cardio Log Deleted".to_string()
-                }
-            },
+    pub async fn delete_cardio_log(&self, user_id: i32, log_id: i32) -> ApiResult<PutResponse> {
+        if let Err(err) = self.logger.delete_cardio_log(user_id, log_id).await {
+            error!("Error deleting cardio log for user_id {}: {}", user_id, err);
+            return Err(err);
+        }
+        info!("Cardio log deleted: {}", log_id);
+        Ok(PutResponse {
+            user_id, id: Some(log_id), success: true, message: "Cardio Log Deleted".to_string()
+        })
+    }
+
+    pub async fn update_measurement(&self, user_id: i32, measurement_id: i32, request: UpdateMeasurementRequest) -> ApiResult<BodyMeasurement> {
+        let values = request.values;
+        let update_data = UpdateBodyMeasurement {
+            measured_on: request.measured_on,
+            weight: values.weight,
+            body_fat_percent: values.body_fat_percent,
+            neck: values.neck,
+            chest: values.chest,
+            waist: values.waist,
+            hips: values.hips,
+            arms: values.arms,
+            thighs: values.thighs,
+            notes: request.notes,
+        };
+        let measurement = match self.measurements.update_measurement(user_id, measurement_id, update_data).await {
+            Ok(m) => m,
             Err(err) => {
-                error!("Error deleting cardio log for user_id {}: {}", user_id, err);
-                PutResponse {
-                    user_id, id: None, success: false, message: format!("{}", err)
-                }
+                error!("Error updating body measurement for user_id {}: {}", user_id, err);
+                return Err(err);
             }
+        };
+        info!("Body measurement updated: {}", measurement.id);
+        Ok(measurement)
+    }
+
+    pub async fn delete_measurement(&self, user_id: i32, measurement_id: i32) -> ApiResult<PutResponse> {
+        if let Err(err) = self.measurements.delete_measurement(user_id, measurement_id).await {
+            error!("Error deleting body measurement for user_id {}: {}", user_id, err);
+            return Err(err);
         }
+        info!("Body measurement deleted: {}", measurement_id);
+        Ok(PutResponse {
+            user_id, id: Some(measurement_id), success: true, message: "Measurement Deleted".to_string()
+        })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,237 @@
+use std::{collections::HashMap, sync::Arc};
+use chrono::NaiveDate;
+use log::{debug, info};
+use crate::{db::{model::{NewPersonalRecord, PersonalRecord, SetType, UpdatePersonalRecord, WorkoutSet}, records::RecordsDB, workouts::WorkoutDB}, error::ApiResult, services::get_service::OneRepMaxFormula};
+
+pub const HEAVIEST_WEIGHT: &str = "heaviest_weight";
+pub const REPS_AT_WEIGHT: &str = "reps_at_weight";
+pub const ESTIMATED_1RM: &str = "estimated_1rm";
+pub const SESSION_VOLUME: &str = "session_volume";
+
+/// Sets logged without a session are grouped by the day they were performed on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum SessionKey{
+    Session(i32),
+    Day(NaiveDate),
+}
+
+impl SessionKey{
+    fn of(set: &WorkoutSet) -> Self{
+        match set.workout_session_id{
+            Some(id) => SessionKey::Session(id),
+            None => SessionKey::Day(set.performed_on),
+        }
+    }
+
+    fn of_record(record: &PersonalRecord) -> Self{
+        match record.workout_session_id{
+            Some(id) => SessionKey::Session(id),
+            None => SessionKey::Day(record.achieved_on),
+        }
+    }
+}
+
+/// Running bests for a single variation, fed sets in chronological order.
+#[derive(Default)]
+struct RecordTracker{
+    heaviest: f64,
+    best_e1rm: f64,
+    best_volume: f64,
+    reps_at_weight: HashMap<u64, i32>,
+}
+
+impl RecordTracker{
+    fn from_records(records: &[PersonalRecord]) -> Self{
+        let mut tracker = RecordTracker::default();
+        for record in records.iter(){
+            match record.record_type.as_str(){
+                HEAVIEST_WEIGHT => tracker.heaviest = tracker.heaviest.max(record.value),
+                ESTIMATED_1RM => tracker.best_e1rm = tracker.best_e1rm.max(record.value),
+                SESSION_VOLUME => tracker.best_volume = tracker.best_volume.max(record.value),
+                REPS_AT_WEIGHT => {
+                    tracker.reps_at_weight.entry(record.weight.to_bits())
+                        .and_modify(|r| *r = (*r).max(record.reps))
+                        .or_insert(record.reps);
+                }
+                _ => (),
+            }
+        }
+        tracker
+    }
+
+    fn evaluate(&mut self, set: &WorkoutSet, session_volume: f64) -> Vec<NewPersonalRecord>{
+        let mut broken = Vec::new();
+        // Warm-ups never count towards records
+        if set.weight <= 0.0 || set.reps <= 0 || set.set_type == SetType::Warmup.as_str() {
+            return broken;
+        }
+
+        if set.weight > self.heaviest {
+            self.heaviest = set.weight;
+            broken.push(Self::record(set, HEAVIEST_WEIGHT, set.weight));
+        }
+
+        let best_reps = self.reps_at_weight.entry(set.weight.to_bits()).or_insert(0);
+        if set.reps > *best_reps {
+            *best_reps = set.reps;
+            broken.push(Self::record(set, REPS_AT_WEIGHT, set.reps as f64));
+        }
+
+        let e1rm = OneRepMaxFormula::Epley.estimate(set.weight, set.reps);
+        if e1rm > self.best_e1rm {
+            self.best_e1rm = e1rm;
+            broken.push(Self::record(set, ESTIMATED_1RM, e1rm));
+        }
+
+        if session_volume > self.best_volume {
+            self.best_volume = session_volume;
+            broken.push(Self::record(set, SESSION_VOLUME, session_volume));
+        }
+        broken
+    }
+
+    fn record(set: &WorkoutSet, record_type: &str, value: f64) -> NewPersonalRecord{
+        NewPersonalRecord{
+            user_id: set.user_id,
+            variation_id: set.variation_id,
+            set_id: Some(set.id),
+            workout_session_id: set.workout_session_id,
+            record_type: record_type.to_string(),
+            value,
+            weight: set.weight,
+            reps: set.reps,
+            achieved_on: set.performed_on,
+        }
+    }
+}
+
+pub struct RecordsService{
+    records: Arc<RecordsDB>,
+    workout: Arc<WorkoutDB>,
+}
+
+impl RecordsService{
+    pub fn new(records: Arc<RecordsDB>, workout: Arc<WorkoutDB>) -> Self{
+        RecordsService {
+            records,
+            workout
+        }
+    }
+
+    /// Checks a freshly inserted set against the current bests and stores any PRs it breaks.
+    pub async fn record_new_set(&self, set: &WorkoutSet) -> ApiResult<Vec<PersonalRecord>>{
+        let existing = match self.records.get_records(set.user_id, Some(set.variation_id)).await{
+            Ok(r) => r,
+            Err(err) => return Err(err)
+        };
+
+        // A backdated set may supersede records set after it, so the history is replayed instead
+        if existing.iter().any(|r| r.achieved_on > set.performed_on) {
+            self.rebuild_records(set.user_id, set.variation_id).await?;
+            let records = self.records.get_records(set.user_id, Some(set.variation_id)).await?;
+            let broken: Vec<PersonalRecord> = records.into_iter().filter(|r| r.set_id == Some(set.id)).collect();
+            info!("{} personal record(s) broken by backdated set {} for user_id: {}", broken.len(), set.id, set.user_id);
+            return Ok(broken);
+        }
+
+        let history = match self.workout.get_variation_sets(set.user_id, set.variation_id).await{
+            Ok(h) => h,
+            Err(err) => return Err(err)
+        };
+
+        let key = SessionKey::of(set);
+        let session_volume: f64 = history.iter()
+            .filter(|s| SessionKey::of(s) == key)
+            .map(|s| s.weight * s.reps as f64)
+            .sum();
+
+        let mut tracker = RecordTracker::from_records(&existing);
+        let broken = tracker.evaluate(set, session_volume);
+        if broken.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Later sets in the session already holding the volume record raise that row instead of adding one
+        let current_volume = existing.iter()
+            .filter(|r| r.record_type == SESSION_VOLUME)
+            .max_by(|a, b| a.value.total_cmp(&b.value));
+
+        let mut new_records = Vec::new();
+        let mut inserts = Vec::new();
+        for record in broken.into_iter(){
+            match current_volume{
+                Some(current) if record.record_type == SESSION_VOLUME && SessionKey::of_record(current) == key => {
+                    let update = UpdatePersonalRecord{
+                        set_id: record.set_id,
+                        value: record.value,
+                        weight: record.weight,
+                        reps: record.reps,
+                    };
+                    match self.records.update_record(current.id, update).await{
+                        Ok(updated) => new_records.push(updated),
+                        Err(err) => return Err(err)
+                    }
+                }
+                _ => inserts.push(record),
+            }
+        }
+        match self.records.add_records(inserts).await{
+            Ok(inserted) => new_records.extend(inserted),
+            Err(err) => return Err(err)
+        }
+
+        info!("{} personal record(s) broken by set {} for user_id: {}", new_records.len(), set.id, set.user_id);
+        Ok(new_records)
+    }
+
+    /// Replays the whole history of a variation, used after sets are edited or removed.
+    pub async fn rebuild_records(&self, user_id: i32, variation_id: i32) -> ApiResult<()>{
+        let history = match self.workout.get_variation_sets(user_id, variation_id).await{
+            Ok(h) => h,
+            Err(err) => return Err(err)
+        };
+
+        let mut tracker = RecordTracker::default();
+        let mut volumes: HashMap<SessionKey, f64> = HashMap::new();
+        let mut records: Vec<NewPersonalRecord> = Vec::new();
+        let mut last_volume: Option<(SessionKey, usize)> = None;
+
+        for set in history.iter(){
+            let key = SessionKey::of(set);
+            let volume = volumes.entry(key.clone()).or_insert(0.0);
+            *volume += set.weight * set.reps as f64;
+
+            for record in tracker.evaluate(set, *volume).into_iter(){
+                if record.record_type == SESSION_VOLUME {
+                    if let Some((last_key, idx)) = &last_volume {
+                        if *last_key == key {
+                            records[*idx] = record;
+                            continue;
+                        }
+                    }
+                    last_volume = Some((key.clone(), records.len()));
+                }
+                records.push(record);
+            }
+        }
+
+        debug!("Rebuilt {} personal records for user_id: {}, variation_id: {}", records.len(), user_id, variation_id);
+        self.records.replace_records(user_id, variation_id, records).await
+    }
+
+    pub async fn rebuild_session_records(&self, user_id: i32, variation_ids: Vec<i32>) -> ApiResult<()>{
+        for variation_id in variation_ids.into_iter(){
+            self.rebuild_records(user_id, variation_id).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_session_variation_ids(&self, user_id: i32, session_id: i32) -> ApiResult<Vec<i32>>{
+        self.workout.get_session_variation_ids(user_id, session_id).await
+    }
+
+    pub async fn get_records(&self, user_id: i32, variation_id: Option<i32>) -> ApiResult<Vec<PersonalRecord>>{
+        info!("Fetching personal records for user_id: {}", user_id);
+        self.records.get_records(user_id, variation_id).await
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::Arc;
+use chrono::Utc;
+use log::{error, info};
+use crate::{api::routines::{RoutineRequest, StartRoutineQuery},
+            db::{logger::LoggerDB, routines::RoutineDB,
+                model::{CardioLog, NewCardioLog, NewRoutine, NewRoutineExercise, NewWorkoutSession, NewWorkoutSet, Routine, RoutineExercise, SetType, UpdateRoutine, WorkoutSession, WorkoutSet}},
+            error::{ApiError, ApiResult},
+            services::ownership_service::OwnershipService};
+
+pub struct RoutineService{
+    routines: Arc<RoutineDB>,
+    logger: Arc<LoggerDB>,
+    ownership: Arc<OwnershipService>,
+}
+
+impl RoutineService{
+    pub fn new(routines: Arc<RoutineDB>, logger: Arc<LoggerDB>, ownership: Arc<OwnershipService>) -> Self{
+        RoutineService{
+            routines,
+            logger,
+            ownership
+        }
+    }
+
+    pub async fn get_routines(&self, user_id: i32) -> ApiResult<Vec<(Routine, Vec<RoutineExercise>)>>{
+        info!("Fetching routines for user_id: {}", user_id);
+        self.routines.get_routines(user_id).await
+    }
+
+    pub async fn get_routine(&self, user_id: i32, routine_id: i32) -> ApiResult<(Routine, Vec<RoutineExercise>)>{
+        match self.routines.get_routine(user_id, routine_id).await?{
+            Some(r) => Ok(r),
+            None => Err(ApiError::NotFound("Routine not found".to_string())),
+        }
+    }
+
+    /// Checks every referenced exercise and numbers the entries in the order they were sent.
+    async fn build_exercises(&self, user_id: i32, request: &RoutineRequest) -> ApiResult<Vec<NewRoutineExercise>>{
+        let mut exercises = Vec::with_capacity(request.exercises.len());
+        for (i, e) in request.exercises.iter().enumerate(){
+            if let Some(variation_id) = e.variation_id {
+                self.ownership.check_variation(user_id, variation_id).await?;
+            }
+            if let Some(cardio_id) = e.cardio_exercise_id {
+                self.ownership.check_cardio_exercise(user_id, cardio_id).await?;
+            }
+            exercises.push(NewRoutineExercise{
+                routine_id: 0,
+                position: i as i32 + 1,
+                variation_id: e.variation_id,
+                cardio_exercise_id: e.cardio_exercise_id,
+                target_sets: e.target_sets.unwrap_or(1),
+                reps_min: e.reps_min,
+                reps_max: e.reps_max,
+                target_weight: e.target_weight,
+                rest_seconds: e.rest_seconds,
+                target_duration_minutes: e.target_duration_minutes,
+                notes: e.notes.clone(),
+            });
+        }
+        Ok(exercises)
+    }
+
+    pub async fn create_routine(&self, user_id: i32, request: RoutineRequest) -> ApiResult<(Routine, Vec<RoutineExercise>)>{
+        let exercises = self.build_exercises(user_id, &request).await?;
+        let routine = NewRoutine{
+            user_id,
+            name: request.name.trim().to_string(),
+            notes: request.notes,
+        };
+        match self.routines.add_routine(routine, exercises).await{
+            Ok(r) => {
+                info!("Routine {} created for user_id {}", r.0.id, user_id);
+                Ok(r)
+            }
+            Err(err) => {
+                error!("Error creating routine for user_id {}: {}", user_id, err);
+                Err(err)
+            }
+        }
+    }
+
+    pub async fn update_routine(&self, user_id: i32, routine_id: i32, request: RoutineRequest) -> ApiResult<(Routine, Vec<RoutineExercise>)>{
+        let exercises = self.build_exercises(user_id, &request).await?;
+        let data = UpdateRoutine{
+            name: request.name.trim().to_string(),
+            notes: request.notes,
+            updated_at: Utc::now().naive_utc(),
+        };
+        match self.routines.replace_routine(user_id, routine_id, data, exercises).await{
+            Ok(r) => {
+                info!("Routine {} updated", routine_id);
+                Ok(r)
+            }
+            Err(err) => {
+                error!("Error updating routine {} for user_id {}: {}", routine_id, user_id, err);
+                Err(err)
+            }
+        }
+    }
+
+    pub async fn delete_routine(&self, user_id: i32, routine_id: i32) -> ApiResult<()>{
+        if let Err(err) = self.routines.delete_routine(user_id, routine_id).await{
+            error!("Error deleting routine {} for user_id {}: {}", routine_id, user_id, err);
+            return Err(err);
+        }
+        info!("Routine deleted: {}", routine_id);
+        Ok(())
+    }
+
+    pub async fn start_routine(&self, user_id: i32, routine_id: i32, request: StartRoutineQuery) -> ApiResult<(WorkoutSession, Vec<WorkoutSet>, Vec<CardioLog>)>{
+        let (routine, exercises) = self.get_routine(user_id, routine_id).await?;
+        self.start_session(user_id, &routine, &exercises, request, None).await
+    }
+
+    /// Creates a session from the routine with one planned row per target set. Planned rows are
+    /// stored as not completed, so records and analytics ignore them until the user ticks them off.
+    /// Programs pass their own copy of the exercises with the prescribed weights filled in.
+    pub async fn start_session(&self, user_id: i32, routine: &Routine, exercises: &[RoutineExercise], request: StartRoutineQuery, program_day_id: Option<i32>) -> ApiResult<(WorkoutSession, Vec<WorkoutSet>, Vec<CardioLog>)>{
+        let routine_id = routine.id;
+        let start_time = request.start_time.unwrap_or_else(|| Utc::now().naive_utc());
+        let date = start_time.date();
+
+        let session = NewWorkoutSession{
+            user_id,
+            date,
+            title: Some(request.title.unwrap_or_else(|| routine.name.clone())),
+            notes: routine.notes.clone(),
+            start_time,
+            end_time: start_time,
+            program_day_id,
+        };
+
+        let mut sets = Vec::new();
+        let mut logs = Vec::new();
+        for exercise in exercises.iter(){
+            if let Some(variation_id) = exercise.variation_id {
+                for _ in 0..exercise.target_sets{
+                    sets.push(NewWorkoutSet{
+                        workout_session_id: None,
+                        user_id,
+                        variation_id,
+                        weight: exercise.target_weight.unwrap_or(0.0),
+                        reps: exercise.reps_max.or(exercise.reps_min).unwrap_or(0),
+                        performed_on: date,
+                        set_index: Some(sets.len() as i32 + 1),
+                        set_type: SetType::default().as_str().to_string(),
+                        rpe: None,
+                        rir: None,
+                        rest_seconds: exercise.rest_seconds,
+                        tempo: None,
+                        completed: false,
+                    });
+                }
+            }
+            if let Some(cardio_exercise_id) = exercise.cardio_exercise_id {
+                for _ in 0..exercise.target_sets{
+                    logs.push(NewCardioLog{
+                        workout_session_id: None,
+                        user_id,
+                        cardio_exercise_id,
+                        duration_minutes: exercise.target_duration_minutes.unwrap_or(0),
+                        performed_on: Some(date),
+                        distance_m: None,
+                        elevation_gain_m: None,
+                        avg_heart_rate: None,
+                        max_heart_rate: None,
+                        calories: None,
+                        started_at: None,
+                        completed: false,
+                    });
+                }
+            }
+        }
+
+        match self.logger.add_full_session(session, sets, logs).await{
+            Ok(r) => {
+                info!("Routine {} started as session {} for user_id {}", routine_id, r.0.id, user_id);
+                Ok(r)
+            }
+            Err(err) => {
+                error!("Error starting routine {} for user_id {}: {}", routine_id, user_id, err);
+                Err(err)
+            }
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use std::sync::Arc;
+use log::info;
+use crate::{db::search::{SearchDB, SearchHit}, error::ApiResult};
+
+pub struct SearchService{
+    search: Arc<SearchDB>,
+}
+
+impl SearchService{
+    pub fn new(search: Arc<SearchDB>) -> Self{
+        SearchService { search }
+    }
+
+    pub async fn search(&self, user_id: i32, terms: &str, limit: i64) -> ApiResult<Vec<SearchHit>>{
+        info!("Searching for user_id: {}", user_id);
+        self.search.search(user_id, terms.trim(), limit).await
+    }
+}
@@ -0,0 +1,233 @@
+use std::sync::Arc;
+use chrono::{Duration, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use crate::{db::{model::{ApiToken, NewApiToken, NewRefreshToken}, tokens::TokenDB, user::UserDB}, error::{ApiError, ApiResult}, services::{crypto::{generate_token, hash_token}, jwt_service::JwtService}};
+
+const REFRESH_TOKEN_DAYS: i64 = 30;
+/// Lets the extractor tell personal API tokens apart from JWTs in a Bearer header.
+pub const API_TOKEN_PREFIX: &str = "ftk_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope{
+    ReadOnly,
+    ReadWrite,
+}
+
+impl TokenScope{
+    pub fn as_str(&self) -> &'static str{
+        match self{
+            TokenScope::ReadOnly => "read_only",
+            TokenScope::ReadWrite => "read_write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self>{
+        match scope{
+            "read_only" => Some(TokenScope::ReadOnly),
+            "read_write" => Some(TokenScope::ReadWrite),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedApiToken{
+    #[serde(flatten)]
+    pub details: ApiToken,
+    /// Plain token, only ever returned on creation
+    pub token: String,
+}
+
+#[derive(Debug)]
+pub struct ApiTokenIdentity{
+    pub token_id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub scope: TokenScope,
+}
+
+#[derive(Debug)]
+pub struct SessionTokens{
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+pub struct SessionService{
+    tokens: Arc<TokenDB>,
+    user: Arc<UserDB>,
+    jwt: Arc<JwtService>,
+}
+
+impl SessionService{
+    pub fn new(tokens: Arc<TokenDB>, user: Arc<UserDB>, jwt: Arc<JwtService>) -> Self{
+        SessionService {
+            tokens,
+            user,
+            jwt
+        }
+    }
+
+    pub fn access_token_minutes(&self) -> i64{
+        self.jwt.expiration_minutes()
+    }
+
+    pub fn refresh_token_days(&self) -> i64{
+        REFRESH_TOKEN_DAYS
+    }
+
+    /// Issues an access token and the first refresh token of a new family after login.
+    pub async fn start_session(&self, user_id: i32, username: &str) -> ApiResult<SessionTokens>{
+        let family_id = generate_token();
+        let access_token = match self.jwt.generate_token(username, user_id, Some(&family_id)){
+            Ok(t) => t,
+            Err(err) => return Err(ApiError::Internal(format!("Error generating access token: {}", err)))
+        };
+        let refresh_token = generate_token();
+        let new_token = NewRefreshToken{
+            user_id,
+            family_id,
+            token_hash: hash_token(&refresh_token),
+            expires_at: (Utc::now() + Duration::days(REFRESH_TOKEN_DAYS)).naive_utc(),
+        };
+        self.tokens.add_refresh_token(new_token).await?;
+
+        info!("Session started for user_id: {}", user_id);
+        Ok(SessionTokens { access_token, refresh_token })
+    }
+
+    /// Exchanges a refresh token for a new access/refresh pair. Returns `None` when the token
+    /// is unknown, expired, revoked or already used; reuse revokes the whole family.
+    pub async fn refresh_session(&self, refresh_token: &str) -> ApiResult<Option<SessionTokens>>{
+        let stored = match self.tokens.get_refresh_token(&hash_token(refresh_token)).await{
+            Ok(Some(t)) => t,
+            Ok(None) => {
+                warn!("Unknown refresh token presented");
+                return Ok(None);
+            }
+            Err(err) => return Err(err)
+        };
+
+        if stored.revoked_at.is_some() {
+            warn!("Revoked refresh token presented for user_id: {}", stored.user_id);
+            return Ok(None);
+        }
+        if stored.used_at.is_some() {
+            warn!("Refresh token reuse detected for user_id: {}, revoking family", stored.user_id);
+            self.tokens.revoke_family(&stored.family_id).await?;
+            return Ok(None);
+        }
+        if stored.expires_at <= Utc::now().naive_utc() {
+            warn!("Expired refresh token presented for user_id: {}", stored.user_id);
+            return Ok(None);
+        }
+
+        let user = match self.user.get_user_by_id(stored.user_id).await{
+            Ok(Some(u)) => u,
+            Ok(None) => return Ok(None),
+            Err(err) => return Err(err)
+        };
+
+        let refresh_token = generate_token();
+        let next = NewRefreshToken{
+            user_id: stored.user_id,
+            family_id: stored.family_id.clone(),
+            token_hash: hash_token(&refresh_token),
+            expires_at: (Utc::now() + Duration::days(REFRESH_TOKEN_DAYS)).naive_utc(),
+        };
+        match self.tokens.rotate_refresh_token(stored.id, next).await{
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                warn!("Refresh token consumed concurrently for user_id: {}, revoking family", stored.user_id);
+                self.tokens.revoke_family(&stored.family_id).await?;
+                return Ok(None);
+            }
+            Err(err) => return Err(err)
+        }
+
+        let access_token = match self.jwt.generate_token(&user.username, user.id, Some(&stored.family_id)){
+            Ok(t) => t,
+            Err(err) => return Err(ApiError::Internal(format!("Error generating access token: {}", err)))
+        };
+        info!("Session refreshed for user_id: {}", user.id);
+        Ok(Some(SessionTokens { access_token, refresh_token }))
+    }
+
+    pub async fn end_session(&self, refresh_token: &str) -> ApiResult<()>{
+        let stored = match self.tokens.get_refresh_token(&hash_token(refresh_token)).await{
+            Ok(Some(t)) => t,
+            Ok(None) => return Ok(()),
+            Err(err) => return Err(err)
+        };
+        self.tokens.revoke_family(&stored.family_id).await?;
+        info!("Session ended for user_id: {}", stored.user_id);
+        Ok(())
+    }
+
+    /// Ends the session an access token belongs to, for logout requests that don't carry the
+    /// refresh cookie. Expired access tokens are accepted since only their signature matters here.
+    pub async fn end_session_for_access_token(&self, access_token: &str) -> ApiResult<()>{
+        let claims = match self.jwt.validate_token_ignoring_expiry(access_token){
+            Ok(t) => t.claims,
+            Err(_) => return Ok(()),
+        };
+        if let Some(family_id) = claims.fid {
+            self.tokens.revoke_family(&family_id).await?;
+            info!("Session ended for user_id: {}", claims.id);
+        }
+        Ok(())
+    }
+
+    pub async fn create_api_token(&self, user_id: i32, name: &str, scope: TokenScope) -> ApiResult<CreatedApiToken>{
+        let token = format!("{}{}", API_TOKEN_PREFIX, generate_token());
+        let new_token = NewApiToken{
+            user_id,
+            name,
+            token_hash: hash_token(&token),
+            scope: scope.as_str(),
+        };
+        let details = match self.tokens.add_api_token(new_token).await{
+            Ok(t) => t,
+            Err(err) => return Err(err)
+        };
+        info!("API token {} created for user_id: {}", details.id, user_id);
+        Ok(CreatedApiToken { details, token })
+    }
+
+    pub async fn get_api_tokens(&self, user_id: i32) -> ApiResult<Vec<ApiToken>>{
+        self.tokens.get_api_tokens(user_id).await
+    }
+
+    pub async fn revoke_api_token(&self, user_id: i32, token_id: i32) -> ApiResult<()>{
+        self.tokens.revoke_api_token(user_id, token_id).await?;
+        info!("API token {} revoked for user_id: {}", token_id, user_id);
+        Ok(())
+    }
+
+    /// Access tokens are checked statelessly, so this catches ones issued before the account was deleted.
+    pub async fn user_exists(&self, user_id: i32) -> ApiResult<bool>{
+        self.user.user_exists(user_id).await
+    }
+
+    pub async fn authenticate_api_token(&self, token: &str) -> ApiResult<Option<ApiTokenIdentity>>{
+        let (api_token, username) = match self.tokens.use_api_token(&hash_token(token)).await{
+            Ok(Some(found)) => found,
+            Ok(None) => return Ok(None),
+            Err(err) => return Err(err)
+        };
+        let scope = match TokenScope::parse(&api_token.scope){
+            Some(s) => s,
+            None => {
+                warn!("API token {} has unknown scope '{}'", api_token.id, api_token.scope);
+                return Ok(None);
+            }
+        };
+        Ok(Some(ApiTokenIdentity{
+            token_id: api_token.id,
+            user_id: api_token.user_id,
+            username,
+            scope,
+        }))
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+use chrono::{DateTime, NaiveDateTime};
+use roxmltree::{Document, Node};
+use serde::Deserialize;
+use crate::error::{ApiError, ApiResult};
+
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+/// Climbs smaller than this are treated as GPS/barometer noise when summing elevation gain.
+const ELEVATION_NOISE_M: f64 = 2.0;
+/// Seconds between the Unix epoch and the FIT epoch (1989-12-31T00:00:00Z).
+const FIT_EPOCH_OFFSET: i64 = 631_065_600;
+const FIT_RECORD_MESSAGE: u16 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackFormat{
+    Gpx,
+    Tcx,
+    Fit,
+}
+
+impl TrackFormat{
+    /// Sniffs the format from the file contents, used when the client doesn't name one.
+    pub fn detect(data: &[u8]) -> Option<Self>{
+        if data.len() >= 12 && &data[8..12] == b".FIT" {
+            return Some(TrackFormat::Fit);
+        }
+        let head = String::from_utf8_lossy(&data[..data.len().min(2048)]);
+        if head.contains("<gpx") {
+            Some(TrackFormat::Gpx)
+        } else if head.contains("<TrainingCenterDatabase") {
+            Some(TrackFormat::Tcx)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str{
+        match self{
+            TrackFormat::Gpx => "GPX",
+            TrackFormat::Tcx => "TCX",
+            TrackFormat::Fit => "FIT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParsedPoint{
+    pub time: Option<NaiveDateTime>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub elevation_m: Option<f64>,
+    pub heart_rate: Option<i32>,
+    /// Cumulative distance when the device recorded one (TCX and FIT)
+    pub distance_m: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedTrack{
+    pub name: Option<String>,
+    pub points: Vec<ParsedPoint>,
+}
+
+#[derive(Debug)]
+pub struct TrackStats{
+    pub started_at: NaiveDateTime,
+    pub duration_seconds: i64,
+    pub distance_m: f64,
+    pub elevation_gain_m: f64,
+    pub avg_heart_rate: Option<i32>,
+    pub max_heart_rate: Option<i32>,
+}
+
+pub fn parse(format: TrackFormat, data: &[u8]) -> ApiResult<ParsedTrack>{
+    match format{
+        TrackFormat::Gpx => parse_gpx(&xml_text(data)?),
+        TrackFormat::Tcx => parse_tcx(&xml_text(data)?),
+        TrackFormat::Fit => parse_fit(data),
+    }
+}
+
+fn xml_text(data: &[u8]) -> ApiResult<String>{
+    match std::str::from_utf8(data){
+        Ok(text) => Ok(text.trim_start_matches('\u{feff}').to_string()),
+        Err(_) => Err(ApiError::BadRequest("File is not valid UTF-8".to_string())),
+    }
+}
+
+fn parse_time(text: &str) -> Option<NaiveDateTime>{
+    DateTime::parse_from_rfc3339(text.trim()).ok().map(|d| d.naive_utc())
+}
+
+fn child_text<'a>(node: Node<'a, 'a>, name: &str) -> Option<&'a str>{
+    node.children().find(|c| c.tag_name().name() == name).and_then(|c| c.text())
+}
+
+fn child_number(node: Node, name: &str) -> Option<f64>{
+    child_text(node, name).and_then(|t| t.trim().parse().ok())
+}
+
+fn parse_gpx(text: &str) -> ApiResult<ParsedTrack>{
+    let doc = match Document::parse(text){
+        Ok(d) => d,
+        Err(err) => return Err(ApiError::BadRequest(format!("Invalid GPX file: {}", err))),
+    };
+    let root = doc.root_element();
+    if root.tag_name().name() != "gpx" {
+        return Err(ApiError::BadRequest("Invalid GPX file: missing <gpx> root".to_string()));
+    }
+
+    let name = root.descendants()
+        .find(|n| n.tag_name().name() == "trk")
+        .and_then(|trk| child_text(trk, "name"))
+        .map(|n| n.trim().to_string());
+    let points = root.descendants()
+        .filter(|n| n.tag_name().name() == "trkpt")
+        .map(|pt| ParsedPoint{
+            time: child_text(pt, "time").and_then(parse_time),
+            latitude: pt.attribute("lat").and_then(|v| v.parse().ok()),
+            longitude: pt.attribute("lon").and_then(|v| v.parse().ok()),
+            elevation_m: child_number(pt, "ele"),
+            // Garmin's TrackPointExtension, namespaces vary between exporters so match on the local name
+            heart_rate: pt.descendants()
+                .find(|n| n.tag_name().name() == "hr")
+                .and_then(|n| n.text())
+                .and_then(|t| t.trim().parse().ok()),
+            distance_m: None,
+        })
+        .collect();
+    Ok(ParsedTrack{ name, points })
+}
+
+fn parse_tcx(text: &str) -> ApiResult<ParsedTrack>{
+    let doc = match Document::parse(text){
+        Ok(d) => d,
+        Err(err) => return Err(ApiError::BadRequest(format!("Invalid TCX file: {}", err))),
+    };
+    let root = doc.root_element();
+    if root.tag_name().name() != "TrainingCenterDatabase" {
+        return Err(ApiError::BadRequest("Invalid TCX file: missing <TrainingCenterDatabase> root".to_string()));
+    }
+
+    let name = root.descendants()
+        .find(|n| n.tag_name().name() == "Activity")
+        .and_then(|a| a.attribute("Sport"))
+        .map(|s| s.to_string());
+    let points = root.descendants()
+        .filter(|n| n.tag_name().name() == "Trackpoint")
+        .map(|pt| {
+            let position = pt.children().find(|c| c.tag_name().name() == "Position");
+            ParsedPoint{
+                time: child_text(pt, "Time").and_then(parse_time),
+                latitude: position.and_then(|p| child_number(p, "LatitudeDegrees")),
+                longitude: position.and_then(|p| child_number(p, "LongitudeDegrees")),
+                elevation_m: child_number(pt, "AltitudeMeters"),
+                heart_rate: pt.children()
+                    .find(|c| c.tag_name().name() == "HeartRateBpm")
+                    .and_then(|hr| child_number(hr, "Value"))
+                    .map(|v| v.round() as i32),
+                distance_m: child_number(pt, "DistanceMeters"),
+            }
+        })
+        .collect();
+    Ok(ParsedTrack{ name, points })
+}
+
+struct FitDefinition{
+    big_endian: bool,
+    global: u16,
+    /// (field number, size in bytes)
+    fields: Vec<(u8, usize)>,
+    developer_size: usize,
+}
+
+struct FitReader<'a>{
+    data: &'a [u8],
+    pos: usize,
+    end: usize,
+}
+
+impl<'a> FitReader<'a>{
+    fn take(&mut self, n: usize) -> ApiResult<&'a [u8]>{
+        if self.pos + n > self.end {
+            return Err(ApiError::BadRequest("Invalid FIT file: truncated record".to_string()));
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> ApiResult<u8>{
+        Ok(self.take(1)?[0])
+    }
+}
+
+/// Reads a 1, 2 or 4 byte unsigned value, anything else isn't a field we use.
+fn fit_uint(bytes: &[u8], big_endian: bool) -> Option<u32>{
+    let value = match bytes.len(){
+        1 => bytes[0] as u32,
+        2 => {
+            let b = [bytes[0], bytes[1]];
+            if big_endian { u16::from_be_bytes(b) as u32 } else { u16::from_le_bytes(b) as u32 }
+        }
+        4 => {
+            let b = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            if big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) }
+        }
+        _ => return None,
+    };
+    // All bits set is FIT's "invalid" marker for unsigned types
+    let invalid = match bytes.len(){ 1 => 0xFF, 2 => 0xFFFF, _ => 0xFFFF_FFFF };
+    (value != invalid).then_some(value)
+}
+
+fn fit_semicircles(bytes: &[u8], big_endian: bool) -> Option<f64>{
+    let raw = fit_uint(bytes, big_endian)? as i32;
+    (raw != i32::MAX).then(|| raw as f64 * (180.0 / 2_147_483_648.0))
+}
+
+fn fit_time(timestamp: u32) -> Option<NaiveDateTime>{
+    DateTime::from_timestamp(timestamp as i64 + FIT_EPOCH_OFFSET, 0).map(|d| d.naive_utc())
+}
+
+/// Minimal FIT decoder: walks definition and data messages and keeps only `record` messages,
+/// which hold the per-second samples. Developer fields are skipped and the CRC isn't checked.
+fn parse_fit(data: &[u8]) -> ApiResult<ParsedTrack>{
+    if data.len() < 12 || &data[8..12] != b".FIT" {
+        return Err(ApiError::BadRequest("Invalid FIT file: bad header".to_string()));
+    }
+    let header_size = data[0] as usize;
+    let data_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    let mut reader = FitReader{
+        data,
+        pos: header_size,
+        end: (header_size + data_size).min(data.len()),
+    };
+
+    let mut definitions: HashMap<u8, FitDefinition> = HashMap::new();
+    let mut points = Vec::new();
+    let mut last_timestamp: u32 = 0;
+
+    while reader.pos < reader.end {
+        let header = reader.byte()?;
+        let (local, compressed_offset) = if header & 0x80 != 0 {
+            ((header >> 5) & 0x03, Some((header & 0x1F) as u32))
+        } else {
+            (header & 0x0F, None)
+        };
+
+        if compressed_offset.is_none() && header & 0x40 != 0 {
+            let has_developer_fields = header & 0x20 != 0;
+            reader.take(1)?;
+            let big_endian = reader.byte()? == 1;
+            let global_bytes = reader.take(2)?;
+            let global = if big_endian { u16::from_be_bytes([global_bytes[0], global_bytes[1]]) } else { u16::from_le_bytes([global_bytes[0], global_bytes[1]]) };
+            let field_count = reader.byte()? as usize;
+            let fields = reader.take(field_count * 3)?
+                .chunks(3)
+                .map(|f| (f[0], f[1] as usize))
+                .collect();
+            let developer_size = if has_developer_fields {
+                let count = reader.byte()? as usize;
+                reader.take(count * 3)?.chunks(3).map(|f| f[1] as usize).sum()
+            } else {
+                0
+            };
+            definitions.insert(local, FitDefinition{ big_endian, global, fields, developer_size });
+            continue;
+        }
+
+        let definition = match definitions.get(&local){
+            Some(d) => d,
+            None => return Err(ApiError::BadRequest("Invalid FIT file: data message without a definition".to_string())),
+        };
+        let mut point = ParsedPoint::default();
+        let mut timestamp = compressed_offset.map(|offset| {
+            let mut t = (last_timestamp & !0x1F) + offset;
+            if offset < (last_timestamp & 0x1F) {
+                t += 0x20;
+            }
+            t
+        });
+        for (number, size) in definition.fields.iter(){
+            let bytes = reader.take(*size)?;
+            let be = definition.big_endian;
+            match number{
+                253 => timestamp = fit_uint(bytes, be).or(timestamp),
+                0 => point.latitude = fit_semicircles(bytes, be),
+                1 => point.longitude = fit_semicircles(bytes, be),
+                2 if point.elevation_m.is_none() => point.elevation_m = fit_uint(bytes, be).map(|v| v as f64 / 5.0 - 500.0),
+                78 => point.elevation_m = fit_uint(bytes, be).map(|v| v as f64 / 5.0 - 500.0).or(point.elevation_m),
+                3 => point.heart_rate = fit_uint(bytes, be).map(|v| v as i32),
+                5 => point.distance_m = fit_uint(bytes, be).map(|v| v as f64 / 100.0),
+                _ => (),
+            }
+        }
+        reader.take(definition.developer_size)?;
+
+        if let Some(t) = timestamp {
+            last_timestamp = t;
+        }
+        if definition.global == FIT_RECORD_MESSAGE {
+            point.time = timestamp.and_then(fit_time);
+            points.push(point);
+        }
+    }
+    Ok(ParsedTrack{ name: None, points })
+}
+
+fn haversine_m(a: (f64, f64), b: (f64, f64)) -> f64{
+    let (lat1, lon1) = (a.0.to_radians(), a.1.to_radians());
+    let (lat2, lon2) = (b.0.to_radians(), b.1.to_radians());
+    let h = ((lat2 - lat1) / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+impl ParsedTrack{
+    /// Derives the summary stored on the cardio log. Needs at least two timestamped points.
+    pub fn stats(&self) -> ApiResult<TrackStats>{
+        let times: Vec<NaiveDateTime> = self.points.iter().filter_map(|p| p.time).collect();
+        let (start, end) = match (times.iter().min(), times.iter().max()){
+            (Some(s), Some(e)) if e > s => (*s, *e),
+            _ => return Err(ApiError::Validation("Track needs at least two timestamped points".to_string())),
+        };
+
+        // Prefer the device's own odometer, fall back to summing the GPS path
+        let recorded_distance = self.points.iter().filter_map(|p| p.distance_m).fold(None, |max: Option<f64>, d| Some(max.map_or(d, |m| m.max(d))));
+        let distance_m = match recorded_distance{
+            Some(d) => d,
+            None => {
+                let coords: Vec<(f64, f64)> = self.points.iter()
+                    .filter_map(|p| Some((p.latitude?, p.longitude?)))
+                    .collect();
+                coords.windows(2).map(|w| haversine_m(w[0], w[1])).sum()
+            }
+        };
+
+        let mut elevation_gain_m = 0.0;
+        let mut reference: Option<f64> = None;
+        for elevation in self.points.iter().filter_map(|p| p.elevation_m){
+            match reference{
+                Some(r) if elevation - r >= ELEVATION_NOISE_M => {
+                    elevation_gain_m += elevation - r;
+                    reference = Some(elevation);
+                }
+                Some(r) if elevation < r => reference = Some(elevation),
+                None => reference = Some(elevation),
+                _ => (),
+            }
+        }
+
+        let heart_rates: Vec<i32> = self.points.iter().filter_map(|p| p.heart_rate).filter(|hr| *hr > 0).collect();
+        let avg_heart_rate = (!heart_rates.is_empty())
+            .then(|| (heart_rates.iter().map(|hr| *hr as f64).sum::<f64>() / heart_rates.len() as f64).round() as i32);
+
+        Ok(TrackStats{
+            started_at: start,
+            duration_seconds: (end - start).num_seconds(),
+            distance_m,
+            elevation_gain_m,
+            avg_heart_rate,
+            max_heart_rate: heart_rates.iter().max().copied(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn semicircles(degrees: f64) -> [u8; 4]{
+        ((degrees * 2_147_483_648.0 / 180.0).round() as i32).to_le_bytes()
+    }
+
+    /// Little-endian FIT file with one `record` definition (timestamp, lat, long, heart rate)
+    /// followed by a data message per sample.
+    fn fit_file(samples: &[(u32, f64, f64, u8)]) -> Vec<u8>{
+        let mut records = vec![0x40, 0, 0, FIT_RECORD_MESSAGE as u8, 0, 4, 253, 4, 0x86, 0, 4, 0x85, 1, 4, 0x85, 3, 1, 0x02];
+        for (timestamp, lat, lon, hr) in samples.iter(){
+            records.push(0x00);
+            records.extend_from_slice(&timestamp.to_le_bytes());
+            records.extend_from_slice(&semicircles(*lat));
+            records.extend_from_slice(&semicircles(*lon));
+            records.push(*hr);
+        }
+        let mut file = vec![12, 0x10, 0, 0];
+        file.extend_from_slice(&(records.len() as u32).to_le_bytes());
+        file.extend_from_slice(b".FIT");
+        file.extend_from_slice(&records);
+        file
+    }
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+  <trk><name>Morning Run</name><trkseg>
+    <trkpt lat="0.0" lon="0.0"><ele>10</ele><time>2024-05-01T07:00:00Z</time><extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>120</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions></trkpt>
+    <trkpt lat="0.0" lon="0.005"><ele>11</ele><time>2024-05-01T07:02:30Z</time><extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>140</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions></trkpt>
+    <trkpt lat="0.0" lon="0.01"><ele>15</ele><time>2024-05-01T07:05:00Z</time><extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>160</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+
+    const TCX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">
+  <Activities><Activity Sport="Biking"><Lap><Track>
+    <Trackpoint><Time>2024-05-01T07:00:00Z</Time><Position><LatitudeDegrees>0</LatitudeDegrees><LongitudeDegrees>0</LongitudeDegrees></Position><DistanceMeters>0</DistanceMeters><HeartRateBpm><Value>110</Value></HeartRateBpm></Trackpoint>
+    <Trackpoint><Time>2024-05-01T07:10:00Z</Time><Position><LatitudeDegrees>0</LatitudeDegrees><LongitudeDegrees>0.05</LongitudeDegrees></Position><DistanceMeters>5000</DistanceMeters><HeartRateBpm><Value>130</Value></HeartRateBpm></Trackpoint>
+  </Track></Lap></Activity></Activities>
+</TrainingCenterDatabase>"#;
+
+    #[test]
+    fn detects_formats(){
+        assert_eq!(TrackFormat::detect(&fit_file(&[])), Some(TrackFormat::Fit));
+        assert_eq!(TrackFormat::detect(GPX.as_bytes()), Some(TrackFormat::Gpx));
+        assert_eq!(TrackFormat::detect(TCX.as_bytes()), Some(TrackFormat::Tcx));
+        assert_eq!(TrackFormat::detect(b"date,distance\n"), None);
+    }
+
+    #[test]
+    fn parses_minimal_fit(){
+        let data = fit_file(&[(1_000_000_000, 0.0, 0.0, 100), (1_000_000_060, 0.0, 0.01, 150)]);
+        let track = parse(TrackFormat::Fit, &data).unwrap();
+        assert_eq!(track.points.len(), 2);
+        assert_eq!(track.points[1].heart_rate, Some(150));
+        assert!((track.points[1].longitude.unwrap() - 0.01).abs() < 1e-6);
+
+        let stats = track.stats().unwrap();
+        assert_eq!(stats.started_at, fit_time(1_000_000_000).unwrap());
+        assert_eq!(stats.duration_seconds, 60);
+        assert!((stats.distance_m - 1111.95).abs() < 1.0, "distance {}", stats.distance_m);
+        assert_eq!(stats.avg_heart_rate, Some(125));
+        assert_eq!(stats.max_heart_rate, Some(150));
+    }
+
+    #[test]
+    fn rejects_truncated_fit(){
+        let data = fit_file(&[(1_000_000_000, 0.0, 0.0, 100), (1_000_000_060, 0.0, 0.01, 150)]);
+        for len in [data.len() - 3, 20, 11, 0] {
+            assert!(parse(TrackFormat::Fit, &data[..len]).is_err(), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn rejects_fit_data_without_definition(){
+        let mut data = fit_file(&[]);
+        data.truncate(12);
+        data.extend_from_slice(&[0x01, 0, 0]);
+        data[4] = 3;
+        assert!(parse(TrackFormat::Fit, &data).is_err());
+    }
+
+    #[test]
+    fn parses_gpx_with_haversine_distance(){
+        let track = parse(TrackFormat::Gpx, GPX.as_bytes()).unwrap();
+        assert_eq!(track.name.as_deref(), Some("Morning Run"));
+        assert_eq!(track.points.len(), 3);
+
+        let stats = track.stats().unwrap();
+        assert_eq!(stats.duration_seconds, 300);
+        // 0.01 degrees of longitude on the equator
+        assert!((stats.distance_m - 1111.95).abs() < 1.0, "distance {}", stats.distance_m);
+        // The 1 m step is noise, so the climb to 15 m is measured from the 10 m start
+        assert_eq!(stats.elevation_gain_m, 5.0);
+        assert_eq!(stats.avg_heart_rate, Some(140));
+    }
+
+    #[test]
+    fn parses_tcx_with_recorded_distance(){
+        let track = parse(TrackFormat::Tcx, TCX.as_bytes()).unwrap();
+        assert_eq!(track.name.as_deref(), Some("Biking"));
+
+        let stats = track.stats().unwrap();
+        assert_eq!(stats.duration_seconds, 600);
+        // The device odometer wins over the ~5.56 km GPS path
+        assert_eq!(stats.distance_m, 5000.0);
+        assert_eq!(stats.max_heart_rate, Some(130));
+    }
+
+    #[test]
+    fn stats_need_two_timestamps(){
+        let track = ParsedTrack{ name: None, points: vec![ParsedPoint::default()] };
+        assert!(track.stats().is_err());
+    }
+}
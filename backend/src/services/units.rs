@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use crate::{db::model::{BodyMeasurement, PersonalRecord, RoutineExercise, User, WorkoutSet}, services::records_service::REPS_AT_WEIGHT};
+
+const KG_PER_LB: f64 = 0.453_592_37;
+const CM_PER_IN: f64 = 2.54;
+const M_PER_MI: f64 = 1609.344;
+const M_PER_FT: f64 = 0.3048;
+
+/// Outgoing values are rounded so converted weights don't show float noise.
+fn round2(value: f64) -> f64{
+    (value * 100.0).round() / 100.0
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeightUnit{
+    #[default]
+    Kg,
+    Lb,
+}
+
+impl WeightUnit{
+    pub fn as_str(&self) -> &'static str{
+        match self{
+            WeightUnit::Kg => "kg",
+            WeightUnit::Lb => "lb",
+        }
+    }
+
+    /// Unknown values fall back to kg, the column is constrained so this only guards old rows.
+    pub fn parse(value: &str) -> Self{
+        match value{
+            "lb" => WeightUnit::Lb,
+            _ => WeightUnit::Kg,
+        }
+    }
+
+    pub fn to_kg(self, value: f64) -> f64{
+        match self{
+            WeightUnit::Kg => value,
+            WeightUnit::Lb => value * KG_PER_LB,
+        }
+    }
+
+    pub fn kg_to(self, kg: f64) -> f64{
+        match self{
+            WeightUnit::Kg => round2(kg),
+            WeightUnit::Lb => round2(kg / KG_PER_LB),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LengthUnit{
+    #[default]
+    Cm,
+    In,
+}
+
+impl LengthUnit{
+    pub fn as_str(&self) -> &'static str{
+        match self{
+            LengthUnit::Cm => "cm",
+            LengthUnit::In => "in",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self{
+        match value{
+            "in" => LengthUnit::In,
+            _ => LengthUnit::Cm,
+        }
+    }
+
+    pub fn to_cm(self, value: f64) -> f64{
+        match self{
+            LengthUnit::Cm => value,
+            LengthUnit::In => value * CM_PER_IN,
+        }
+    }
+
+    pub fn cm_to(self, cm: f64) -> f64{
+        match self{
+            LengthUnit::Cm => round2(cm),
+            LengthUnit::In => round2(cm / CM_PER_IN),
+        }
+    }
+}
+
+/// Unit for cardio distances, elevation follows it as metres or feet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceUnit{
+    #[default]
+    Km,
+    Mi,
+}
+
+impl DistanceUnit{
+    pub fn as_str(&self) -> &'static str{
+        match self{
+            DistanceUnit::Km => "km",
+            DistanceUnit::Mi => "mi",
+        }
+    }
+
+    /// Imperial users get miles, everyone else kilometres.
+    pub fn for_length(length: LengthUnit) -> Self{
+        match length{
+            LengthUnit::Cm => DistanceUnit::Km,
+            LengthUnit::In => DistanceUnit::Mi,
+        }
+    }
+
+    pub fn metres_per_unit(self) -> f64{
+        match self{
+            DistanceUnit::Km => 1000.0,
+            DistanceUnit::Mi => M_PER_MI,
+        }
+    }
+
+    pub fn to_m(self, value: f64) -> f64{
+        value * self.metres_per_unit()
+    }
+
+    pub fn m_to(self, m: f64) -> f64{
+        round2(m / self.metres_per_unit())
+    }
+
+    pub fn elevation_to_m(self, value: f64) -> f64{
+        match self{
+            DistanceUnit::Km => value,
+            DistanceUnit::Mi => value * M_PER_FT,
+        }
+    }
+
+    /// Elevation is given in metres alongside kilometres and feet alongside miles.
+    pub fn elevation_unit(self) -> &'static str{
+        match self{
+            DistanceUnit::Km => "m",
+            DistanceUnit::Mi => "ft",
+        }
+    }
+
+    pub fn m_to_elevation(self, m: f64) -> f64{
+        match self{
+            DistanceUnit::Km => round2(m),
+            DistanceUnit::Mi => round2(m / M_PER_FT),
+        }
+    }
+}
+
+/// A user's preferred units. Storage is always metric, values are converted when they enter
+/// and leave the API, and an explicit unit on a request wins over the preference.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Units{
+    pub weight: WeightUnit,
+    pub length: LengthUnit,
+    pub distance: DistanceUnit,
+}
+
+impl Units{
+    pub fn of(user: &User) -> Self{
+        let length = LengthUnit::parse(&user.length_unit);
+        Units {
+            weight: WeightUnit::parse(&user.weight_unit),
+            length,
+            distance: DistanceUnit::for_length(length),
+        }
+    }
+
+    pub fn weight_in(&self, value: f64, unit: Option<WeightUnit>) -> f64{
+        unit.unwrap_or(self.weight).to_kg(value)
+    }
+
+    pub fn weight_out(&self, kg: f64) -> f64{
+        self.weight.kg_to(kg)
+    }
+
+    pub fn length_in(&self, value: f64, unit: Option<LengthUnit>) -> f64{
+        unit.unwrap_or(self.length).to_cm(value)
+    }
+
+    pub fn length_out(&self, cm: f64) -> f64{
+        self.length.cm_to(cm)
+    }
+
+    pub fn distance_in(&self, value: f64, unit: Option<DistanceUnit>) -> f64{
+        unit.unwrap_or(self.distance).to_m(value)
+    }
+
+    pub fn distance_out(&self, m: f64) -> f64{
+        self.distance.m_to(m)
+    }
+
+    pub fn elevation_in(&self, value: f64, unit: Option<DistanceUnit>) -> f64{
+        unit.unwrap_or(self.distance).elevation_to_m(value)
+    }
+
+    pub fn elevation_out(&self, m: f64) -> f64{
+        self.distance.m_to_elevation(m)
+    }
+
+    pub fn set_out(&self, mut set: WorkoutSet) -> WorkoutSet{
+        set.weight = self.weight_out(set.weight);
+        set
+    }
+
+    pub fn routine_exercise_out(&self, mut exercise: RoutineExercise) -> RoutineExercise{
+        exercise.target_weight = exercise.target_weight.map(|w| self.weight_out(w));
+        exercise
+    }
+
+    /// Every record type except reps-at-weight holds a weight (or volume) in its value.
+    pub fn record_out(&self, mut record: PersonalRecord) -> PersonalRecord{
+        if record.record_type != REPS_AT_WEIGHT {
+            record.value = self.weight_out(record.value);
+        }
+        record.weight = self.weight_out(record.weight);
+        record
+    }
+
+    pub fn user_out(&self, mut user: User) -> User{
+        user.weight = user.weight.map(|w| self.weight_out(w));
+        user.height = user.height.map(|h| self.length_out(h));
+        user
+    }
+
+    pub fn measurement_out(&self, mut m: BodyMeasurement) -> BodyMeasurement{
+        m.weight = m.weight.map(|w| self.weight_out(w));
+        for girth in [&mut m.neck, &mut m.chest, &mut m.waist, &mut m.hips, &mut m.arms, &mut m.thighs]{
+            *girth = girth.map(|g| self.length_out(g));
+        }
+        m
+    }
+}
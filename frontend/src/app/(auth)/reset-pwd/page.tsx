@@ -1,5 +1,5 @@
 "use client";
-import React, { useState } from "react";
+import React, { useEffect, useState } from "react";
 import { AuthInput } from '../../components/AuthInput'
 import { AuthButton } from '../../components/AuthButton'
 import { ArrowLeftIcon, EyeIcon, EyeSlashIcon } from "@heroicons/react/24/outline";
@@ -7,25 +7,61 @@ import { useRouter } from "next/navigation";
 
 const ResetPage = () => {
   const [showPassword, setShowPassword] = useState(false);
-  const [username, setUserName] = useState("");
+  const [token, setToken] = useState("");
+  const [email, setEmail] = useState("");
   const [password, setPassword] = useState("");
   const [confirmpassword, setConfirmPassword] = useState("");
   const [error, setError] = useState("");
+  const [notice, setNotice] = useState("");
 
   const router = useRouter();
 
-  const handleReset = async (e: React.FormEvent<HTMLFormElement>) => {
+  useEffect(() => {
+    setToken(new URLSearchParams(window.location.search).get("token") ?? "");
+  }, []);
+
+  const handleRequest = async (e: React.FormEvent<HTMLFormElement>) => {
     e.preventDefault();
     setError("");
+    setNotice("");
 
     try {
       const response = await fetch(process.env.API_URL + 'api/forgot-password', {
+        method: "POST",
+        headers: {
+          "Content-Type": "application/json",
+        },
+        body: JSON.stringify({ email })
+      });
+      if (!response.ok) {
+        throw new Error("Request failed");
+      }
+
+      const data = await response.json();
+      if (data.success) {
+        setNotice(data.message);
+      } else {
+        setError(data.message || "Failed to request password reset");
+      }
+    }
+    catch (error) {
+      console.error("Error:", error);
+      setError("Something went wrong. Please try again.");
+    }
+  };
+
+  const handleReset = async (e: React.FormEvent<HTMLFormElement>) => {
+    e.preventDefault();
+    setError("");
+
+    try {
+      const response = await fetch(process.env.API_URL + 'api/reset-password', {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
         },
         body: JSON.stringify({
-          username,
+          token,
           password,
           confirmpassword
         })
@@ -37,7 +73,7 @@ const ResetPage = () => {
       const data = await response.json();
       if (data.success) {
         console.log("", data.message);
-        router.back();
+        router.push('/login');
       } else {
         setError(data.message || "Failed to reset password");
       }
@@ -92,7 +128,7 @@ const ResetPage = () => {
               Reset Password
             </h1>
             <p className="mt-2 text-gray-500">
-              Enter your credentials to set a new password
+              {token ? "Choose a new password for your account" : "Enter your email and we'll send you a reset link"}
             </p>
           </div>
 
@@ -102,12 +138,27 @@ const ResetPage = () => {
             </div>
           )}
 
-          <form onSubmit={handleReset} className="space-y-6">
+          {notice && (
+            <div className="p-3 text-sm text-green-600 bg-green-50 rounded-lg border border-green-100 font-medium">
+              {notice}
+            </div>
+          )}
+
+          {!token ? (
+          <form onSubmit={handleRequest} className="space-y-6">
             <div className="space-y-4">
-              <AuthInput type='text'
-                value={username} onChange={(e: React.ChangeEvent<HTMLInputElement>) => setUserName(e.target.value)}
-                placeholder='Enter username' required />
+              <AuthInput type='email'
+                value={email} onChange={(e: React.ChangeEvent<HTMLInputElement>) => setEmail(e.target.value)}
+                placeholder='Enter email' required />
+            </div>
 
+            <AuthButton type='submit' className="w-full py-3 bg-black hover:bg-gray-800 text-white rounded-xl font-bold transition-all">
+              Send Reset Link
+            </AuthButton>
+          </form>
+          ) : (
+          <form onSubmit={handleReset} className="space-y-6">
+            <div className="space-y-4">
               <AuthInput type='password'
                 value={password} onChange={(e: React.ChangeEvent<HTMLInputElement>) => setPassword(e.target.value)}
                 placeholder='Enter New password' required />
@@ -131,6 +182,7 @@ const ResetPage = () => {
               Reset Password
             </AuthButton>
           </form>
+          )}
         </div>
       </div>
     </div>
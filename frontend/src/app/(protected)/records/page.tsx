@@ -47,7 +47,7 @@ export default function RecordsPage() {
             });
             if (!res.ok) throw new Error("Failed to fetch history");
             const data = await res.json();
-            setSessions(data);
+            setSessions(data.sessions);
         } catch (err) {
             setError("Could not load workout history");
             console.error(err);
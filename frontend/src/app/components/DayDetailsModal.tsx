@@ -111,7 +111,7 @@ export default function DayDetailsModal({ isOpen, onClose, date }: DayDetailsMod
             });
 
             if (res.ok) {
-                const sessionData: WorkoutSession[] = await res.json();
+                const sessionData: WorkoutSession[] = (await res.json()).sessions;
                 setSessions(sessionData);
 
                 // Fetch details for each session